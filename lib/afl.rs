@@ -0,0 +1,213 @@
+use crate::{
+    error::BajzelError,
+    evaluator::{evaluate_file, structure::FieldDefinition, ProgramEnv},
+    generator::Gen,
+    minimizer::{dissect, render},
+};
+use rand::rngs::StdRng;
+use rand::seq::{IteratorRandom, SliceRandom};
+use rand::{Rng, SeedableRng};
+use std::ffi::{c_uint, c_void};
+use std::path::Path;
+
+/// Environment variable naming the `.fuzl` file
+///
+pub const FUZL_ENV_VAR: &str = "BAJZEL_FUZL";
+
+/// Probability of taking a value of a field from the input to splice with
+/// (when AFL++ gives one) instead of generating a new one
+///
+const SPLICE_PROBABILITY: f64 = 0.5;
+
+/// AFL++ custom mutator changing fields of inputs as a `.fuzl` file
+/// describes them, instead of flipping bits blindly
+///
+/// The library is built as a shared object exposing the custom mutator
+/// API (`afl_custom_init`, `afl_custom_fuzz`, `afl_custom_deinit`):
+///
+/// ```sh
+/// cargo build --release
+/// BAJZEL_FUZL=cmd.fuzl \
+/// AFL_CUSTOM_MUTATOR_LIBRARY=target/release/libbajzel_lib.so \
+/// AFL_CUSTOM_MUTATOR_ONLY=1 \
+///     afl-fuzz -i in -o out -- ./target @@
+/// ```
+///
+/// Every input is a whole session (all messages followed by TERM), like
+/// the ones fed by `bajzel run`. An input from the queue is dissected into
+/// fields (see `minimize`) and a single field gets a new value, so the
+/// rest of the input, which got AFL++ to new coverage, stays as it was.
+/// Inputs not following the `.fuzl` file (such as initial seeds) are
+/// replaced by generated ones.
+///
+pub struct Mutator {
+    env: ProgramEnv,
+    gen: Gen,
+
+    /// Source of choices which field to mutate and how
+    ///
+    rng: StdRng,
+
+    /// Last generated input (AFL++ reads it after `afl_custom_fuzz`)
+    ///
+    out: Vec<u8>,
+}
+
+impl Mutator {
+    /// Load a `.fuzl` file, with random choices determined by a seed
+    ///
+    pub fn load<P>(path: P, seed: u64) -> Result<Self, BajzelError>
+    where
+        P: AsRef<Path>,
+    {
+        let env = evaluate_file(path)?;
+        let mut gen = Gen::default();
+        gen.set_seed(seed);
+        Ok(Self {
+            env,
+            gen,
+            rng: StdRng::seed_from_u64(seed),
+            out: vec![],
+        })
+    }
+
+    /// Mutate an input (`buf`), possibly splicing it with another one
+    /// (`add_buf`), cut to `max_size` bytes
+    ///
+    pub fn mutate(
+        &mut self,
+        buf: &[u8],
+        add_buf: Option<&[u8]>,
+        max_size: usize,
+    ) -> Result<&[u8], BajzelError> {
+        self.out = match self.mutate_field(buf, add_buf, max_size)? {
+            Some(out) => out,
+            None => self.gen.generate_input(&self.env)?,
+        };
+        self.out.truncate(max_size);
+        Ok(&self.out)
+    }
+
+    /// Replace a value of a random field of an input, `None` when the
+    /// input doesn't follow the program or has nothing to mutate
+    ///
+    fn mutate_field(
+        &mut self,
+        buf: &[u8],
+        add_buf: Option<&[u8]>,
+        max_size: usize,
+    ) -> Result<Option<Vec<u8>>, BajzelError> {
+        let mut messages = match dissect(&self.env, buf) {
+            Ok(messages) => messages,
+            Err(_) => return Ok(None),
+        };
+        let spliced = add_buf.and_then(|x| dissect(&self.env, x).ok());
+        let parts = messages.iter_mut().flat_map(|x| x.parts.iter_mut());
+        let mutable = parts.filter(|x| {
+            !matches!(
+                x.def,
+                FieldDefinition::ConstString(_) | FieldDefinition::Captured(_)
+            )
+        });
+        let part = match mutable.choose(&mut self.rng) {
+            Some(part) => part,
+            None => return Ok(None),
+        };
+        let donors: Vec<_> = spliced
+            .iter()
+            .flatten()
+            .flat_map(|x| x.parts.iter())
+            .filter(|x| std::ptr::eq(x.def, part.def))
+            .collect();
+        part.value = match donors.choose(&mut self.rng) {
+            Some(donor) if self.rng.gen_bool(SPLICE_PROBABILITY) => {
+                donor.value.clone()
+            }
+            _ => self.gen.generate_field(&self.env, part.def, max_size)?,
+        };
+        let term = &self.env.get_generator()?.term;
+        Ok(Some(render(&messages, term)))
+    }
+}
+
+/// Load a `.fuzl` file named by `BAJZEL_FUZL`
+///
+/// Returns NULL (which makes AFL++ abort) when the file can't be loaded.
+///
+/// # Safety
+///
+/// Called by AFL++ only.
+///
+#[no_mangle]
+pub unsafe extern "C" fn afl_custom_init(
+    _afl: *mut c_void,
+    seed: c_uint,
+) -> *mut c_void {
+    let path = match std::env::var(FUZL_ENV_VAR) {
+        Ok(path) => path,
+        Err(_) => {
+            eprintln!("[-] {} is not set", FUZL_ENV_VAR);
+            return std::ptr::null_mut();
+        }
+    };
+    match Mutator::load(&path, seed as u64) {
+        Ok(mutator) => Box::into_raw(Box::new(mutator)) as *mut c_void,
+        Err(e) => {
+            eprintln!("[-] {}: {:?}", path, e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Mutate an input picked by AFL++ (`buf`) following the `.fuzl` file,
+/// possibly splicing it with another one (`add_buf`)
+///
+/// # Safety
+///
+/// `data` must come from `afl_custom_init`, `buf` and `add_buf` must be
+/// valid for reads of their sizes (`add_buf` may be NULL) and `out_buf`
+/// must be valid for writes. The output stays valid until the next call.
+///
+#[no_mangle]
+pub unsafe extern "C" fn afl_custom_fuzz(
+    data: *mut c_void,
+    buf: *mut u8,
+    buf_size: usize,
+    out_buf: *mut *const u8,
+    add_buf: *mut u8,
+    add_buf_size: usize,
+    max_size: usize,
+) -> usize {
+    let mutator = &mut *(data as *mut Mutator);
+    let buf = match buf.is_null() {
+        true => &[],
+        false => std::slice::from_raw_parts(buf, buf_size),
+    };
+    let add_buf = match add_buf.is_null() {
+        true => None,
+        false => Some(std::slice::from_raw_parts(add_buf, add_buf_size)),
+    };
+    match mutator.mutate(buf, add_buf, max_size) {
+        Ok(out) => {
+            *out_buf = out.as_ptr();
+            out.len()
+        }
+        Err(e) => {
+            eprintln!("[-] Generate error: {:?}", e);
+            0
+        }
+    }
+}
+
+/// Free the mutator
+///
+/// # Safety
+///
+/// `data` must come from `afl_custom_init` and can't be used afterwards.
+///
+#[no_mangle]
+pub unsafe extern "C" fn afl_custom_deinit(data: *mut c_void) {
+    if !data.is_null() {
+        drop(Box::from_raw(data as *mut Mutator));
+    }
+}
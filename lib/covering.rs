@@ -0,0 +1,373 @@
+use crate::{
+    error::BajzelError,
+    evaluator::{
+        structure::{FieldDefinition, GroupDefinition, ValueDist},
+        ProgramEnv,
+    },
+};
+use itertools::Itertools;
+use rand::distributions::Alphanumeric;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use std::collections::HashSet;
+
+/// Set of test cases in which every combination of `strength` field choices
+/// shows up at least once
+///
+/// Instead of every possible value, each field contributes a few choices:
+/// - numbers: RANGE boundaries, small values (-1, 0, 1) and a middle value
+/// - strings and bytes: lengths at LEN boundaries and in the middle
+/// - listed values: WEIGHTS or DICT entries
+///
+/// Fields of referenced groups are covered as if they were defined in the
+/// group itself. Inputs shorter than OUT_MIN are padded with zero bytes.
+///
+pub struct CoveringArray {
+    params: Vec<Vec<Choice>>,
+    rows: Vec<Vec<usize>>,
+    out_min: usize,
+}
+
+/// Single choice of a field
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Choice {
+    /// Exact value
+    ///
+    Value(Vec<u8>),
+
+    /// Random alphanumeric string of a given length
+    ///
+    Alphanumeric(usize),
+
+    /// Random bytes of a given length
+    ///
+    Bytes(usize),
+}
+
+/// Build covering array of a given strength (2 for pairwise) for a group
+/// selected by the GENERATE section
+///
+/// Returns an error if GENERATE selects a SEQUENCE, or if any test case is
+/// longer than OUT_MAX, as cutting it would lose the combinations of the
+/// fields at its end.
+///
+pub fn covering_array(
+    env: &ProgramEnv,
+    strength: usize,
+) -> Result<CoveringArray, BajzelError> {
+    let gen = env.get_generator()?;
+    if env.find_sequence(&gen.name).is_some() {
+        return Err(BajzelError::Syntax(format!(
+            "sequence {} can't be covered, GENERATE a group instead",
+            gen.name
+        )));
+    }
+    let group = env.get_group(&gen.name)?;
+    let mut params = Vec::new();
+    collect_choices(env, group, &mut params)?;
+    let sizes = params.iter().map(Vec::len).collect_vec();
+    let rows = build_rows(&sizes, strength);
+    for row in &rows {
+        let len: usize = params
+            .iter()
+            .zip(row)
+            .map(|(choices, index)| choices[*index].len())
+            .sum();
+        if len > gen.out_max as usize {
+            return Err(BajzelError::Syntax(format!(
+                "test case of {} bytes is longer than OUT_MAX ({})",
+                len, gen.out_max
+            )));
+        }
+    }
+    Ok(CoveringArray {
+        rows,
+        params,
+        out_min: gen.out_min as usize,
+    })
+}
+
+impl CoveringArray {
+    /// Number of test cases
+    ///
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Render test cases into inputs
+    ///
+    /// Random parts (string contents, bytes) are generated anew on each call.
+    ///
+    pub fn inputs(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.rows.iter().map(|row| {
+            let mut bytes = Vec::new();
+            for (choices, index) in self.params.iter().zip(row) {
+                choices[*index].write(&mut bytes);
+            }
+            bytes.resize(bytes.len().max(self.out_min), 0);
+            bytes
+        })
+    }
+}
+
+impl Choice {
+    fn len(&self) -> usize {
+        match self {
+            Choice::Value(x) => x.len(),
+            Choice::Alphanumeric(len) | Choice::Bytes(len) => *len,
+        }
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        let mut rng = thread_rng();
+        match self {
+            Choice::Value(x) => bytes.extend_from_slice(x),
+            Choice::Alphanumeric(len) => {
+                bytes.extend((&mut rng).sample_iter(&Alphanumeric).take(*len))
+            }
+            Choice::Bytes(len) => {
+                bytes.extend((0..*len).map(|_| rng.gen::<u8>()))
+            }
+        }
+    }
+}
+
+/// Collect choices of every field of a group, flattening referenced groups
+///
+fn collect_choices(
+    env: &ProgramEnv,
+    group: &GroupDefinition,
+    params: &mut Vec<Vec<Choice>>,
+) -> Result<(), BajzelError> {
+    for field in group.fields_iter() {
+        let choices = match &field.def {
+            FieldDefinition::ConstString(x) => {
+                vec![Choice::Value(x.as_bytes().to_vec())]
+            }
+            // Nothing is captured without a target
+            FieldDefinition::Captured(_) => vec![Choice::Value(vec![])],
+            FieldDefinition::TextNumber(x) => {
+                number_classes(x.min_value, x.max_value, &x.dist)
+                    .into_iter()
+                    .map(|value| Choice::Value(x.encode(value)))
+                    .collect()
+            }
+            FieldDefinition::ByteNumber(x) => {
+                number_classes(x.min_value, x.max_value, &x.dist)
+                    .into_iter()
+                    .map(|value| Choice::Value(x.encode(value)))
+                    .collect()
+            }
+            FieldDefinition::AsciiString(x) => match &x.dict {
+                Some(dict) => dict_choices(dict.entries()),
+                None => length_classes(x.length_min, x.length_max, &x.dist)
+                    .into_iter()
+                    .map(Choice::Alphanumeric)
+                    .collect(),
+            },
+            FieldDefinition::Bytes(x) => match &x.dict {
+                Some(dict) => dict_choices(dict.entries()),
+                None => length_classes(x.length_min, x.length_max, &x.dist)
+                    .into_iter()
+                    .map(Choice::Bytes)
+                    .collect(),
+            },
+            FieldDefinition::Ref(x) => {
+                let name = x
+                    .group
+                    .as_ref()
+                    .ok_or(BajzelError::NotConstructedProperly)?;
+                collect_choices(env, env.get_group(name)?, params)?;
+                continue;
+            }
+        };
+        params.push(choices);
+    }
+    Ok(())
+}
+
+fn dict_choices(entries: &[Vec<u8>]) -> Vec<Choice> {
+    entries
+        .iter()
+        .cloned()
+        .map(Choice::Value)
+        .unique()
+        .collect()
+}
+
+/// Values listed by WEIGHTS or ENUM (if any)
+///
+fn listed_values(dist: &ValueDist) -> Option<Vec<i128>> {
+    match dist {
+        ValueDist::Weights(weights) => Some(
+            weights
+                .iter()
+                .filter(|(_, weight)| *weight > 0)
+                .map(|(value, _)| *value)
+                .sorted()
+                .dedup()
+                .collect(),
+        ),
+        ValueDist::Enum(values) => {
+            Some(values.iter().copied().sorted().dedup().collect())
+        }
+        _ => None,
+    }
+}
+
+fn number_classes(min: i128, max: i128, dist: &ValueDist) -> Vec<i128> {
+    if let Some(values) = listed_values(dist) {
+        return values;
+    }
+    [min, min + 1, -1, 0, 1, min + (max - min) / 2, max - 1, max]
+        .into_iter()
+        .filter(|x| (min..=max).contains(x))
+        .sorted()
+        .dedup()
+        .collect()
+}
+
+fn length_classes(min: usize, max: usize, dist: &ValueDist) -> Vec<usize> {
+    if let Some(values) = listed_values(dist) {
+        return values.into_iter().map(|x| x as usize).collect();
+    }
+    let (min, max) = (min as i128, max as i128);
+    [min, min + 1, min + (max - min) / 2, max - 1, max]
+        .into_iter()
+        .filter(|x| (min..=max).contains(x))
+        .sorted()
+        .dedup()
+        .map(|x| x as usize)
+        .collect()
+}
+
+/// Tuple of `(param, choice)` pairs sorted by param
+///
+type Tuple = Vec<(usize, usize)>;
+
+/// Number of candidate rows built before picking the best one
+///
+const CANDIDATE_ROWS: usize = 16;
+
+/// Greedy construction of a covering array (AETG-like)
+///
+/// Several candidate rows are built and the one covering the most of the
+/// remaining tuples is kept, until every tuple is covered.
+///
+fn build_rows(sizes: &[usize], strength: usize) -> Vec<Vec<usize>> {
+    if sizes.contains(&0) {
+        return vec![];
+    }
+    let strength = strength.clamp(1, sizes.len().max(1));
+    let mut uncovered: HashSet<Tuple> = (0..sizes.len())
+        .combinations(strength)
+        .flat_map(|params| {
+            params
+                .iter()
+                .map(|p| 0..sizes[*p])
+                .multi_cartesian_product()
+                .map(move |choices| {
+                    params.iter().copied().zip(choices).collect::<Tuple>()
+                })
+        })
+        .collect();
+
+    let mut rng = thread_rng();
+    let mut rows = Vec::new();
+    if sizes.is_empty() {
+        rows.push(vec![]);
+    }
+    while !uncovered.is_empty() {
+        let row = (0..CANDIDATE_ROWS)
+            .map(|_| candidate_row(sizes, strength, &uncovered, &mut rng))
+            .max_by_key(|row| covered_by(row, strength, &uncovered).len())
+            .expect("at least one candidate");
+        for tuple in covered_by(&row, strength, &uncovered) {
+            uncovered.remove(&tuple);
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+/// Build a row starting from a random uncovered tuple, then give the
+/// remaining params (in random order) choices covering the most tuples
+///
+fn candidate_row<R>(
+    sizes: &[usize],
+    strength: usize,
+    uncovered: &HashSet<Tuple>,
+    rng: &mut R,
+) -> Vec<usize>
+where
+    R: Rng,
+{
+    let mut row: Vec<Option<usize>> = vec![None; sizes.len()];
+    let seed = uncovered
+        .iter()
+        .nth(rng.gen_range(0..uncovered.len()))
+        .expect("index within bounds");
+    for (param, choice) in seed {
+        row[*param] = Some(*choice);
+    }
+    let mut free = (0..sizes.len()).filter(|p| row[*p].is_none()).collect_vec();
+    free.shuffle(rng);
+    for param in free {
+        let gains = (0..sizes[param])
+            .map(|choice| gain(&row, param, choice, strength, uncovered))
+            .collect_vec();
+        let best = *gains.iter().max().expect("params have choices");
+        let best_choices = (0..sizes[param])
+            .filter(|choice| gains[*choice] == best)
+            .collect_vec();
+        row[param] = best_choices.choose(rng).copied();
+    }
+    row.into_iter().map(|x| x.unwrap_or(0)).collect()
+}
+
+/// Uncovered tuples present in a row
+///
+fn covered_by(
+    row: &[usize],
+    strength: usize,
+    uncovered: &HashSet<Tuple>,
+) -> Vec<Tuple> {
+    (0..row.len())
+        .combinations(strength)
+        .map(|params| params.iter().map(|p| (*p, row[*p])).collect::<Tuple>())
+        .filter(|tuple| uncovered.contains(tuple))
+        .collect()
+}
+
+/// Number of uncovered tuples a choice would cover together with the params
+/// already assigned in a row
+///
+fn gain(
+    row: &[Option<usize>],
+    param: usize,
+    choice: usize,
+    strength: usize,
+    uncovered: &HashSet<Tuple>,
+) -> usize {
+    let assigned = (0..row.len())
+        .filter(|p| *p != param && row[*p].is_some())
+        .collect_vec();
+    assigned
+        .into_iter()
+        .combinations(strength - 1)
+        .filter(|others| {
+            let mut tuple = others
+                .iter()
+                .map(|p| (*p, row[*p].expect("assigned")))
+                .collect::<Tuple>();
+            tuple.push((param, choice));
+            tuple.sort_unstable();
+            uncovered.contains(&tuple)
+        })
+        .count()
+}
@@ -0,0 +1,128 @@
+use crate::error::BajzelError;
+use nom::branch::alt;
+use nom::bytes::complete::{is_not, take, take_while1};
+use nom::character::complete::{char, digit1, space0};
+use nom::combinator::{all_consuming, map, map_res, opt, value};
+use nom::multi::fold_many0;
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
+use nom::IResult;
+use std::path::Path;
+
+/// Collection of known tokens (keywords, magic values, etc.)
+///
+/// Dictionary files follow AFL dictionary syntax:
+///
+/// ```text
+/// # Comments and empty lines are ignored
+/// kw_get="GET"
+/// kw_crlf@1="\x0d\x0a"
+/// "SELECT"
+/// ```
+///
+/// Supported escape sequences are `\\`, `\"` and `\xNN`.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dictionary {
+    entries: Vec<Vec<u8>>,
+}
+
+impl Dictionary {
+    /// Parse dictionary from a string
+    ///
+    pub fn parse(input: &str) -> Result<Self, BajzelError> {
+        let mut entries = Vec::new();
+        for (no, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (_, entry) =
+                all_consuming(parse_entry)(line).map_err(|_| {
+                    BajzelError::Syntax(format!(
+                        "dictionary: malformed entry in line {}",
+                        no + 1
+                    ))
+                })?;
+            entries.push(entry);
+        }
+        Ok(Self { entries })
+    }
+
+    /// Read dictionary from a file
+    ///
+    pub fn load<P>(path: P) -> Result<Self, BajzelError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let input = std::fs::read_to_string(path).map_err(|e| {
+            BajzelError::Io(format!("{}: {}", path.display(), e))
+        })?;
+        Self::parse(input.as_str())
+    }
+
+    /// Add entries of other dictionary to this one
+    ///
+    pub fn extend(&mut self, other: Dictionary) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn entries(&self) -> &[Vec<u8>] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Parse a single dictionary entry
+///
+/// Input: `[keyword[@level]=]"value"`
+///
+fn parse_entry(input: &str) -> IResult<&str, Vec<u8>> {
+    preceded(
+        opt(terminated(
+            pair(
+                take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+                opt(preceded(char('@'), digit1)),
+            ),
+            tuple((space0, char('='), space0)),
+        )),
+        delimited(char('"'), parse_value, char('"')),
+    )(input)
+}
+
+/// Parse contents of a quoted value, resolving escape sequences
+///
+fn parse_value(input: &str) -> IResult<&str, Vec<u8>> {
+    fold_many0(
+        alt((
+            map(is_not("\\\""), |x: &str| x.as_bytes().to_vec()),
+            map(parse_escaped, |x| vec![x]),
+        )),
+        Vec::new,
+        |mut acc, x| {
+            acc.extend(x);
+            acc
+        },
+    )(input)
+}
+
+fn parse_escaped(input: &str) -> IResult<&str, u8> {
+    preceded(
+        char('\\'),
+        alt((
+            value(b'\\', char('\\')),
+            value(b'"', char('"')),
+            preceded(
+                char('x'),
+                map_res(take(2usize), |x: &str| u8::from_str_radix(x, 16)),
+            ),
+        )),
+    )(input)
+}
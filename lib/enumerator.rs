@@ -0,0 +1,412 @@
+use crate::{
+    error::BajzelError,
+    evaluator::{
+        structure::{FieldDefinition, GroupDefinition, ValueDist},
+        ProgramEnv,
+    },
+};
+use itertools::Itertools;
+use std::collections::HashSet;
+
+/// Characters used by random strings (the same set as generator uses)
+///
+static ALPHANUMERIC: &[u8] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Exhaustive walk over all inputs described by a program
+///
+/// Every field has a finite domain (RANGE of a number, LEN and alphabet of
+/// a string, entries of a dictionary, etc.) and the enumeration goes through
+/// the Cartesian product of all of them, last field changing the fastest.
+///
+/// Inputs that don't fit in `OUT_MIN..=OUT_MAX` are skipped, and so are
+/// inputs produced before, as adjacent fields of variable length can give
+/// the same bytes in several combinations (such as `""` + `"0"` and `"0"` +
+/// `""`).
+///
+pub struct Enumeration<'a> {
+    domains: Vec<Domain<'a>>,
+    sizes: Vec<Option<u128>>,
+    counter: Vec<u128>,
+    out_min: usize,
+    out_max: usize,
+    done: bool,
+
+    /// Inputs produced so far, kept only when combinations are ambiguous
+    ///
+    seen: Option<HashSet<Vec<u8>>>,
+
+    /// Maximal number of combinations to walk through
+    ///
+    limit: Option<u128>,
+    walked: u128,
+    skipped: u128,
+}
+
+/// Finite set of values a single field can take
+///
+enum Domain<'a> {
+    /// Explicit list of values
+    ///
+    Values(Vec<Vec<u8>>),
+
+    /// Numbers from `min..=max`, encoded by a given function
+    ///
+    Numbers {
+        min: i128,
+        max: i128,
+        encode: Box<dyn Fn(i128) -> Vec<u8> + 'a>,
+    },
+
+    /// All strings of given lengths made of a given alphabet
+    ///
+    Strings {
+        alphabet: Alphabet,
+        lengths: Vec<usize>,
+    },
+}
+
+enum Alphabet {
+    Alphanumeric,
+    Bytes,
+}
+
+/// Prepare enumeration of a group selected by the GENERATE section
+///
+/// Returns an error if GENERATE selects a SEQUENCE, as messages of
+/// a session aren't enumerated.
+///
+pub fn enumerate(env: &ProgramEnv) -> Result<Enumeration<'_>, BajzelError> {
+    let gen = env.get_generator()?;
+    if env.find_sequence(&gen.name).is_some() {
+        return Err(BajzelError::Syntax(format!(
+            "sequence {} can't be enumerated, GENERATE a group instead",
+            gen.name
+        )));
+    }
+    let group = env.get_group(&gen.name)?;
+    let mut domains = Vec::new();
+    Domain::from_group(env, group, &mut domains)?;
+    let sizes = domains.iter().map(Domain::size).collect::<Vec<_>>();
+    let done = sizes.contains(&Some(0));
+    let ambiguous = domains
+        .iter()
+        .filter(|domain| !domain.is_fixed_width())
+        .count()
+        > 1;
+    Ok(Enumeration {
+        counter: vec![0; domains.len()],
+        domains,
+        sizes,
+        out_min: gen.out_min as usize,
+        out_max: gen.out_max as usize,
+        done,
+        seen: ambiguous.then(HashSet::new),
+        limit: None,
+        walked: 0,
+        skipped: 0,
+    })
+}
+
+impl<'a> Enumeration<'a> {
+    /// Number of combinations to walk through, which is an upper bound of
+    /// the number of inputs (duplicates and inputs not fitting in
+    /// OUT_MIN/OUT_MAX are counted too)
+    ///
+    /// Returns `None` when the number does not fit in `u128`.
+    ///
+    pub fn combination_count(&self) -> Option<u128> {
+        self.sizes
+            .iter()
+            .try_fold(1u128, |acc, size| acc.checked_mul((*size)?))
+    }
+
+    /// Stop after walking through a given number of combinations, whether
+    /// they were produced or skipped
+    ///
+    pub fn with_limit(mut self, limit: u128) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Number of combinations walked through so far
+    ///
+    pub fn walked(&self) -> u128 {
+        self.walked
+    }
+
+    /// Number of combinations skipped so far (as duplicates or not fitting
+    /// in OUT_MIN/OUT_MAX)
+    ///
+    pub fn skipped(&self) -> u128 {
+        self.skipped
+    }
+
+    /// Whether all combinations were walked through (`false` when stopped
+    /// by the limit)
+    ///
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Move counter to the next combination
+    ///
+    fn advance(&mut self) {
+        for (pos, size) in self.sizes.iter().enumerate().rev() {
+            self.counter[pos] += 1;
+            if Some(self.counter[pos]) != *size {
+                return;
+            }
+            self.counter[pos] = 0;
+        }
+        self.done = true;
+    }
+
+    fn current(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (domain, index) in self.domains.iter().zip(&self.counter) {
+            domain.write_value(*index, &mut bytes);
+        }
+        bytes
+    }
+}
+
+impl<'a> Iterator for Enumeration<'a> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done && self.limit.is_none_or(|x| self.walked < x) {
+            let bytes = self.current();
+            self.advance();
+            self.walked += 1;
+            let fits = (self.out_min..=self.out_max).contains(&bytes.len());
+            let unique = match &mut self.seen {
+                Some(seen) if fits => seen.insert(bytes.clone()),
+                _ => true,
+            };
+            if fits && unique {
+                return Some(bytes);
+            }
+            self.skipped += 1;
+        }
+        None
+    }
+}
+
+impl<'a> Domain<'a> {
+    /// Collect domains of all fields of a group, flattening referenced
+    /// groups
+    ///
+    fn from_group(
+        env: &'a ProgramEnv,
+        group: &'a GroupDefinition,
+        domains: &mut Vec<Domain<'a>>,
+    ) -> Result<(), BajzelError> {
+        for field in group.fields_iter() {
+            match &field.def {
+                FieldDefinition::Ref(x) => {
+                    let name = x
+                        .group
+                        .as_ref()
+                        .ok_or(BajzelError::NotConstructedProperly)?;
+                    Self::from_group(env, env.get_group(name)?, domains)?;
+                }
+                def => domains.push(Self::from_field(def)),
+            }
+        }
+        Ok(())
+    }
+
+    fn from_field(def: &'a FieldDefinition) -> Self {
+        match def {
+            FieldDefinition::ConstString(x) => {
+                Domain::Values(vec![x.as_bytes().to_vec()])
+            }
+            // Nothing is captured without a target
+            FieldDefinition::Captured(_) => Domain::Values(vec![vec![]]),
+            FieldDefinition::TextNumber(x) => {
+                Self::numbers(x.min_value, x.max_value, &x.dist, |v| {
+                    x.encode(v)
+                })
+            }
+            FieldDefinition::ByteNumber(x) => {
+                Self::numbers(x.min_value, x.max_value, &x.dist, |v| {
+                    x.encode(v)
+                })
+            }
+            FieldDefinition::AsciiString(x) => match &x.dict {
+                Some(dict) => Self::entries(dict.entries()),
+                None => Self::strings(
+                    Alphabet::Alphanumeric,
+                    x.length_min,
+                    x.length_max,
+                    &x.dist,
+                ),
+            },
+            FieldDefinition::Bytes(x) => match &x.dict {
+                Some(dict) => Self::entries(dict.entries()),
+                None => Self::strings(
+                    Alphabet::Bytes,
+                    x.length_min,
+                    x.length_max,
+                    &x.dist,
+                ),
+            },
+            FieldDefinition::Ref(_) => {
+                unreachable!("references are flattened by from_group")
+            }
+        }
+    }
+
+    /// Distinct entries of a dictionary, in their order
+    ///
+    fn entries(entries: &[Vec<u8>]) -> Self {
+        Domain::Values(entries.iter().unique().cloned().collect())
+    }
+
+    /// Numbers from RANGE, or only the listed ones when WEIGHTS or ENUM are
+    /// used
+    ///
+    fn numbers<F>(min: i128, max: i128, dist: &ValueDist, encode: F) -> Self
+    where
+        F: Fn(i128) -> Vec<u8> + 'a,
+    {
+        match dist {
+            ValueDist::Weights(weights) => {
+                let mut values = weights
+                    .iter()
+                    .filter(|(_, weight)| *weight > 0)
+                    .map(|(value, _)| *value)
+                    .collect::<Vec<_>>();
+                values.sort_unstable();
+                values.dedup();
+                Domain::Values(values.into_iter().map(encode).collect())
+            }
+            ValueDist::Enum(values) => {
+                let mut values = values.clone();
+                values.sort_unstable();
+                values.dedup();
+                Domain::Values(values.into_iter().map(encode).collect())
+            }
+            _ => Domain::Numbers {
+                min,
+                max,
+                encode: Box::new(encode),
+            },
+        }
+    }
+
+    /// Strings of lengths from LEN, or only the listed ones when WEIGHTS
+    /// are used
+    ///
+    fn strings(
+        alphabet: Alphabet,
+        len_min: usize,
+        len_max: usize,
+        dist: &ValueDist,
+    ) -> Self {
+        match dist {
+            ValueDist::Weights(weights) => {
+                let mut lengths = weights
+                    .iter()
+                    .filter(|(_, weight)| *weight > 0)
+                    .map(|(len, _)| *len as usize)
+                    .collect::<Vec<_>>();
+                lengths.sort_unstable();
+                lengths.dedup();
+                Domain::Strings { alphabet, lengths }
+            }
+            _ => Domain::Strings {
+                alphabet,
+                lengths: (len_min..=len_max).collect(),
+            },
+        }
+    }
+
+    /// Number of values in the domain (`None` if it doesn't fit in `u128`)
+    ///
+    fn size(&self) -> Option<u128> {
+        match self {
+            Domain::Values(values) => Some(values.len() as u128),
+            Domain::Numbers { min, max, .. } => Some((max - min + 1) as u128),
+            Domain::Strings { alphabet, lengths } => {
+                lengths.iter().try_fold(0u128, |acc, len| {
+                    acc.checked_add(alphabet.count(*len)?)
+                })
+            }
+        }
+    }
+
+    /// Whether all values are of the same length, so that they can't shift
+    /// bytes between neighbouring fields
+    ///
+    fn is_fixed_width(&self) -> bool {
+        match self {
+            Domain::Values(values) => values.iter().map(Vec::len).all_equal(),
+            // The longest and shortest encodings are at either end or
+            // around zero
+            Domain::Numbers { min, max, encode } => {
+                let width = |x: i128| encode(x.clamp(*min, *max)).len();
+                [*max, 0, -1].into_iter().all(|x| width(x) == width(*min))
+            }
+            Domain::Strings { lengths, .. } => lengths.len() <= 1,
+        }
+    }
+
+    fn write_value(&self, index: u128, bytes: &mut Vec<u8>) {
+        match self {
+            Domain::Values(values) => {
+                bytes.extend_from_slice(&values[index as usize])
+            }
+            Domain::Numbers { min, encode, .. } => {
+                bytes.extend(encode(min + index as i128))
+            }
+            Domain::Strings { alphabet, lengths } => {
+                let mut index = index;
+                for len in lengths {
+                    match alphabet.count(*len) {
+                        Some(count) if index >= count => index -= count,
+                        _ => {
+                            alphabet.write_string(index, *len, bytes);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Alphabet {
+    fn symbols(&self) -> u128 {
+        match self {
+            Alphabet::Alphanumeric => ALPHANUMERIC.len() as u128,
+            Alphabet::Bytes => 256,
+        }
+    }
+
+    /// Number of strings of a given length
+    ///
+    fn count(&self, len: usize) -> Option<u128> {
+        u32::try_from(len)
+            .ok()
+            .and_then(|len| self.symbols().checked_pow(len))
+    }
+
+    /// Write `index`-th string of a given length, first character changing
+    /// the slowest
+    ///
+    fn write_string(&self, index: u128, len: usize, bytes: &mut Vec<u8>) {
+        let start = bytes.len();
+        let mut index = index;
+        for _ in 0..len {
+            let symbol = (index % self.symbols()) as usize;
+            index /= self.symbols();
+            bytes.push(match self {
+                Alphabet::Alphanumeric => ALPHANUMERIC[symbol],
+                Alphabet::Bytes => symbol as u8,
+            });
+        }
+        bytes[start..].reverse();
+    }
+}
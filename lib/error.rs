@@ -1,8 +1,28 @@
+use std::fmt;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum BajzelError {
     Conversion(String),
     ProgramNotFinished,
     Syntax(String),
     Expr(String),
+    Io(String),
     NotConstructedProperly,
 }
+
+impl fmt::Display for BajzelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BajzelError::Conversion(x) => write!(f, "conversion error: {}", x),
+            BajzelError::ProgramNotFinished => {
+                write!(f, "program end not expected yet")
+            }
+            BajzelError::Syntax(x) => write!(f, "syntax error: {}", x),
+            BajzelError::Expr(x) => write!(f, "expression error: {}", x),
+            BajzelError::Io(x) => write!(f, "io error: {}", x),
+            BajzelError::NotConstructedProperly => {
+                write!(f, "program was not constructed properly")
+            }
+        }
+    }
+}
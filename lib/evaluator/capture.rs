@@ -0,0 +1,267 @@
+use super::{
+    structure::{FieldDefinition, GroupDefinition},
+    syntax_err, ProgramEnv,
+};
+use crate::{
+    error::BajzelError,
+    parser::{Expr, Literal},
+};
+use regex::bytes::Regex;
+use std::collections::{HashMap, HashSet};
+
+/// Values captured from replies, by variable name
+///
+pub type Variables = HashMap<String, Vec<u8>>;
+
+/// Way of binding variables to parts of a reply
+///
+/// Example:
+///
+/// ```fuzl
+/// DEFINE welcome
+///     "HELLO "
+///     string AS session_id -> LEN(8 8),
+///
+/// SEQUENCE session
+///     login -> CAPTURE(nonce "NONCE=([0-9a-f]+)"),
+///     hello -> REPLY(welcome),
+///     command
+/// ```
+///
+/// Captured values are then used by `$nonce` and `$session_id` fields.
+///
+#[derive(Debug, Clone)]
+pub enum CaptureDef {
+    /// Bind a variable to the first group of a regex match (or to the whole
+    /// match if there are no groups)
+    ///
+    Pattern { var: String, regex: Regex },
+
+    /// Match a reply against a group, binding every aliased field
+    ///
+    /// The regex is built once the program is evaluated (see
+    /// `compile_replies`), as the group may be defined after the sequence.
+    ///
+    Reply { group: String, regex: Option<Regex> },
+}
+
+impl CaptureDef {
+    /// Syntax:
+    ///     CAPTURE(var "regex")
+    ///
+    pub fn from_capture_expr(expr: Expr) -> Result<Self, BajzelError> {
+        let usage = "CAPTURE: expected (var \"regex\")";
+        let list = match expr {
+            Expr::Group(list) => list,
+            _ => return syntax_err(usage),
+        };
+        let (var, pattern) = match list.as_slice() {
+            [Expr::IdentExpr(var), Expr::LiteralExpr(pattern)] => {
+                (var, pattern)
+            }
+            _ => return syntax_err(usage),
+        };
+        let pattern = match pattern {
+            Literal::StringLiteral(x) => x,
+            _ => return syntax_err(usage),
+        };
+        let regex = Regex::new(pattern).map_err(|e| {
+            BajzelError::Syntax(format!("CAPTURE: invalid regex ({})", e))
+        })?;
+        Ok(CaptureDef::Pattern {
+            var: var.to_string(),
+            regex,
+        })
+    }
+
+    /// Syntax:
+    ///     REPLY(group)
+    ///
+    pub fn from_reply_expr(expr: Expr) -> Result<Self, BajzelError> {
+        match expr {
+            Expr::IdentExpr(group) => Ok(CaptureDef::Reply {
+                group: group.to_string(),
+                regex: None,
+            }),
+            _ => syntax_err("REPLY: expected a group name"),
+        }
+    }
+
+    /// Bind variables to parts of a reply
+    ///
+    /// Variables are left untouched when the reply doesn't match.
+    ///
+    pub fn apply(
+        &self,
+        reply: &[u8],
+        vars: &mut Variables,
+    ) -> Result<(), BajzelError> {
+        match self {
+            CaptureDef::Pattern { var, regex } => {
+                if let Some(captures) = regex.captures(reply) {
+                    let value = captures
+                        .get(1)
+                        .or_else(|| captures.get(0))
+                        .map(|x| x.as_bytes().to_vec())
+                        .unwrap_or_default();
+                    vars.insert(var.clone(), value);
+                }
+            }
+            CaptureDef::Reply { regex, .. } => {
+                let regex = regex
+                    .as_ref()
+                    .ok_or(BajzelError::NotConstructedProperly)?;
+                if let Some(captures) = regex.captures(reply) {
+                    for name in regex.capture_names().flatten() {
+                        if let Some(value) = captures.name(name) {
+                            vars.insert(
+                                name.to_owned(),
+                                value.as_bytes().to_vec(),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Names of variables bound by the capture
+    ///
+    pub fn variables(&self) -> Result<Vec<String>, BajzelError> {
+        match self {
+            CaptureDef::Pattern { var, .. } => Ok(vec![var.clone()]),
+            CaptureDef::Reply { regex, .. } => Ok(regex
+                .as_ref()
+                .ok_or(BajzelError::NotConstructedProperly)?
+                .capture_names()
+                .flatten()
+                .map(str::to_owned)
+                .collect()),
+        }
+    }
+}
+
+impl ProgramEnv {
+    /// Build regexes of all `REPLY(group)` captures, so they're not built
+    /// again for every reply
+    ///
+    pub(crate) fn compile_replies(&mut self) -> Result<(), BajzelError> {
+        let mut sequences = std::mem::take(&mut self.sequences);
+        let steps = sequences.values_mut().flat_map(|x| x.steps.iter_mut());
+        let result = steps
+            .flat_map(|step| step.captures.iter_mut())
+            .try_for_each(|capture| match capture {
+                CaptureDef::Reply { group, regex } => {
+                    *regex = Some(reply_regex(self, group)?);
+                    Ok(())
+                }
+                CaptureDef::Pattern { .. } => Ok(()),
+            });
+        self.sequences = sequences;
+        result
+    }
+}
+
+/// Build a regex matching replies laid out like a given group
+///
+/// - constant strings match exactly
+/// - text numbers match decimal digits
+/// - byte numbers match the number of bytes of their format
+/// - strings match at least LEN min characters (no line breaks), or one
+///   of DICT entries
+/// - bytes match at least LEN min bytes, or one of DICT entries
+///
+/// Aliased fields become named groups, so an alias can't be used twice
+/// (also within referenced groups).
+///
+pub(crate) fn reply_regex(
+    env: &ProgramEnv,
+    group: &str,
+) -> Result<Regex, BajzelError> {
+    let def = env.get_group(&group).map_err(|_| {
+        BajzelError::Syntax(format!("REPLY({}): unknown group", group))
+    })?;
+    let mut pattern = String::from("(?s-u)");
+    let mut aliases = HashSet::new();
+    group_pattern(env, def, &mut pattern, &mut aliases).map_err(
+        |e| match e {
+            BajzelError::Syntax(msg) => {
+                BajzelError::Syntax(format!("REPLY({}): {}", group, msg))
+            }
+            e => e,
+        },
+    )?;
+    Regex::new(&pattern).map_err(|e| {
+        BajzelError::Syntax(format!(
+            "REPLY({}): invalid pattern ({})",
+            group, e
+        ))
+    })
+}
+
+fn group_pattern<'a>(
+    env: &'a ProgramEnv,
+    group: &'a GroupDefinition,
+    pattern: &mut String,
+    aliases: &mut HashSet<&'a str>,
+) -> Result<(), BajzelError> {
+    for field in group.fields_iter() {
+        let field_pattern = match &field.def {
+            FieldDefinition::ConstString(x) => escape_bytes(x.as_bytes()),
+            FieldDefinition::TextNumber(_) => "-?[0-9]+".to_owned(),
+            FieldDefinition::ByteNumber(x) => {
+                format!(".{{{}}}", x.format.bits() / 8)
+            }
+            FieldDefinition::AsciiString(x) => match &x.dict {
+                Some(dict) => alternatives(dict.entries()),
+                None => format!("[^\\r\\n]{{{},}}", x.length_min),
+            },
+            FieldDefinition::Bytes(x) => match &x.dict {
+                Some(dict) => alternatives(dict.entries()),
+                None => format!(".{{{},}}", x.length_min),
+            },
+            FieldDefinition::Captured(_) => "[^\\r\\n]*?".to_owned(),
+            FieldDefinition::Ref(x) => {
+                let name = x
+                    .group
+                    .as_ref()
+                    .ok_or(BajzelError::NotConstructedProperly)?;
+                let mut nested = String::new();
+                let nested_group = env.get_group(name)?;
+                group_pattern(env, nested_group, &mut nested, aliases)?;
+                nested
+            }
+        };
+        match &field.alias {
+            Some(alias) if !aliases.insert(alias) => {
+                return syntax_err(format!("alias {} used twice", alias))
+            }
+            Some(alias) => {
+                pattern.push_str(&format!("(?P<{}>{})", alias, field_pattern))
+            }
+            None => pattern.push_str(&format!("(?:{})", field_pattern)),
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn alternatives(entries: &[Vec<u8>]) -> String {
+    entries
+        .iter()
+        .map(|x| escape_bytes(x))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Escape bytes to be matched literally
+///
+pub(crate) fn escape_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|x| match x.is_ascii_alphanumeric() {
+            true => (*x as char).to_string(),
+            false => format!("\\x{:02x}", x),
+        })
+        .collect()
+}
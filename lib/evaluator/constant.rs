@@ -0,0 +1,123 @@
+use super::{syntax_err, ProgramEnv};
+use crate::{
+    error::BajzelError,
+    parser::{Expr, Ident, Literal},
+};
+
+impl ProgramEnv {
+    /// Declare a constant usable in place of a literal
+    ///
+    /// Syntax:
+    ///     CONST HEADER_SIZE = 40
+    ///     CONST MAX_SIZE = HEADER_SIZE    # constants may refer to others
+    ///
+    /// Constants have to be declared before they're used.
+    ///
+    pub fn define_const(
+        &mut self,
+        name: Ident,
+        expr: Expr,
+    ) -> Result<(), BajzelError> {
+        match self.eval_expr(expr) {
+            Expr::LiteralExpr(value) => self.add_const(&name, value),
+            Expr::IdentExpr(x) => syntax_err(format!(
+                "CONST {}: unknown constant ({})",
+                *name, *x
+            )),
+            _ => syntax_err(format!("CONST {}: expected a value", *name)),
+        }
+    }
+
+    /// Declare an enumeration of integers, making its members constants
+    ///
+    /// Syntax:
+    ///     ENUM compression { BI_RGB = 0, BI_RLE8 = 1, BI_RLE4 = 2 }
+    ///
+    pub fn define_enum(
+        &mut self,
+        name: Ident,
+        members: Vec<(Ident, Expr)>,
+    ) -> Result<(), BajzelError> {
+        if self.enums.contains_key(name.as_str()) {
+            return syntax_err(format!("ENUM {} already defined", *name));
+        }
+        let mut values = Vec::with_capacity(members.len());
+        for (member, expr) in members {
+            let value = match self.eval_expr(expr) {
+                Expr::LiteralExpr(Literal::IntegerLiteral(x)) => x,
+                _ => {
+                    return syntax_err(format!(
+                        "ENUM {}: {} is not an integer",
+                        *name, *member
+                    ))
+                }
+            };
+            self.add_const(&member, Literal::IntegerLiteral(value))?;
+            values.push((member.to_string(), value));
+        }
+        self.enums.insert(name.to_string(), values);
+        Ok(())
+    }
+
+    pub(crate) fn add_const(
+        &mut self,
+        name: &str,
+        value: Literal,
+    ) -> Result<(), BajzelError> {
+        if self.consts.contains_key(name) {
+            return syntax_err(format!("constant {} already defined", name));
+        }
+        self.consts.insert(name.to_owned(), value);
+        Ok(())
+    }
+
+    /// Evaluate expression to a resolved form, replacing names of
+    /// constants with their values
+    ///
+    /// Other names (such as `log` in `DIST(log)`) are left untouched.
+    ///
+    pub(crate) fn eval_expr(&self, expr: Expr) -> Expr {
+        match expr {
+            Expr::IdentExpr(name) => match self.consts.get(name.as_str()) {
+                Some(value) => Expr::LiteralExpr(value.clone()),
+                None => Expr::IdentExpr(name),
+            },
+            Expr::Group(v) => {
+                Expr::Group(v.into_iter().map(|x| self.eval_expr(x)).collect())
+            }
+            x => x,
+        }
+    }
+
+    /// Replace a name of an enumeration given to `ENUM(name)` with values
+    /// of its members
+    ///
+    /// Values are returned untouched, as templates resolve enumerations
+    /// when they're defined.
+    ///
+    pub(crate) fn resolve_enum(&self, expr: Expr) -> Result<Expr, BajzelError> {
+        let name = match expr {
+            Expr::IdentExpr(name) => name,
+            Expr::Group(_) => return Ok(expr),
+            _ => return syntax_err("ENUM(name): expected a name"),
+        };
+        let members = self.enums.get(name.as_str()).ok_or_else(|| {
+            BajzelError::Syntax(format!(
+                "ENUM: unknown enumeration ({})",
+                *name
+            ))
+        })?;
+        Ok(Expr::Group(
+            members
+                .iter()
+                .map(|(_, x)| Expr::LiteralExpr(Literal::IntegerLiteral(*x)))
+                .collect(),
+        ))
+    }
+
+    /// Members of an enumeration of a given name (if there's one)
+    ///
+    pub fn find_enum(&self, name: &str) -> Option<&[(String, i128)]> {
+        self.enums.get(name).map(|x| x.as_slice())
+    }
+}
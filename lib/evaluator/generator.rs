@@ -12,15 +12,53 @@ pub struct GenDefinition {
     pub out_min: u32,
     pub out_max: u32,
     pub term: Vec<u8>,
+    pub num_mix: NumberMix,
+
+    /// Maximum number of messages in a session (when generating
+    /// a SEQUENCE)
+    ///
+    pub max_messages: u32,
+}
+
+/// Weights of strategies used to generate numeric fields
+///
+/// Example:
+///
+/// ```fuzl
+/// GENERATE command WITH
+///     NUM_UNIFORM     = 70    # value from RANGE (following DIST/WEIGHTS)
+///     NUM_INTERESTING = 20    # 0, -1, MAX, MIN, powers of two +/- 1, etc.
+///     NUM_BOUNDARY    = 10    # RANGE edges
+/// ```
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NumberMix {
+    pub uniform: u32,
+    pub interesting: u32,
+    pub boundary: u32,
 }
 
 impl GenDefinition {
+    /// Names of parameters set after `GENERATE name WITH`
+    ///
+    pub const PARAMS: [&'static str; 7] = [
+        "OUT_MIN",
+        "OUT_MAX",
+        "TERM",
+        "NUM_UNIFORM",
+        "NUM_INTERESTING",
+        "NUM_BOUNDARY",
+        "MAX_MESSAGES",
+    ];
+
     pub fn new(name: String) -> Self {
         Self {
             name,
             out_min: 0,
             out_max: 4096,
             term: Vec::new(),
+            num_mix: NumberMix::default(),
+            max_messages: 64,
         }
     }
 
@@ -60,6 +98,11 @@ impl GenDefinition {
         }
     }
 
+    pub fn set_max_messages(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        self.max_messages = eval_weight("MAX_MESSAGES", expr)?;
+        Ok(())
+    }
+
     pub fn set_term(&mut self, expr: Expr) -> Result<(), BajzelError> {
         match expr {
             Expr::LiteralExpr(literal) => match literal {
@@ -99,3 +142,41 @@ impl GenDefinition {
         }
     }
 }
+
+impl NumberMix {
+    pub fn set_uniform(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        self.uniform = eval_weight("NUM_UNIFORM", expr)?;
+        Ok(())
+    }
+
+    pub fn set_interesting(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        self.interesting = eval_weight("NUM_INTERESTING", expr)?;
+        Ok(())
+    }
+
+    pub fn set_boundary(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        self.boundary = eval_weight("NUM_BOUNDARY", expr)?;
+        Ok(())
+    }
+}
+
+impl Default for NumberMix {
+    fn default() -> Self {
+        Self {
+            uniform: 70,
+            interesting: 20,
+            boundary: 10,
+        }
+    }
+}
+
+fn eval_weight(param: &str, expr: Expr) -> Result<u32, BajzelError> {
+    match expr {
+        Expr::LiteralExpr(Literal::IntegerLiteral(value))
+            if (0..=u32::MAX as i128).contains(&value) =>
+        {
+            Ok(value as u32)
+        }
+        _ => syntax_err(format!("{}: expected a non-negative integer", param)),
+    }
+}
@@ -0,0 +1,120 @@
+use super::{
+    capture::CaptureDef, evaluate_file_in, sequence::END_STEP,
+    structure::FieldDefinition, syntax_err, ProgramEnv,
+};
+use crate::error::BajzelError;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Separator of a namespace and a name of an imported group or sequence
+///
+pub const NAMESPACE_SEP: &str = "::";
+
+impl ProgramEnv {
+    /// Evaluate a program from a given path (relative to the base
+    /// directory) and add its groups, templates, sequences, constants and
+    /// enumerations prefixed with a namespace
+    ///
+    /// Syntax:
+    ///     IMPORT "common/tlv.fuzl"
+    ///
+    /// The namespace is the name of the file without extension, so groups
+    /// above are available as `tlv::record` (and constants as `tlv::MAX`).
+    /// References within an imported program are renamed the same way and
+    /// its `GENERATE` is ignored.
+    ///
+    pub fn import(&mut self, path: &str) -> Result<(), BajzelError> {
+        let path = self.base_dir.join(path);
+        let namespace = namespace_of(&path)?;
+        let ctx = ProgramEnv {
+            import_chain: self.import_chain.clone(),
+            imported: true,
+            ..Default::default()
+        };
+        let lib = evaluate_file_in(&path, ctx)?;
+        let prefixed = |name: &str| -> String {
+            format!("{}{}{}", namespace, NAMESPACE_SEP, name)
+        };
+
+        let groups: HashSet<_> = lib.groups.keys().cloned().collect();
+        for (name, mut template) in lib.templates {
+            template.rename(prefixed, &groups);
+            let name = prefixed(&name);
+            if self.templates.contains_key(&name) {
+                return syntax_err(format!(
+                    "IMPORT: template {} already defined",
+                    name
+                ));
+            }
+            self.templates.insert(name, template);
+        }
+        for (name, mut group) in lib.groups {
+            for field in group.fields_iter_mut() {
+                if let FieldDefinition::Ref(def) = &mut field.def {
+                    def.group = def.group.as_deref().map(prefixed);
+                }
+            }
+            let name = prefixed(&name);
+            if self.groups.contains_key(&name) {
+                return syntax_err(format!(
+                    "IMPORT: group {} already defined",
+                    name
+                ));
+            }
+            self.groups.insert(name, group);
+        }
+        for (name, mut sequence) in lib.sequences {
+            for step in sequence.steps.iter_mut() {
+                step.group = prefixed(&step.group);
+                step.label = step.label.as_deref().map(prefixed);
+                for next in step.next.iter_mut().flatten() {
+                    if next != END_STEP {
+                        *next = prefixed(next);
+                    }
+                }
+                for capture in step.captures.iter_mut() {
+                    if let CaptureDef::Reply { group, .. } = capture {
+                        *group = prefixed(group);
+                    }
+                }
+            }
+            let name = prefixed(&name);
+            if self.sequences.contains_key(&name) {
+                return syntax_err(format!(
+                    "IMPORT: sequence {} already defined",
+                    name
+                ));
+            }
+            self.sequences.insert(name, sequence);
+        }
+        for (name, value) in lib.consts {
+            self.add_const(&prefixed(&name), value)?;
+        }
+        for (name, members) in lib.enums {
+            self.enums.insert(prefixed(&name), members);
+        }
+        self.sources.extend(lib.sources);
+        Ok(())
+    }
+}
+
+/// Namespace of an imported file: its name without extension, which has
+/// to be a valid identifier
+///
+fn namespace_of(path: &Path) -> Result<String, BajzelError> {
+    let stem = path
+        .file_stem()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut chars = stem.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    match valid {
+        true => Ok(stem),
+        false => syntax_err(format!(
+            "IMPORT: {} is not a valid namespace ({})",
+            stem,
+            path.display()
+        )),
+    }
+}
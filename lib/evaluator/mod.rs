@@ -4,19 +4,30 @@ const DEBUG_STATE: bool = false;
 
 use self::{
     generator::GenDefinition,
+    sequence::{SequenceDefinition, END_STEP},
     structure::{
-        AsciiStringDef, ByteNumberDef, BytesDef, Field, FieldDefinition,
-        GroupDefinition, NumberFormat, TextNumberDef,
+        Field, FieldDefinition, GroupDefinition, NumberFormat, RefDef,
+        TextNumberDef,
     },
+    template::{Instance, TemplateDefinition},
 };
 use crate::{
     error::BajzelError,
-    parser::{Expr, Ident, Literal, Program, Statement},
+    lexer::{lex_tokens, Tokens},
+    parser::{parse_tokens, Expr, Ident, Literal, Program, Statement},
 };
-use std::collections::HashMap;
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
 
+pub(crate) mod capture;
+pub(crate) mod constant;
 pub(crate) mod generator;
+pub(crate) mod import;
+pub(crate) mod sequence;
 pub(crate) mod structure;
+pub(crate) mod template;
 
 #[derive(Debug, Default)]
 pub struct ProgramEnv {
@@ -24,10 +35,31 @@ pub struct ProgramEnv {
     ///
     groups: HashMap<String, GroupDefinition>,
 
+    /// Map sequence name to its definition
+    ///
+    sequences: HashMap<String, SequenceDefinition>,
+
+    /// Map template name to its definition
+    ///
+    templates: HashMap<String, TemplateDefinition>,
+
+    /// Instances of templates used by ref fields, expanded into groups at
+    /// the end of the program
+    ///
+    instances: Vec<Instance>,
+
     /// Generator definition
     ///
     gen: Option<GenDefinition>,
 
+    /// Map constant name to its value, including members of enumerations
+    ///
+    consts: HashMap<String, Literal>,
+
+    /// Map enumeration name to its members (in order of declaration)
+    ///
+    enums: HashMap<String, Vec<(String, i128)>>,
+
     /// Name of an active group
     ///
     /// All field definitions will affect this group.
@@ -38,6 +70,39 @@ pub struct ProgramEnv {
     /// All attribute updates will affect this field (from an active group).
     ///
     cur_field: Option<String>,
+
+    /// Name of an active template
+    ///
+    /// All field definitions and attribute updates will be added to it.
+    ///
+    cur_template: Option<String>,
+
+    /// Name of an active sequence
+    ///
+    /// All step definitions will affect this sequence.
+    ///
+    cur_sequence: Option<String>,
+
+    /// Directory against which relative paths are resolved
+    ///
+    /// Usually it's a directory containing the `.fuzl` file.
+    ///
+    base_dir: PathBuf,
+
+    /// Files the program was read from (when loaded from a file),
+    /// files it imports and dictionaries it refers to
+    ///
+    sources: Vec<PathBuf>,
+
+    /// Canonical paths of the file being evaluated and files importing it
+    /// (outermost first), to detect import cycles
+    ///
+    import_chain: Vec<PathBuf>,
+
+    /// Whether the program is imported by another one, in which case it
+    /// doesn't need a `GENERATE` section
+    ///
+    imported: bool,
 }
 
 #[derive(Debug)]
@@ -46,14 +111,44 @@ pub enum Evaluator {
     DefiningFields(ProgramEnv),
     DefiningFieldAttr(ProgramEnv),
     UpdatingFieldAttrs(ProgramEnv),
+    DefiningTemplate(ProgramEnv),
+    DefiningSequence(ProgramEnv),
     DefiningGenerator(ProgramEnv),
     Finished(ProgramEnv),
 }
 
 /// Evaluate program to an environment
 ///
+/// Relative paths are resolved against the current working directory.
+///
 pub fn evaluate_program(program: Program) -> Result<ProgramEnv, BajzelError> {
-    let mut evaluator = Evaluator::Started(ProgramEnv::default());
+    evaluate_program_in(program, "")
+}
+
+/// Evaluate program to an environment, resolving relative paths (such as
+/// `DICT("words.txt")`) against a given directory
+///
+pub fn evaluate_program_in<P>(
+    program: Program,
+    base_dir: P,
+) -> Result<ProgramEnv, BajzelError>
+where
+    P: AsRef<Path>,
+{
+    let ctx = ProgramEnv {
+        base_dir: base_dir.as_ref().to_path_buf(),
+        ..Default::default()
+    };
+    evaluate_in(program, ctx)
+}
+
+/// Evaluate program statements one by one in a given environment
+///
+fn evaluate_in(
+    program: Program,
+    ctx: ProgramEnv,
+) -> Result<ProgramEnv, BajzelError> {
+    let mut evaluator = Evaluator::Started(ctx);
     for statement in program.into_iter() {
         if DEBUG_STATE {
             println!(">>> {:?}", statement);
@@ -70,9 +165,89 @@ pub fn evaluate_program(program: Program) -> Result<ProgramEnv, BajzelError> {
     }
 }
 
+/// Lex, parse and evaluate source of a `.fuzl` program, resolving relative
+/// paths against a given directory
+///
+pub fn evaluate_source_in<P>(
+    input: &str,
+    base_dir: P,
+) -> Result<ProgramEnv, BajzelError>
+where
+    P: AsRef<Path>,
+{
+    evaluate_program_in(parse_source(input)?, base_dir)
+}
+
+/// Load a `.fuzl` file, resolving relative paths against its directory
+///
+pub fn evaluate_file<P>(path: P) -> Result<ProgramEnv, BajzelError>
+where
+    P: AsRef<Path>,
+{
+    let ctx = ProgramEnv::default();
+    evaluate_file_in(path.as_ref(), ctx)
+}
+
+/// Load a `.fuzl` file in a given environment (telling whether the file
+/// is imported and by which files)
+///
+fn evaluate_file_in(
+    path: &Path,
+    mut ctx: ProgramEnv,
+) -> Result<ProgramEnv, BajzelError> {
+    let io_err = |e: std::io::Error| {
+        BajzelError::Io(format!("{}: {}", path.display(), e))
+    };
+    let input = std::fs::read_to_string(path).map_err(io_err)?;
+    let canonical = path.canonicalize().map_err(io_err)?;
+    if ctx.import_chain.contains(&canonical) {
+        let files = ctx.import_chain.iter().chain(Some(&canonical));
+        return syntax_err(format!(
+            "IMPORT: cycle ({})",
+            files
+                .map(|x| x.file_name().unwrap_or_default().to_string_lossy())
+                .join(" -> ")
+        ));
+    }
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    ctx.base_dir = base_dir.to_path_buf();
+    ctx.import_chain.push(canonical);
+    ctx.sources.push(path.to_path_buf());
+    evaluate_in(parse_source(&input)?, ctx)
+}
+
+fn parse_source(input: &str) -> Result<Program, BajzelError> {
+    let tokens = lex_tokens(input)
+        .map_err(|_| BajzelError::Syntax("lexer failed".to_owned()))?;
+    parse_tokens(Tokens::new(&tokens)).map_err(BajzelError::Syntax)
+}
+
 impl Evaluator {
     pub fn eval(self, statement: Statement) -> Result<Self, BajzelError> {
-        match self {
+        if let Statement::Import(path) = &statement {
+            return match self {
+                Evaluator::Started(mut ctx) => {
+                    ctx.import(path)?;
+                    Ok(Evaluator::Started(ctx))
+                }
+                _ => syntax_err("IMPORT must precede definitions"),
+            };
+        }
+        // Declarations may appear anywhere before the names are used, so
+        // they don't change the state
+        let mut evaluator = self;
+        match statement {
+            Statement::DefineConst(name, expr) => {
+                evaluator.env_mut().define_const(name, expr)?;
+                return Ok(evaluator);
+            }
+            Statement::DefineEnum(name, members) => {
+                evaluator.env_mut().define_enum(name, members)?;
+                return Ok(evaluator);
+            }
+            _ => (),
+        }
+        match evaluator {
             Evaluator::Started(ctx) => state_started(ctx, statement),
             Evaluator::DefiningFields(ctx) => {
                 state_defining_fields(ctx, statement)
@@ -83,13 +258,32 @@ impl Evaluator {
             Evaluator::UpdatingFieldAttrs(ctx) => {
                 state_updating_field_attrs(ctx, statement)
             }
+            Evaluator::DefiningTemplate(ctx) => {
+                state_defining_template(ctx, statement)
+            }
+            Evaluator::DefiningSequence(ctx) => {
+                state_defining_sequence(ctx, statement)
+            }
             Evaluator::DefiningGenerator(ctx) => {
                 state_defining_generator(ctx, statement)
             }
-            x => {
-                eprintln!("####> {:?}", x);
-                unimplemented!()
-            }
+            Evaluator::Finished(_) => syntax_err(format!(
+                "unexpected statement after the end of a program: {:?}",
+                statement
+            )),
+        }
+    }
+
+    fn env_mut(&mut self) -> &mut ProgramEnv {
+        match self {
+            Evaluator::Started(ctx)
+            | Evaluator::DefiningFields(ctx)
+            | Evaluator::DefiningFieldAttr(ctx)
+            | Evaluator::UpdatingFieldAttrs(ctx)
+            | Evaluator::DefiningTemplate(ctx)
+            | Evaluator::DefiningSequence(ctx)
+            | Evaluator::DefiningGenerator(ctx)
+            | Evaluator::Finished(ctx) => ctx,
         }
     }
 }
@@ -103,6 +297,25 @@ fn state_started(
             start_group_definition(&mut ctx, name)?;
             Ok(Evaluator::DefiningFields(ctx))
         }
+        Statement::StartTemplateDefinition(name, params) => {
+            ctx.create_template(name, params)?;
+            Ok(Evaluator::DefiningTemplate(ctx))
+        }
+        // Groups may come from imports only
+        Statement::StartSequenceDefinition(name) if !ctx.groups.is_empty() => {
+            start_sequence_definition(&mut ctx, name)?;
+            Ok(Evaluator::DefiningSequence(ctx))
+        }
+        Statement::StartGeneratorDefinition(name) if !ctx.groups.is_empty() => {
+            start_generator_definition(&mut ctx, name)?;
+            Ok(Evaluator::DefiningGenerator(ctx))
+        }
+        Statement::Run
+            if ctx.imported
+                && !(ctx.groups.is_empty() && ctx.templates.is_empty()) =>
+        {
+            finish_program(ctx)
+        }
         _ => syntax_err("At least one DEFINE section is required"),
     }
 }
@@ -121,6 +334,21 @@ fn state_defining_fields(
             define_var_field(kind, &mut ctx, alias)?;
             Ok(Evaluator::DefiningFields(ctx))
         }
+        Statement::DefineRefField(group, alias) => {
+            define_ref_field(group, &mut ctx, alias)?;
+            Ok(Evaluator::DefiningFields(ctx))
+        }
+        Statement::DefineCapturedField(var, alias) => {
+            define_captured_field(var, &mut ctx, alias)?;
+            Ok(Evaluator::DefiningFields(ctx))
+        }
+        Statement::DefineTemplateRefField(template, args, alias) => {
+            ctx.create_template_ref_field(template, args, alias);
+            Ok(Evaluator::DefiningFields(ctx))
+        }
+        Statement::DefineParamField(kind, _) => {
+            syntax_err(format!("unknown type ({})", *kind))
+        }
         Statement::MakeCurrentField(name) => {
             make_current_field(&mut ctx, name);
             Ok(Evaluator::DefiningFieldAttr(ctx))
@@ -129,12 +357,21 @@ fn state_defining_fields(
             start_group_definition(&mut ctx, name)?;
             Ok(Evaluator::DefiningFields(ctx))
         }
+        Statement::StartTemplateDefinition(name, params) => {
+            ctx.create_template(name, params)?;
+            Ok(Evaluator::DefiningTemplate(ctx))
+        }
         Statement::StartGeneratorDefinition(name) => {
             start_generator_definition(&mut ctx, name)?;
             Ok(Evaluator::DefiningGenerator(ctx))
         }
+        Statement::StartSequenceDefinition(name) => {
+            start_sequence_definition(&mut ctx, name)?;
+            Ok(Evaluator::DefiningSequence(ctx))
+        }
         Statement::StartFieldsSection => Ok(Evaluator::UpdatingFieldAttrs(ctx)),
-        x => unimplemented!("state_defining_fields: {:?}", x),
+        Statement::Run => finish_program(ctx),
+        x => syntax_err(format!("unexpected statement in DEFINE: {:?}", x)),
     }
 }
 
@@ -151,15 +388,44 @@ fn state_defining_field_attr(
             define_const_field(literal, &mut ctx, alias)?;
             Ok(Evaluator::DefiningFields(ctx))
         }
+        Statement::DefineVariableField(kind, alias) => {
+            define_var_field(kind, &mut ctx, alias)?;
+            Ok(Evaluator::DefiningFields(ctx))
+        }
+        Statement::DefineRefField(group, alias) => {
+            define_ref_field(group, &mut ctx, alias)?;
+            Ok(Evaluator::DefiningFields(ctx))
+        }
+        Statement::DefineCapturedField(var, alias) => {
+            define_captured_field(var, &mut ctx, alias)?;
+            Ok(Evaluator::DefiningFields(ctx))
+        }
+        Statement::DefineTemplateRefField(template, args, alias) => {
+            ctx.create_template_ref_field(template, args, alias);
+            Ok(Evaluator::DefiningFields(ctx))
+        }
+        Statement::DefineParamField(kind, _) => {
+            syntax_err(format!("unknown type ({})", *kind))
+        }
         Statement::StartGroupDefinition(name) => {
             start_group_definition(&mut ctx, name)?;
             Ok(Evaluator::DefiningFields(ctx))
         }
+        Statement::StartTemplateDefinition(name, params) => {
+            ctx.create_template(name, params)?;
+            Ok(Evaluator::DefiningTemplate(ctx))
+        }
         Statement::StartGeneratorDefinition(name) => {
             start_generator_definition(&mut ctx, name)?;
             Ok(Evaluator::DefiningGenerator(ctx))
         }
-        x => unimplemented!("state_defining_field_attr: {:?}", x),
+        Statement::StartSequenceDefinition(name) => {
+            start_sequence_definition(&mut ctx, name)?;
+            Ok(Evaluator::DefiningSequence(ctx))
+        }
+        Statement::StartFieldsSection => Ok(Evaluator::UpdatingFieldAttrs(ctx)),
+        Statement::Run => finish_program(ctx),
+        x => syntax_err(format!("unexpected statement in DEFINE: {:?}", x)),
     }
 }
 
@@ -180,11 +446,76 @@ fn state_updating_field_attrs(
             start_group_definition(&mut ctx, name)?;
             Ok(Evaluator::DefiningFields(ctx))
         }
+        Statement::StartTemplateDefinition(name, params) => {
+            ctx.create_template(name, params)?;
+            Ok(Evaluator::DefiningTemplate(ctx))
+        }
+        Statement::StartGeneratorDefinition(name) => {
+            start_generator_definition(&mut ctx, name)?;
+            Ok(Evaluator::DefiningGenerator(ctx))
+        }
+        Statement::StartSequenceDefinition(name) => {
+            start_sequence_definition(&mut ctx, name)?;
+            Ok(Evaluator::DefiningSequence(ctx))
+        }
+        Statement::Run => finish_program(ctx),
+        x => syntax_err(format!("unexpected statement in WHERE: {:?}", x)),
+    }
+}
+
+/// Collect statements of a template until another block starts
+///
+fn state_defining_template(
+    mut ctx: ProgramEnv,
+    statement: Statement,
+) -> Result<Evaluator, BajzelError> {
+    match statement {
+        Statement::StartGroupDefinition(_)
+        | Statement::StartTemplateDefinition(..)
+        | Statement::StartSequenceDefinition(_)
+        | Statement::StartGeneratorDefinition(_)
+        | Statement::Run => {
+            ctx.cur_template = None;
+            state_started(ctx, statement)
+        }
+        x => {
+            ctx.add_template_statement(x)?;
+            Ok(Evaluator::DefiningTemplate(ctx))
+        }
+    }
+}
+
+fn state_defining_sequence(
+    mut ctx: ProgramEnv,
+    statement: Statement,
+) -> Result<Evaluator, BajzelError> {
+    match statement {
+        Statement::DefineStep(group, label) => {
+            ctx.create_step(group, label);
+            Ok(Evaluator::DefiningSequence(ctx))
+        }
+        Statement::UpdateField(ident, expr) => {
+            ctx.update_step(ident, expr)?;
+            Ok(Evaluator::DefiningSequence(ctx))
+        }
+        Statement::StartGroupDefinition(name) => {
+            start_group_definition(&mut ctx, name)?;
+            Ok(Evaluator::DefiningFields(ctx))
+        }
+        Statement::StartTemplateDefinition(name, params) => {
+            ctx.create_template(name, params)?;
+            Ok(Evaluator::DefiningTemplate(ctx))
+        }
+        Statement::StartSequenceDefinition(name) => {
+            start_sequence_definition(&mut ctx, name)?;
+            Ok(Evaluator::DefiningSequence(ctx))
+        }
         Statement::StartGeneratorDefinition(name) => {
             start_generator_definition(&mut ctx, name)?;
             Ok(Evaluator::DefiningGenerator(ctx))
         }
-        x => unimplemented!("state_updating_field_attrs: {:?}", x),
+        Statement::Run => finish_program(ctx),
+        x => syntax_err(format!("unexpected statement in SEQUENCE: {:?}", x)),
     }
 }
 
@@ -197,9 +528,25 @@ fn state_defining_generator(
             update_generator_param(&mut ctx, name, expr)?;
             Ok(Evaluator::DefiningGenerator(ctx))
         }
-        Statement::Run => Ok(Evaluator::Finished(ctx)),
-        x => unimplemented!("state_defining_generator: {:?}", x),
+        Statement::Run => finish_program(ctx),
+        x => syntax_err(format!("unexpected statement in GENERATE: {:?}", x)),
+    }
+}
+
+/// Finish evaluation at the end of a program, checking whether all names
+/// it uses are defined
+///
+/// Only imported programs may end without a `GENERATE` section.
+///
+fn finish_program(ctx: ProgramEnv) -> Result<Evaluator, BajzelError> {
+    if ctx.gen.is_none() && !ctx.imported {
+        return Err(BajzelError::ProgramNotFinished);
     }
+    let mut ctx = ctx.expand_instances()?;
+    ctx.check_references()?;
+    ctx.compile_replies()?;
+    ctx.check_sequences()?;
+    Ok(Evaluator::Finished(ctx))
 }
 
 fn start_group_definition(
@@ -211,6 +558,13 @@ fn start_group_definition(
     Ok(())
 }
 
+fn start_sequence_definition(
+    ctx: &mut ProgramEnv,
+    name: Ident,
+) -> Result<(), BajzelError> {
+    ctx.create_sequence(name)
+}
+
 fn start_generator_definition(
     ctx: &mut ProgramEnv,
     name: Ident,
@@ -226,16 +580,19 @@ fn define_const_field(
 ) -> Result<(), BajzelError> {
     let field_def = match literal {
         crate::parser::Literal::IntegerLiteral(x) => {
-            FieldDefinition::TextNumber(TextNumberDef::new(NumberFormat::Int64))
+            let mut def = TextNumberDef::new(NumberFormat::Int64);
+            def.min_value = x;
+            def.max_value = x;
+            FieldDefinition::TextNumber(def)
         }
         crate::parser::Literal::StringLiteral(x) => {
             FieldDefinition::ConstString(x)
         }
-        crate::parser::Literal::BytesLiteral(x) => {
-            todo!("bytes literals not implemented")
+        crate::parser::Literal::BytesLiteral(_) => {
+            return syntax_err("bytes literals are not supported as fields")
         }
         crate::parser::Literal::Reserved(x) => {
-            todo!("reserved literals not implemented")
+            return syntax_err(format!("{} is not supported as a field", *x))
         }
     };
     ctx.create_field(field_def, alias);
@@ -247,26 +604,27 @@ fn define_var_field(
     ctx: &mut ProgramEnv,
     alias: Option<Ident>,
 ) -> Result<(), BajzelError> {
-    let mut field_def = None;
-    if kind.starts_with("le_") || kind.starts_with("be_") {
-        if let Ok(def) = ByteNumberDef::from_str(kind.as_str()) {
-            field_def.replace(FieldDefinition::ByteNumber(def));
-        }
-    } else if kind == "string" {
-        field_def.replace(FieldDefinition::AsciiString(AsciiStringDef::new()));
-    } else if kind == "bytes" {
-        field_def.replace(FieldDefinition::Bytes(BytesDef::new()));
-    } else if kind.starts_with('i') || kind.starts_with('u') {
-        if let Ok(def) = TextNumberDef::from_str(kind.as_str()) {
-            field_def.replace(FieldDefinition::TextNumber(def));
-        }
-    }
-    let field_def = field_def.ok_or_else(|| {
-        BajzelError::Syntax(format!(
-            "Unsupported variable field type ({})",
-            kind
-        ))
-    })?;
+    let field_def = FieldDefinition::from_type(&kind)?;
+    ctx.create_field(field_def, alias);
+    Ok(())
+}
+
+fn define_ref_field(
+    group: Ident,
+    ctx: &mut ProgramEnv,
+    alias: Option<Ident>,
+) -> Result<(), BajzelError> {
+    let field_def = FieldDefinition::Ref(RefDef::new(Some(group.to_string())));
+    ctx.create_field(field_def, alias);
+    Ok(())
+}
+
+fn define_captured_field(
+    var: Ident,
+    ctx: &mut ProgramEnv,
+    alias: Option<Ident>,
+) -> Result<(), BajzelError> {
+    let field_def = FieldDefinition::Captured(var.to_string());
     ctx.create_field(field_def, alias);
     Ok(())
 }
@@ -310,6 +668,50 @@ impl ProgramEnv {
         self.cur_field = None;
     }
 
+    /// Create a new sequence definition and make it a current one
+    ///
+    pub fn create_sequence(&mut self, name: Ident) -> Result<(), BajzelError> {
+        if self.sequences.contains_key(name.as_str()) {
+            return syntax_err(format!("SEQUENCE {} already defined", *name));
+        }
+        self.sequences
+            .insert(name.to_string(), SequenceDefinition::default());
+        self.cur_sequence = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Add a step to a current sequence
+    ///
+    /// Note: Might panic if called when no sequence is created.
+    ///
+    pub fn create_step(&mut self, group: Ident, label: Option<Ident>) {
+        let cur_sequence = self
+            .cur_sequence
+            .as_ref()
+            .expect("current sequence should not be missing");
+        self.sequences
+            .get_mut(cur_sequence)
+            .expect("current sequence assigned only when it's added")
+            .add_step(group.to_string(), label.map(|x| x.to_string()));
+    }
+
+    /// Update attribute of the last step of a current sequence
+    ///
+    pub fn update_step(
+        &mut self,
+        attr: Ident,
+        expr: Expr,
+    ) -> Result<(), BajzelError> {
+        let expr = self.eval_expr(expr);
+        let step = self
+            .cur_sequence
+            .as_ref()
+            .and_then(|name| self.sequences.get_mut(name))
+            .and_then(|sequence| sequence.last_step_mut())
+            .ok_or(BajzelError::NotConstructedProperly)?;
+        step.update(attr.as_str(), expr)
+    }
+
     /// Create a generator definition
     ///
     /// Note: Only a single GENERATE section is allowed so more than single call
@@ -355,42 +757,74 @@ impl ProgramEnv {
     /// Update attribute of a given name with value from a given expression of a field
     /// that was made a current one.
     ///
-    /// TODO: Make the function not to panic when no field was made active.
+    /// Returns an error if the active field does not exist in a current group.
     ///
     pub fn update_field(
         &mut self,
         attr: Ident,
         expr: Expr,
     ) -> Result<(), BajzelError> {
-        let expr = eval_expr(expr)?;
         let attr = attr.as_str();
-        let field = self.get_field();
+        let expr = match attr {
+            "DICT" => self.resolve_path(expr),
+            "ENUM" => self.resolve_enum(expr)?,
+            _ => self.eval_expr(expr),
+        };
+        let field = self.get_field()?;
         match &mut field.def {
             FieldDefinition::ConstString(x) => {
                 syntax_err("'string' type does not have any attributes")
             }
+            FieldDefinition::Captured(x) => {
+                syntax_err("captured values do not have any attributes")
+            }
             FieldDefinition::TextNumber(def) => def.update(attr, expr),
             FieldDefinition::AsciiString(def) => def.update(attr, expr),
             FieldDefinition::ByteNumber(def) => def.update(attr, expr),
             FieldDefinition::Bytes(def) => def.update(attr, expr),
+            FieldDefinition::Ref(def) => def.update(attr, expr),
+        }
+    }
+
+    /// Resolve a relative path given as a string literal against the base
+    /// directory
+    ///
+    /// Other expressions are returned untouched.
+    ///
+    fn resolve_path(&mut self, expr: Expr) -> Expr {
+        match expr {
+            Expr::LiteralExpr(Literal::StringLiteral(path)) => {
+                let path = self.base_dir.join(path);
+                self.sources.push(path.clone());
+                Expr::LiteralExpr(Literal::StringLiteral(
+                    path.to_string_lossy().into_owned(),
+                ))
+            }
+            x => x,
         }
     }
 
     /// Return an active field of a current group
     ///
-    /// TODO: Make the function not to panic when no field was made active.
+    /// Returns an error if no field of the active name exists in the group.
     ///
-    pub fn get_field(&mut self) -> &mut Field {
-        let cur_group =
-            self.cur_group.as_ref().expect("current group must be set");
-        let cur_field = self.cur_field.as_ref().expect("current field is set");
-        let field = self
+    pub fn get_field(&mut self) -> Result<&mut Field, BajzelError> {
+        let (Some(cur_group), Some(cur_field)) =
+            (self.cur_group.as_ref(), self.cur_field.as_ref())
+        else {
+            return syntax_err("field attributes outside of WHERE");
+        };
+        match self
             .groups
             .get_mut(cur_group)
             .and_then(|group_def| group_def.find_field_mut(cur_field))
-            .expect("Current group and current field must be set properly");
-
-        field
+        {
+            Some(field) => Ok(field),
+            None => Err(BajzelError::Syntax(format!(
+                "unknown field {} in WHERE of {}",
+                cur_field, cur_group
+            ))),
+        }
     }
 
     /// Update parameter of a generator
@@ -402,18 +836,149 @@ impl ProgramEnv {
         param: Ident,
         expr: Expr,
     ) -> Result<(), BajzelError> {
+        let expr = self.eval_expr(expr);
         let def = self.gen.as_mut().expect("GENERATE section is created");
-        let expr = eval_expr(expr)?;
         let param = param.as_str();
 
         match param.to_ascii_uppercase().as_str() {
             "OUT_MIN" => def.set_min_output_len(expr),
             "OUT_MAX" => def.set_max_output_len(expr),
             "TERM" => def.set_term(expr),
+            "NUM_UNIFORM" => def.num_mix.set_uniform(expr),
+            "NUM_INTERESTING" => def.num_mix.set_interesting(expr),
+            "NUM_BOUNDARY" => def.num_mix.set_boundary(expr),
+            "MAX_MESSAGES" => def.set_max_messages(expr),
             _ => syntax_err("unsupported generator parameter"),
         }
     }
 
+    /// Make sure every ref field points to an existing group and groups
+    /// don't reference themselves (directly or not)
+    ///
+    pub fn check_references(&self) -> Result<(), BajzelError> {
+        for name in self.groups.keys() {
+            self.check_group_references(name, &mut vec![])?;
+        }
+        Ok(())
+    }
+
+    fn check_group_references<'a>(
+        &'a self,
+        name: &'a str,
+        path: &mut Vec<&'a str>,
+    ) -> Result<(), BajzelError> {
+        if path.contains(&name) {
+            return syntax_err(format!(
+                "ref: recursive group reference ({} -> {})",
+                path.join(" -> "),
+                name
+            ));
+        }
+        if self.is_template(name) {
+            return syntax_err(format!(
+                "ref: {} is a template, arguments are missing",
+                name
+            ));
+        }
+        let group = self.groups.get(name).ok_or_else(|| {
+            BajzelError::Syntax(format!("ref: unknown group ({})", name))
+        })?;
+        path.push(name);
+        for field in group.fields_iter() {
+            if let FieldDefinition::Ref(def) = &field.def {
+                let group = def.group.as_ref().ok_or_else(|| {
+                    BajzelError::Syntax(
+                        "ref: missing group name (FROM or TO)".to_owned(),
+                    )
+                })?;
+                self.check_group_references(group, path)?;
+            }
+        }
+        path.pop();
+        Ok(())
+    }
+
+    /// Make sure steps of every sequence generate from existing groups and
+    /// go only to existing steps, and every `$var` used by a field is
+    /// captured by some step
+    ///
+    pub fn check_sequences(&self) -> Result<(), BajzelError> {
+        let mut captured = HashSet::new();
+        for (name, sequence) in &self.sequences {
+            if self.groups.contains_key(name) {
+                return syntax_err(format!(
+                    "SEQUENCE {}: group of the same name exists",
+                    name
+                ));
+            }
+            let names = sequence.steps.iter().map(|x| x.name()).counts();
+            let mut labels =
+                sequence.steps.iter().filter_map(|x| x.label.as_ref());
+            if let Some(label) = labels.find(|x| names[x.as_str()] > 1) {
+                return syntax_err(format!(
+                    "SEQUENCE {}: step name used twice ({})",
+                    name, label
+                ));
+            }
+            for step in &sequence.steps {
+                if !self.groups.contains_key(&step.group) {
+                    return syntax_err(format!(
+                        "SEQUENCE {}: unknown group ({})",
+                        name, step.group
+                    ));
+                }
+                for capture in &step.captures {
+                    captured.extend(capture.variables()?);
+                }
+                let next = step.next.iter().flatten();
+                for next in next.filter(|x| x.as_str() != END_STEP) {
+                    match names.get(next.as_str()) {
+                        None => {
+                            return syntax_err(format!(
+                                "SEQUENCE {}: unknown step in NEXT ({})",
+                                name, next
+                            ))
+                        }
+                        Some(2..) => {
+                            return syntax_err(format!(
+                                "SEQUENCE {}: step in NEXT is ambiguous ({}), \
+                                 label it with AS",
+                                name, next
+                            ))
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        for group in self.groups.values() {
+            for field in group.fields_iter() {
+                match &field.def {
+                    FieldDefinition::Captured(var)
+                        if !captured.contains(var) =>
+                    {
+                        return syntax_err(format!(
+                            "${}: variable is not captured by any step",
+                            var
+                        ));
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Return sequence definition of a given name (if there's one)
+    ///
+    pub fn find_sequence<T>(&self, name: &T) -> Option<&SequenceDefinition>
+    where
+        T: AsRef<str>,
+    {
+        self.sequences.get(name.as_ref())
+    }
+
+    /// Return group definition of a given name
     ///
     pub fn get_group<T>(
         &self,
@@ -430,13 +995,84 @@ impl ProgramEnv {
     pub fn get_generator(&self) -> Result<&GenDefinition, BajzelError> {
         self.gen.as_ref().ok_or(BajzelError::NotConstructedProperly)
     }
+
+    /// Files the program depends on: the program itself (when loaded from
+    /// a file) and dictionaries
+    ///
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+
+    /// Summary of a single group or sequence, like in `Display`
+    ///
+    pub fn summary_of(&self, name: &str) -> Option<String> {
+        let mut output = String::new();
+        if let Some(group) = self.groups.get(name) {
+            write_group(&mut output, name, group).ok()?;
+        } else if let Some(sequence) = self.sequences.get(name) {
+            write_sequence(&mut output, name, sequence).ok()?;
+        } else {
+            return None;
+        }
+        Some(output)
+    }
 }
 
-/// Evaluate expression to a resolved form
+/// Summary of evaluated definitions (groups, sequences and generator),
+/// sorted by name
 ///
-fn eval_expr(expr: Expr) -> Result<Expr, BajzelError> {
-    // Nothing to do for a now
-    Ok(expr)
+impl fmt::Display for ProgramEnv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut groups: Vec<_> = self.groups.iter().collect();
+        groups.sort_by_key(|(name, _)| *name);
+        for (name, group) in groups {
+            write_group(f, name, group)?;
+        }
+        let mut sequences: Vec<_> = self.sequences.iter().collect();
+        sequences.sort_by_key(|(name, _)| *name);
+        for (name, sequence) in sequences {
+            write_sequence(f, name, sequence)?;
+        }
+        if let Some(gen) = &self.gen {
+            writeln!(f, "generate {}", gen.name)?;
+            writeln!(f, "    OUT  = {} to {}", gen.out_min, gen.out_max)?;
+            writeln!(f, "    TERM = \"{}\"", gen.term.escape_ascii())?;
+        }
+        Ok(())
+    }
+}
+
+fn write_group<W: fmt::Write>(
+    f: &mut W,
+    name: &str,
+    group: &GroupDefinition,
+) -> fmt::Result {
+    writeln!(f, "group {}", name)?;
+    for field in group.fields_iter() {
+        let alias = field.alias.as_deref().unwrap_or("_");
+        writeln!(f, "    {}: {:?}", alias, field.def)?;
+    }
+    Ok(())
+}
+
+fn write_sequence<W: fmt::Write>(
+    f: &mut W,
+    name: &str,
+    sequence: &SequenceDefinition,
+) -> fmt::Result {
+    writeln!(f, "sequence {}", name)?;
+    for step in &sequence.steps {
+        write!(f, "    {}", step.group)?;
+        if let Some(label) = &step.label {
+            write!(f, " AS {}", label)?;
+        }
+        write!(f, " (repeat {} to {})", step.repeat_min, step.repeat_max)?;
+        if let Some(next) = &step.next {
+            write!(f, " -> {}", next.join(" "))?;
+        }
+        writeln!(f)?;
+    }
+    Ok(())
 }
 
 /// Extract i64 from the expression, if possible.
@@ -452,15 +1088,35 @@ fn eval_expr(expr: Expr) -> Result<Expr, BajzelError> {
 /// assert_eq!(eval_expr_to_i64(&x), Ok(42i64));
 /// ```
 pub fn eval_expr_to_i64(expr: &Expr) -> Result<i64, BajzelError> {
+    let value = eval_expr_to_i128(expr)?;
+    i64::try_from(value).map_err(|_| {
+        BajzelError::Expr(format!(
+            "eval_expr_to_i64: integer out of range ({})",
+            value
+        ))
+    })
+}
+
+/// Extract i128 from the expression, if possible
+///
+/// Unlike `eval_expr_to_i64`, it fits values of every number format
+/// (such as `u64::MAX`).
+///
+pub fn eval_expr_to_i128(expr: &Expr) -> Result<i128, BajzelError> {
     match expr {
         Expr::LiteralExpr(literal) => match literal {
             Literal::IntegerLiteral(value) => Ok(*value),
-            Literal::StringLiteral(_) => todo!(),
-            Literal::BytesLiteral(_) => todo!(),
-            Literal::Reserved(_) => todo!(),
+            _ => Err(BajzelError::Expr(format!(
+                "eval_expr_to_i128: expected an integer ({:?})",
+                literal
+            ))),
         },
+        Expr::IdentExpr(ident) => Err(BajzelError::Expr(format!(
+            "eval_expr_to_i128: unexpected identifier ({})",
+            ident.as_str()
+        ))),
         Expr::Group(_group) => Err(BajzelError::Expr(
-            "eval_expr_to_i64: group expr not expected".to_owned(),
+            "eval_expr_to_i128: group expr not expected".to_owned(),
         )),
         Expr::Empty => Ok(0),
     }
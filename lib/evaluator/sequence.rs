@@ -0,0 +1,207 @@
+use super::{capture::CaptureDef, syntax_err};
+use crate::{
+    error::BajzelError,
+    parser::{Expr, Literal},
+};
+
+/// Name used in `NEXT` to finish a session
+///
+pub const END_STEP: &str = "END";
+
+/// Ordered list of messages sent within a single session
+///
+/// Example:
+///
+/// ```fuzl
+/// SEQUENCE session
+///     login -> MUTATE(0),         # always well-formed
+///     command -> REPEAT(1 5),     # 1 to 5 commands in a row
+///     quit
+/// ```
+///
+/// Steps follow one another unless `NEXT` is given, in which case the
+/// step to go to is picked randomly from the listed ones (`END` finishes
+/// the session):
+///
+/// ```fuzl
+/// SEQUENCE session
+///     login
+///     command -> NEXT(command quit END),
+///     quit
+/// ```
+///
+/// A step is named after its group, unless it's given a label with `AS`,
+/// which is needed to go to a group used by several steps:
+///
+/// ```fuzl
+/// SEQUENCE session
+///     login
+///     command -> NEXT(relogin END),
+///     login AS relogin -> NEXT(command),
+/// ```
+///
+#[derive(Debug, Default)]
+pub struct SequenceDefinition {
+    pub steps: Vec<StepDefinition>,
+}
+
+#[derive(Debug)]
+pub struct StepDefinition {
+    /// Group a message is generated from
+    ///
+    pub group: String,
+
+    /// Name used by `NEXT` instead of the group name
+    ///
+    pub label: Option<String>,
+
+    pub repeat_min: u32,
+    pub repeat_max: u32,
+
+    /// Whether fuzzing heuristics (interesting numbers, global dictionary)
+    /// are used, otherwise values only follow field attributes
+    ///
+    pub mutate: bool,
+
+    /// Steps to pick from after this one (`None` means the following step)
+    ///
+    pub next: Option<Vec<String>>,
+
+    /// Variables bound from a reply to the step's message
+    ///
+    pub captures: Vec<CaptureDef>,
+}
+
+impl SequenceDefinition {
+    pub fn add_step(&mut self, group: String, label: Option<String>) {
+        self.steps.push(StepDefinition {
+            label,
+            ..StepDefinition::new(group)
+        });
+    }
+
+    /// Return the most recently added step
+    ///
+    pub fn last_step_mut(&mut self) -> Option<&mut StepDefinition> {
+        self.steps.last_mut()
+    }
+
+    /// Return position of a step of a given name (see `StepDefinition::name`)
+    ///
+    pub fn find_step(&self, name: &str) -> Option<usize> {
+        self.steps.iter().position(|x| x.name() == name)
+    }
+}
+
+impl StepDefinition {
+    /// Names of attributes accepted by `update`
+    ///
+    pub const ATTRIBUTES: [&'static str; 5] =
+        ["REPEAT", "MUTATE", "NEXT", "CAPTURE", "REPLY"];
+
+    pub fn new(group: String) -> Self {
+        Self {
+            group,
+            label: None,
+            repeat_min: 1,
+            repeat_max: 1,
+            mutate: true,
+            next: None,
+            captures: vec![],
+        }
+    }
+
+    /// Name of a step used by `NEXT`: its label or, without one, its group
+    ///
+    pub fn name(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.group)
+    }
+
+    pub fn update(
+        &mut self,
+        attr_name: &str,
+        expr: Expr,
+    ) -> Result<(), BajzelError> {
+        match attr_name {
+            "REPEAT" => self.set_repeat(expr),
+            "MUTATE" => self.set_mutate(expr),
+            "NEXT" => self.set_next(expr),
+            "CAPTURE" => {
+                self.captures.push(CaptureDef::from_capture_expr(expr)?);
+                Ok(())
+            }
+            "REPLY" => {
+                self.captures.push(CaptureDef::from_reply_expr(expr)?);
+                Ok(())
+            }
+            x => syntax_err(format!("step: unsupported attribute ({})", x)),
+        }
+    }
+
+    /// Syntax:
+    ///     REPEAT(count)
+    ///     REPEAT(min max)
+    ///
+    fn set_repeat(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        let list = match expr {
+            Expr::Group(list) => list,
+            x => vec![x],
+        };
+        let bounds = list
+            .iter()
+            .map(|x| match x {
+                Expr::LiteralExpr(Literal::IntegerLiteral(x)) => Some(*x),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        let (min, max) = match bounds.as_deref() {
+            Some([count]) => (*count, *count),
+            Some([min, max]) => (*min, *max),
+            _ => return syntax_err("REPEAT: expected (count) or (min max)"),
+        };
+        if min < 0 || min > max || max > u32::MAX as i128 {
+            return syntax_err("REPEAT: expected 0 <= min <= max");
+        }
+        self.repeat_min = min as u32;
+        self.repeat_max = max as u32;
+        Ok(())
+    }
+
+    /// Syntax:
+    ///     MUTATE(0)
+    ///     MUTATE(1)
+    ///
+    fn set_mutate(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        match expr {
+            Expr::LiteralExpr(Literal::IntegerLiteral(x @ (0 | 1))) => {
+                self.mutate = x == 1;
+                Ok(())
+            }
+            _ => syntax_err("MUTATE: expected 0 or 1"),
+        }
+    }
+
+    /// Syntax:
+    ///     NEXT(step ...)
+    ///
+    fn set_next(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        let list = match expr {
+            Expr::Group(list) => list,
+            x => vec![x],
+        };
+        let next = list
+            .into_iter()
+            .map(|x| match x {
+                Expr::IdentExpr(name)
+                    if name.eq_ignore_ascii_case(END_STEP) =>
+                {
+                    Ok(END_STEP.to_owned())
+                }
+                Expr::IdentExpr(name) => Ok(name.to_string()),
+                _ => syntax_err("NEXT: expected step names"),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.next = Some(next);
+        Ok(())
+    }
+}
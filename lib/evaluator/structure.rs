@@ -1,9 +1,23 @@
 use crate::{
+    dictionary::Dictionary,
     error::BajzelError,
     parser::{Expr, Literal},
 };
 
-use super::{eval_expr_to_i64, syntax_err};
+use super::{eval_expr_to_i128, eval_expr_to_i64, syntax_err};
+
+/// Interesting values used by AFL (`-128`..`2147483647`)
+///
+#[rustfmt::skip]
+static AFL_INTERESTING: [i128; 27] = [
+    // 8-bit
+    -128, -1, 0, 1, 16, 32, 64, 100, 127,
+    // 16-bit
+    -32768, -129, 128, 255, 256, 512, 1000, 1024, 4096, 32767,
+    // 32-bit
+    -2147483648, -100663046, -32769, 32768, 65535, 65536, 100663045,
+    2147483647,
+];
 
 #[derive(Debug, Default)]
 pub struct GroupDefinition {
@@ -27,6 +41,12 @@ impl GroupDefinition {
     pub(crate) fn fields_iter(&self) -> impl Iterator<Item = &Field> {
         self.fields.iter()
     }
+
+    pub(crate) fn fields_iter_mut(
+        &mut self,
+    ) -> impl Iterator<Item = &mut Field> {
+        self.fields.iter_mut()
+    }
 }
 
 #[derive(Debug)]
@@ -50,20 +70,73 @@ pub enum FieldDefinition {
     /// For example, number 42069 will be represented as a string "42069"
     TextNumber(TextNumberDef),
 
+    /// Random string that can be displayed (only displayable characters)
+    ///
+    AsciiString(AsciiStringDef),
+
     /// Random number represented in byte form
     ///
     /// For example, number 305_419_896  can be represented as bytes:
     /// - big endian:     `0x12 0x34 0x56 0x78`
     /// - little endian:  `0x78 0x56 0x34 0x12`
-    // ByteNumber(ByteNumberDef),
+    ByteNumber(ByteNumberDef),
 
-    /// Random string that can be displayed (only displayable characters)
+    Bytes(BytesDef),
+
+    /// Fields of other group, such as a header of a file
     ///
-    AsciiString(AsciiStringDef),
+    Ref(RefDef),
 
-    ByteNumber(ByteNumberDef),
+    /// Value of a variable captured from a reply earlier in a session
+    ///
+    /// Empty until the variable is captured.
+    ///
+    Captured(String),
+}
 
-    Bytes(BytesDef),
+impl FieldDefinition {
+    /// Create a random field of a given type (such as `le_u16` or `string`)
+    /// with default attributes
+    ///
+    pub fn from_type(kind: &str) -> Result<Self, BajzelError> {
+        let mut field_def = None;
+        if kind.starts_with("le_") || kind.starts_with("be_") {
+            if let Ok(def) = ByteNumberDef::from_str(kind) {
+                field_def.replace(FieldDefinition::ByteNumber(def));
+            }
+        } else if kind == "string" {
+            field_def
+                .replace(FieldDefinition::AsciiString(AsciiStringDef::new()));
+        } else if kind == "bytes" {
+            field_def.replace(FieldDefinition::Bytes(BytesDef::new()));
+        } else if kind == "ref" {
+            field_def.replace(FieldDefinition::Ref(RefDef::new(None)));
+        } else if kind.starts_with('i') || kind.starts_with('u') {
+            if let Ok(def) = TextNumberDef::from_str(kind) {
+                field_def.replace(FieldDefinition::TextNumber(def));
+            }
+        }
+        field_def.ok_or_else(|| {
+            BajzelError::Syntax(format!(
+                "Unsupported variable field type ({})",
+                kind
+            ))
+        })
+    }
+
+    /// Names of attributes the field accepts
+    ///
+    pub fn attributes(&self) -> &'static [&'static str] {
+        match self {
+            FieldDefinition::ConstString(_) => &[],
+            FieldDefinition::Captured(_) => &[],
+            FieldDefinition::TextNumber(_) => &TextNumberDef::ATTRIBUTES,
+            FieldDefinition::AsciiString(_) => &AsciiStringDef::ATTRIBUTES,
+            FieldDefinition::ByteNumber(_) => &ByteNumberDef::ATTRIBUTES,
+            FieldDefinition::Bytes(_) => &BytesDef::ATTRIBUTES,
+            FieldDefinition::Ref(_) => &RefDef::ATTRIBUTES,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -71,6 +144,7 @@ pub struct TextNumberDef {
     pub format: NumberFormat,
     pub min_value: i128,
     pub max_value: i128,
+    pub dist: ValueDist,
     pub display: Option<NumberDisplayFormat>,
 }
 
@@ -80,18 +154,28 @@ pub struct ByteNumberDef {
     pub endianess: ByteOrder,
     pub min_value: i128,
     pub max_value: i128,
+    pub dist: ValueDist,
 }
 
 #[derive(Debug)]
 pub struct BytesDef {
     pub length_min: usize,
     pub length_max: usize,
+    pub dist: ValueDist,
+    pub dict: Option<Dictionary>,
 }
 
 #[derive(Debug)]
 pub struct AsciiStringDef {
     pub length_min: usize,
     pub length_max: usize,
+    pub dist: ValueDist,
+    pub dict: Option<Dictionary>,
+}
+
+#[derive(Debug)]
+pub struct RefDef {
+    pub group: Option<String>,
 }
 
 #[derive(Debug)]
@@ -106,6 +190,34 @@ pub enum NumberFormat {
     Uint64,
 }
 
+/// Distribution of values picked from a range (RANGE or LEN)
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ValueDist {
+    /// Every value is equally likely
+    ///
+    #[default]
+    Uniform,
+
+    /// Every order of magnitude is equally likely, so small values are
+    /// favored while huge ones still show up from time to time
+    ///
+    Log,
+
+    /// Values are centered around `mean`, clamped to the range
+    ///
+    Normal { mean: f64, sd: f64 },
+
+    /// Explicit list of `(value, weight)` pairs
+    ///
+    Weights(Vec<(i128, u32)>),
+
+    /// Members of an enumeration are favored, but any value of the range
+    /// shows up from time to time
+    ///
+    Enum(Vec<i128>),
+}
+
 #[derive(Debug)]
 pub enum NumberDisplayFormat {
     Binary,
@@ -124,6 +236,10 @@ pub enum ByteOrder {
 }
 
 impl ByteNumberDef {
+    /// Names of attributes accepted by `update`
+    ///
+    pub const ATTRIBUTES: [&'static str; 4] =
+        ["RANGE", "DIST", "WEIGHTS", "ENUM"];
     pub fn new(format: NumberFormat, endianess: ByteOrder) -> Self {
         let min = format.min_as_i128();
         let max = format.max_as_i128();
@@ -132,6 +248,7 @@ impl ByteNumberDef {
             endianess,
             min_value: min,
             max_value: max,
+            dist: ValueDist::Uniform,
         }
     }
 
@@ -182,11 +299,74 @@ impl ByteNumberDef {
         attr_name: &str,
         expr: Expr,
     ) -> Result<(), BajzelError> {
-        unimplemented!("ByteNumberDef: update {:?} -> {:?}", attr_name, expr);
+        match attr_name {
+            "RANGE" => self.set_range(expr),
+            "DIST" => self.set_dist(expr),
+            "WEIGHTS" => self.set_weights(expr),
+            "ENUM" => self.set_enum(expr),
+            _ => syntax_err("unsupported byte number attribute"),
+        }
+    }
+
+    fn set_range(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        let (min, max) = eval_expr_to_range(&expr, &self.format)?;
+        self.min_value = min;
+        self.max_value = max;
+        Ok(())
+    }
+
+    fn set_dist(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        self.dist = eval_expr_to_dist(&expr)?;
+        Ok(())
+    }
+
+    fn set_weights(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        let min = self.format.min_as_i128();
+        let max = self.format.max_as_i128();
+        self.dist = eval_expr_to_weights(&expr, min, max)?;
+        Ok(())
+    }
+
+    fn set_enum(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        let min = self.format.min_as_i128();
+        let max = self.format.max_as_i128();
+        self.dist = eval_expr_to_enum(&expr, min, max)?;
+        Ok(())
+    }
+
+    /// Represent a value in byte form of a given size and endianess
+    ///
+    pub fn encode(&self, value: i128) -> Vec<u8> {
+        let size = (self.format.bits() / 8) as usize;
+        let mut data = value.to_le_bytes()[0..size].to_vec();
+        if let ByteOrder::BigEndian = self.endianess {
+            data.reverse();
+        }
+        data
+    }
+
+    /// Interesting values of the number format that fit in RANGE
+    ///
+    pub fn interesting_values(&self) -> Vec<i128> {
+        values_in_range(
+            self.format.interesting_values(),
+            self.min_value,
+            self.max_value,
+        )
+    }
+
+    /// Values at the edges of RANGE
+    ///
+    pub fn boundary_values(&self) -> Vec<i128> {
+        boundary_values(self.min_value, self.max_value)
     }
 }
 
 impl TextNumberDef {
+    /// Names of attributes accepted by `update`
+    ///
+    pub const ATTRIBUTES: [&'static str; 4] =
+        ["RANGE", "DIST", "WEIGHTS", "ENUM"];
     pub fn new(format: NumberFormat) -> Self {
         let min = format.min_as_i128();
         let max = format.max_as_i128();
@@ -194,6 +374,7 @@ impl TextNumberDef {
             format,
             min_value: min,
             max_value: max,
+            dist: ValueDist::Uniform,
             display: None,
         }
     }
@@ -219,36 +400,73 @@ impl TextNumberDef {
     ) -> Result<(), BajzelError> {
         match attr_name {
             "RANGE" => self.set_range(expr),
+            "DIST" => self.set_dist(expr),
+            "WEIGHTS" => self.set_weights(expr),
+            "ENUM" => self.set_enum(expr),
             _ => syntax_err("unsupported text number attribute"),
         }
     }
 
     fn set_range(&mut self, expr: Expr) -> Result<(), BajzelError> {
-        match expr {
-            Expr::LiteralExpr(_) => {
-                syntax_err("RANGE(min max): expects exactly 2 values")
-            }
-            Expr::Group(v) => {
-                if v.len() == 2 {
-                    let min = eval_expr_to_i64(&v[0])?;
-                    let max = eval_expr_to_i64(&v[1])?;
-                    self.min_value = min as i128;
-                    self.max_value = max as i128;
-                    Ok(())
-                } else {
-                    syntax_err("RANGE(min max) expects exactly 2 values")
-                }
-            }
-            _ => syntax_err("LEN(...): unsupported expression"),
-        }
+        let (min, max) = eval_expr_to_range(&expr, &self.format)?;
+        self.min_value = min;
+        self.max_value = max;
+        Ok(())
+    }
+
+    fn set_dist(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        self.dist = eval_expr_to_dist(&expr)?;
+        Ok(())
+    }
+
+    fn set_weights(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        let min = self.format.min_as_i128();
+        let max = self.format.max_as_i128();
+        self.dist = eval_expr_to_weights(&expr, min, max)?;
+        Ok(())
+    }
+
+    fn set_enum(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        let min = self.format.min_as_i128();
+        let max = self.format.max_as_i128();
+        self.dist = eval_expr_to_enum(&expr, min, max)?;
+        Ok(())
+    }
+
+    /// Represent a value as a text
+    ///
+    pub fn encode(&self, value: i128) -> Vec<u8> {
+        value.to_string().into_bytes()
+    }
+
+    /// Interesting values of the number format that fit in RANGE
+    ///
+    pub fn interesting_values(&self) -> Vec<i128> {
+        values_in_range(
+            self.format.interesting_values(),
+            self.min_value,
+            self.max_value,
+        )
+    }
+
+    /// Values at the edges of RANGE
+    ///
+    pub fn boundary_values(&self) -> Vec<i128> {
+        boundary_values(self.min_value, self.max_value)
     }
 }
 
 impl AsciiStringDef {
+    /// Names of attributes accepted by `update`
+    ///
+    pub const ATTRIBUTES: [&'static str; 4] =
+        ["LEN", "DICT", "DIST", "WEIGHTS"];
     pub fn new() -> Self {
         Self {
             length_min: 0,
             length_max: 4096,
+            dist: ValueDist::Uniform,
+            dict: None,
         }
     }
 
@@ -259,10 +477,47 @@ impl AsciiStringDef {
     ) -> Result<(), BajzelError> {
         match attr_name {
             "LEN" => self.set_len(expr),
+            "DICT" => self.set_dict(expr),
+            "DIST" => self.set_dist(expr),
+            "WEIGHTS" => self.set_weights(expr),
             _ => syntax_err("unsupported string attribute name"),
         }
     }
 
+    /// Makes values to be picked from a dictionary file
+    ///
+    /// Syntax:
+    ///     DICT("path")    - path to a file in AFL dictionary format
+    ///
+    /// When set, LEN is ignored and values are picked from the dictionary.
+    ///
+    fn set_dict(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        self.dict = Some(eval_expr_to_dict(&expr)?);
+        Ok(())
+    }
+
+    /// Sets distribution of lengths picked from LEN
+    ///
+    /// Syntax:
+    ///     DIST(uniform)           - every length is equally likely (default)
+    ///     DIST(log)               - short lengths favored, long ones rare
+    ///     DIST(normal mean sd)    - lengths centered around `mean`
+    ///
+    fn set_dist(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        self.dist = eval_expr_to_dist(&expr)?;
+        Ok(())
+    }
+
+    /// Sets explicit lengths with their weights
+    ///
+    /// Syntax:
+    ///     WEIGHTS(len weight [len weight ...])
+    ///
+    fn set_weights(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        self.dist = eval_expr_to_weights(&expr, 0, i64::MAX as i128)?;
+        Ok(())
+    }
+
     /// Sets min and max number of characters in the output
     ///
     /// Syntax:
@@ -320,10 +575,16 @@ impl Default for AsciiStringDef {
 }
 
 impl BytesDef {
+    /// Names of attributes accepted by `update`
+    ///
+    pub const ATTRIBUTES: [&'static str; 4] =
+        ["LEN", "DICT", "DIST", "WEIGHTS"];
     pub fn new() -> Self {
         Self {
             length_min: 0,
             length_max: 4096,
+            dist: ValueDist::Uniform,
+            dict: None,
         }
     }
 
@@ -334,10 +595,28 @@ impl BytesDef {
     ) -> Result<(), BajzelError> {
         match attr_name {
             "LEN" => self.set_len(expr),
+            "DICT" => self.set_dict(expr),
+            "DIST" => self.set_dist(expr),
+            "WEIGHTS" => self.set_weights(expr),
             x => syntax_err(format!("bytes: unsupported attribute ({})", x)),
         }
     }
 
+    fn set_dict(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        self.dict = Some(eval_expr_to_dict(&expr)?);
+        Ok(())
+    }
+
+    fn set_dist(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        self.dist = eval_expr_to_dist(&expr)?;
+        Ok(())
+    }
+
+    fn set_weights(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        self.dist = eval_expr_to_weights(&expr, 0, i64::MAX as i128)?;
+        Ok(())
+    }
+
     fn set_len(&mut self, expr: Expr) -> Result<(), BajzelError> {
         match expr {
             Expr::LiteralExpr(literal) => match literal {
@@ -388,6 +667,194 @@ impl Default for BytesDef {
     }
 }
 
+impl RefDef {
+    /// Names of attributes accepted by `update`
+    ///
+    pub const ATTRIBUTES: [&'static str; 1] = ["TO"];
+    pub fn new(group: Option<String>) -> Self {
+        Self { group }
+    }
+
+    pub fn update(
+        &mut self,
+        attr_name: &str,
+        expr: Expr,
+    ) -> Result<(), BajzelError> {
+        match attr_name {
+            "TO" => self.set_group(expr),
+            x => syntax_err(format!("ref: unsupported attribute ({})", x)),
+        }
+    }
+
+    /// Sets name of a referenced group
+    ///
+    /// Syntax:
+    ///     TO(group_name)
+    ///
+    fn set_group(&mut self, expr: Expr) -> Result<(), BajzelError> {
+        match expr {
+            Expr::IdentExpr(name) => {
+                self.group = Some(name.to_string());
+                Ok(())
+            }
+            _ => syntax_err("TO(group_name): expected a group name"),
+        }
+    }
+}
+
+/// Extract (min, max) pair from a `RANGE(min max)` expression
+///
+/// Both values must fit in a given number format.
+///
+fn eval_expr_to_range(
+    expr: &Expr,
+    format: &NumberFormat,
+) -> Result<(i128, i128), BajzelError> {
+    match expr {
+        Expr::Group(v) if v.len() == 2 => {
+            let min = eval_expr_to_i128(&v[0])?;
+            let max = eval_expr_to_i128(&v[1])?;
+            if min > max {
+                return syntax_err("RANGE(min max): min > max is not allowed");
+            }
+            if min < format.min_as_i128() || max > format.max_as_i128() {
+                return syntax_err(format!(
+                    "RANGE(min max): values out of {:?} bounds",
+                    format
+                ));
+            }
+            Ok((min, max))
+        }
+        _ => syntax_err("RANGE(min max): expects exactly 2 values"),
+    }
+}
+
+/// Extract value distribution from a `DIST(...)` expression
+///
+fn eval_expr_to_dist(expr: &Expr) -> Result<ValueDist, BajzelError> {
+    let (name, params) = match expr {
+        Expr::IdentExpr(name) => (name, &[][..]),
+        Expr::Group(v) => match v.split_first() {
+            Some((Expr::IdentExpr(name), params)) => (name, params),
+            _ => return syntax_err("DIST(name ...): expected a name first"),
+        },
+        _ => return syntax_err("DIST(name ...): expected a name"),
+    };
+    match (name.to_lowercase().as_str(), params.len()) {
+        ("uniform", 0) => Ok(ValueDist::Uniform),
+        ("log", 0) => Ok(ValueDist::Log),
+        ("normal", 2) => {
+            let mean = eval_expr_to_i64(&params[0])? as f64;
+            let sd = eval_expr_to_i64(&params[1])? as f64;
+            if sd < 0.0 {
+                return syntax_err(
+                    "DIST(normal mean sd): sd < 0 is not allowed",
+                );
+            }
+            Ok(ValueDist::Normal { mean, sd })
+        }
+        ("normal", _) => syntax_err("DIST(normal mean sd): expects 2 values"),
+        (x, _) => syntax_err(format!("DIST: unsupported distribution ({})", x)),
+    }
+}
+
+/// Extract `(value, weight)` pairs from a `WEIGHTS(...)` expression
+///
+/// Values must be in `min..=max` range and at least one weight must be
+/// greater than zero.
+///
+fn eval_expr_to_weights(
+    expr: &Expr,
+    min: i128,
+    max: i128,
+) -> Result<ValueDist, BajzelError> {
+    let v = match expr {
+        Expr::Group(v) if v.len() % 2 == 0 => v,
+        _ => {
+            return syntax_err("WEIGHTS(value weight ...): expects pairs");
+        }
+    };
+    let mut weights = Vec::with_capacity(v.len() / 2);
+    for pair in v.chunks(2) {
+        let value = eval_expr_to_i128(&pair[0])?;
+        let weight = eval_expr_to_i64(&pair[1])?;
+        if !(min..=max).contains(&value) {
+            return syntax_err(format!(
+                "WEIGHTS: value out of bounds ({})",
+                value
+            ));
+        }
+        if !(0..=u32::MAX as i64).contains(&weight) {
+            return syntax_err(format!("WEIGHTS: invalid weight ({})", weight));
+        }
+        weights.push((value, weight as u32));
+    }
+    if weights.iter().all(|(_, weight)| *weight == 0) {
+        return syntax_err("WEIGHTS: at least one weight must be positive");
+    }
+    Ok(ValueDist::Weights(weights))
+}
+
+/// Extract values of enumeration members from an `ENUM(...)` expression
+///
+/// The name of an enumeration is expected to be already replaced with
+/// values of its members by the environment. Values must be in `min..=max`
+/// range.
+///
+fn eval_expr_to_enum(
+    expr: &Expr,
+    min: i128,
+    max: i128,
+) -> Result<ValueDist, BajzelError> {
+    let v = match expr {
+        Expr::Group(v) => v,
+        _ => return syntax_err("ENUM(name): expected an enumeration"),
+    };
+    let mut values = Vec::with_capacity(v.len());
+    for expr in v {
+        let value = eval_expr_to_i128(expr)?;
+        if !(min..=max).contains(&value) {
+            return syntax_err(format!(
+                "ENUM: value out of bounds ({})",
+                value
+            ));
+        }
+        values.push(value);
+    }
+    Ok(ValueDist::Enum(values))
+}
+
+fn values_in_range(values: Vec<i128>, min: i128, max: i128) -> Vec<i128> {
+    values
+        .into_iter()
+        .filter(|x| (min..=max).contains(x))
+        .collect()
+}
+
+fn boundary_values(min: i128, max: i128) -> Vec<i128> {
+    let mut values = vec![min, min + 1, max - 1, max];
+    values.retain(|x| (min..=max).contains(x));
+    values.dedup();
+    values
+}
+
+/// Load dictionary from a path given in the expression
+///
+/// Path is expected to be already resolved by the environment.
+///
+fn eval_expr_to_dict(expr: &Expr) -> Result<Dictionary, BajzelError> {
+    match expr {
+        Expr::LiteralExpr(Literal::StringLiteral(path)) => {
+            let dict = Dictionary::load(path)?;
+            if dict.is_empty() {
+                return syntax_err(format!("DICT({}): no entries", path));
+            }
+            Ok(dict)
+        }
+        _ => syntax_err("DICT(path): expected a string literal"),
+    }
+}
+
 impl NumberFormat {
     pub fn min_as_i128(&self) -> i128 {
         match self {
@@ -402,6 +869,35 @@ impl NumberFormat {
         }
     }
 
+    pub fn bits(&self) -> u32 {
+        match self {
+            NumberFormat::Int8 | NumberFormat::Uint8 => 8,
+            NumberFormat::Int16 | NumberFormat::Uint16 => 16,
+            NumberFormat::Int32 | NumberFormat::Uint32 => 32,
+            NumberFormat::Int64 | NumberFormat::Uint64 => 64,
+        }
+    }
+
+    /// Values that are likely to trigger edge cases in a target
+    ///
+    /// These are: format limits, small numbers, powers of two (+/- 1) and
+    /// well-known AFL values that fit in the format.
+    ///
+    pub fn interesting_values(&self) -> Vec<i128> {
+        let min = self.min_as_i128();
+        let max = self.max_as_i128();
+        let mut values = vec![min, min + 1, max - 1, max, -1, 0, 1];
+        for bit in 1..self.bits() {
+            let x = 1i128 << bit;
+            values.extend([x - 1, x, x + 1, -x - 1, -x, -x + 1]);
+        }
+        values.extend(AFL_INTERESTING);
+        values.retain(|x| (min..=max).contains(x));
+        values.sort_unstable();
+        values.dedup();
+        values
+    }
+
     pub fn max_as_i128(&self) -> i128 {
         match self {
             NumberFormat::Int8 => i8::MAX as i128,
@@ -0,0 +1,455 @@
+use super::{
+    structure::{FieldDefinition, RefDef},
+    syntax_err, Evaluator, ProgramEnv,
+};
+use crate::{
+    error::BajzelError,
+    parser::{Expr, Ident, Literal, Statement},
+};
+use itertools::Itertools;
+use std::collections::HashSet;
+
+/// Group definition parameterized with types, group names and values,
+/// expanded into a concrete group for every distinct list of arguments
+///
+/// Example:
+///
+/// ```fuzl
+/// DEFINE tlv(T, max)
+///     T     AS tag
+///     u8    AS len
+///     bytes AS value -> LEN(0 max),
+///
+/// DEFINE file
+///     ref AS small FROM tlv(u8, 16)      # group "tlv(u8, 16)"
+///     ref AS large FROM tlv(le_u16, 255) # group "tlv(le_u16, 255)"
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct TemplateDefinition {
+    pub params: Vec<String>,
+
+    /// How parameters are used by fields (`None` when only passed to other
+    /// templates), which tells what arguments they accept
+    ///
+    kinds: Vec<Option<ParamKind>>,
+
+    /// Statements defining fields, with parameters left in place
+    ///
+    body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParamKind {
+    /// Type of a field, such as `le_u16`
+    ///
+    Type,
+
+    /// Group of a `ref` field (`FROM` or `TO`)
+    ///
+    Group,
+
+    /// Enumeration given to `ENUM`
+    ///
+    Enum,
+
+    /// Value used in attributes, such as `255`
+    ///
+    Value,
+}
+
+impl ParamKind {
+    fn name(&self) -> &'static str {
+        match self {
+            ParamKind::Type => "a type",
+            ParamKind::Group => "a group",
+            ParamKind::Enum => "an enumeration",
+            ParamKind::Value => "a value",
+        }
+    }
+}
+
+/// Instance of a template waiting to be expanded
+///
+#[derive(Debug, Clone)]
+pub(crate) struct Instance {
+    /// Name of the group the instance is expanded into
+    ///
+    name: String,
+    template: String,
+    args: Vec<Expr>,
+}
+
+impl TemplateDefinition {
+    fn new(params: Vec<String>) -> Self {
+        TemplateDefinition {
+            kinds: vec![None; params.len()],
+            params,
+            body: vec![],
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.params.iter().position(|x| x == name)
+    }
+
+    /// Remember how a parameter is used, making sure it's used in a single
+    /// way
+    ///
+    fn use_param(
+        &mut self,
+        name: &str,
+        kind: ParamKind,
+    ) -> Result<(), BajzelError> {
+        let i = match self.position(name) {
+            Some(i) => i,
+            None if kind == ParamKind::Type => {
+                return syntax_err(format!("unknown parameter ({})", name))
+            }
+            None => return Ok(()),
+        };
+        match self.kinds[i] {
+            Some(x) if x != kind => syntax_err(format!(
+                "parameter {} used both as {} and {}",
+                name,
+                x.name(),
+                kind.name()
+            )),
+            _ => {
+                self.kinds[i] = Some(kind);
+                Ok(())
+            }
+        }
+    }
+
+    /// Statements of the body with parameters replaced by arguments
+    ///
+    fn expand(&self, args: &[Expr]) -> Vec<Statement> {
+        let arg = |name: &str| self.position(name).map(|i| args[i].clone());
+        let name_of = |ident: Ident| match arg(&ident) {
+            Some(Expr::IdentExpr(x)) => x,
+            _ => ident,
+        };
+        self.body
+            .iter()
+            .cloned()
+            .map(|statement| match statement {
+                Statement::DefineParamField(kind, alias) => {
+                    Statement::DefineVariableField(
+                        name_of(kind).to_string(),
+                        alias,
+                    )
+                }
+                Statement::DefineRefField(group, alias) => {
+                    Statement::DefineRefField(name_of(group), alias)
+                }
+                Statement::DefineTemplateRefField(name, nested, alias) => {
+                    let nested = nested
+                        .into_iter()
+                        .map(|x| substitute(x, &arg))
+                        .collect();
+                    Statement::DefineTemplateRefField(name, nested, alias)
+                }
+                Statement::UpdateField(attr, expr) => {
+                    Statement::UpdateField(attr, substitute(expr, &arg))
+                }
+                x => x,
+            })
+            .collect()
+    }
+}
+
+impl TemplateDefinition {
+    /// Rename groups and templates the body refers to (but not parameters)
+    /// when the template is imported
+    ///
+    pub(crate) fn rename<F>(&mut self, prefixed: F, groups: &HashSet<String>)
+    where
+        F: Fn(&str) -> String,
+    {
+        let is_param = |name: &str| self.params.iter().any(|x| x == name);
+        for statement in self.body.iter_mut() {
+            match statement {
+                Statement::DefineRefField(group, _) if !is_param(group) => {
+                    *group = Ident::from(prefixed(group).as_str());
+                }
+                Statement::UpdateField(attr, Expr::IdentExpr(group))
+                    if attr.as_str() == "TO" && !is_param(group) =>
+                {
+                    *group = Ident::from(prefixed(group).as_str());
+                }
+                Statement::DefineTemplateRefField(name, args, _) => {
+                    *name = Ident::from(prefixed(name).as_str());
+                    for arg in args.iter_mut() {
+                        match arg {
+                            Expr::IdentExpr(x)
+                                if groups.contains(x.as_str()) =>
+                            {
+                                *x = Ident::from(prefixed(x).as_str());
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl ProgramEnv {
+    /// Create a new group template and make it a current one
+    ///
+    pub fn create_template(
+        &mut self,
+        name: Ident,
+        params: Vec<Ident>,
+    ) -> Result<(), BajzelError> {
+        if self.templates.contains_key(name.as_str()) {
+            return syntax_err(format!("DEFINE {} already defined", *name));
+        }
+        let params: Vec<_> = params.iter().map(|x| x.to_string()).collect();
+        if let Some(x) = params.iter().duplicates().next() {
+            return syntax_err(format!(
+                "DEFINE {}: duplicate parameter ({})",
+                *name, x
+            ));
+        }
+        self.templates
+            .insert(name.to_string(), TemplateDefinition::new(params));
+        self.cur_template = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Add a statement defining (or updating) a field to a current template
+    ///
+    /// Constants (and enumerations given to `ENUM`) are resolved right away,
+    /// unless shadowed by parameters.
+    ///
+    /// Note: Might panic if called when no template is created.
+    ///
+    pub fn add_template_statement(
+        &mut self,
+        statement: Statement,
+    ) -> Result<(), BajzelError> {
+        let name = self
+            .cur_template
+            .clone()
+            .expect("current template should not be missing");
+        let template = &self.templates[&name];
+        let is_param = |x: &Expr| match x {
+            Expr::IdentExpr(x) => template.position(x).is_some(),
+            _ => false,
+        };
+        let constant = |name: &str| match template.position(name) {
+            Some(_) => None,
+            None => self.consts.get(name).cloned().map(Expr::LiteralExpr),
+        };
+        let (statement, used) = match statement {
+            Statement::DefineParamField(kind, alias) => {
+                let used = vec![(kind.to_string(), ParamKind::Type)];
+                (Statement::DefineParamField(kind, alias), used)
+            }
+            Statement::DefineRefField(group, alias) => {
+                let used = vec![(group.to_string(), ParamKind::Group)];
+                (Statement::DefineRefField(group, alias), used)
+            }
+            Statement::DefineTemplateRefField(name, args, alias) => {
+                let args = args
+                    .into_iter()
+                    .map(|x| substitute(x, &constant))
+                    .collect();
+                (Statement::DefineTemplateRefField(name, args, alias), vec![])
+            }
+            Statement::UpdateField(attr, expr) => {
+                let expr = match attr.as_str() {
+                    "ENUM" if !is_param(&expr) => self.resolve_enum(expr)?,
+                    _ => substitute(expr, &constant),
+                };
+                let kind = match (attr.as_str(), &expr) {
+                    ("TO", Expr::IdentExpr(_)) => ParamKind::Group,
+                    ("ENUM", Expr::IdentExpr(_)) => ParamKind::Enum,
+                    _ => ParamKind::Value,
+                };
+                let mut used = vec![];
+                params_in(&expr, &mut used);
+                let used = used.into_iter().map(|x| (x, kind)).collect();
+                (Statement::UpdateField(attr, expr), used)
+            }
+            x => (x, vec![]),
+        };
+        let template = self
+            .templates
+            .get_mut(&name)
+            .expect("current template assigned only when it's added");
+        for (param, kind) in used {
+            template
+                .use_param(&param, kind)
+                .map_err(|e| in_context(&name, e))?;
+        }
+        template.body.push(statement);
+        Ok(())
+    }
+
+    /// Define a ref field filled with fields of a template instance, which
+    /// is expanded into a group at the end of the program
+    ///
+    pub fn create_template_ref_field(
+        &mut self,
+        template: Ident,
+        args: Vec<Expr>,
+        alias: Option<Ident>,
+    ) {
+        let args: Vec<_> =
+            args.into_iter().map(|x| self.eval_expr(x)).collect();
+        let name = format!(
+            "{}({})",
+            *template,
+            args.iter().map(describe_arg).join(", ")
+        );
+        let def = FieldDefinition::Ref(RefDef::new(Some(name.clone())));
+        self.create_field(def, alias);
+        self.instances.push(Instance {
+            name,
+            template: template.to_string(),
+            args,
+        });
+    }
+
+    /// Expand instances of templates into groups (including instances used
+    /// by templates), checking their arguments
+    ///
+    pub(crate) fn expand_instances(mut self) -> Result<Self, BajzelError> {
+        while let Some(instance) = self.instances.pop() {
+            if self.groups.contains_key(&instance.name) {
+                continue;
+            }
+            let template =
+                self.templates.get(&instance.template).ok_or_else(|| {
+                    BajzelError::Syntax(format!(
+                        "ref: unknown template ({})",
+                        instance.template
+                    ))
+                })?;
+            let body = check_args(template, &instance.args)
+                .map(|_| template.expand(&instance.args))
+                .map_err(|e| in_context(&instance.name, e))?;
+
+            self.create_group(Ident::from(instance.name.as_str()));
+            let mut evaluator = Evaluator::DefiningFields(self);
+            for statement in body {
+                evaluator = evaluator
+                    .eval(statement)
+                    .map_err(|e| in_context(&instance.name, e))?;
+            }
+            self = match evaluator {
+                Evaluator::DefiningFields(ctx)
+                | Evaluator::DefiningFieldAttr(ctx)
+                | Evaluator::UpdatingFieldAttrs(ctx) => ctx,
+                _ => return Err(BajzelError::NotConstructedProperly),
+            };
+        }
+        self.cur_group = None;
+        self.cur_field = None;
+        Ok(self)
+    }
+
+    /// Whether there's a template of a given name
+    ///
+    pub fn is_template(&self, name: &str) -> bool {
+        self.templates.contains_key(name)
+    }
+}
+
+/// Make sure arguments match parameters of a template in number and in
+/// the way parameters are used
+///
+fn check_args(
+    template: &TemplateDefinition,
+    args: &[Expr],
+) -> Result<(), BajzelError> {
+    if args.len() != template.params.len() {
+        return syntax_err(format!(
+            "expects {} argument(s), got {}",
+            template.params.len(),
+            args.len()
+        ));
+    }
+    let params = template.params.iter().zip(&template.kinds);
+    for ((param, kind), arg) in params.zip(args) {
+        let valid = match (kind, arg) {
+            (None, _) => true,
+            (Some(ParamKind::Type), Expr::IdentExpr(x)) => {
+                x.as_str() != "ref" && FieldDefinition::from_type(x).is_ok()
+            }
+            (Some(ParamKind::Group), Expr::IdentExpr(x)) => {
+                FieldDefinition::from_type(x).is_err()
+            }
+            (Some(ParamKind::Enum), Expr::IdentExpr(_)) => true,
+            (Some(ParamKind::Value), Expr::LiteralExpr(_)) => true,
+            _ => false,
+        };
+        if !valid {
+            return syntax_err(format!(
+                "{} expects {} ({})",
+                param,
+                kind.map_or("", |x| x.name()),
+                describe_arg(arg)
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Replace names in an expression with what a lookup returns for them
+///
+fn substitute<F>(expr: Expr, lookup: &F) -> Expr
+where
+    F: Fn(&str) -> Option<Expr>,
+{
+    match expr {
+        Expr::IdentExpr(name) => lookup(&name).unwrap_or(Expr::IdentExpr(name)),
+        Expr::Group(v) => {
+            Expr::Group(v.into_iter().map(|x| substitute(x, lookup)).collect())
+        }
+        x => x,
+    }
+}
+
+/// Names used in an expression
+///
+fn params_in(expr: &Expr, names: &mut Vec<String>) {
+    match expr {
+        Expr::IdentExpr(name) => names.push(name.to_string()),
+        Expr::Group(v) => v.iter().for_each(|x| params_in(x, names)),
+        _ => (),
+    }
+}
+
+/// Source form of a template argument, as used in names of instances
+///
+fn describe_arg(expr: &Expr) -> String {
+    match expr {
+        Expr::IdentExpr(x) => x.to_string(),
+        Expr::LiteralExpr(Literal::IntegerLiteral(x)) => x.to_string(),
+        Expr::LiteralExpr(Literal::StringLiteral(x)) => format!("\"{}\"", x),
+        Expr::LiteralExpr(Literal::BytesLiteral(x)) => {
+            format!("`{}`", x.iter().map(|x| format!("{:02x}", x)).join(" "))
+        }
+        Expr::LiteralExpr(Literal::Reserved(x)) => x.to_string(),
+        Expr::Group(v) => v.iter().map(describe_arg).join(" "),
+        Expr::Empty => String::new(),
+    }
+}
+
+/// Prefix a syntax error with a name of a template (or an instance) it
+/// comes from
+///
+fn in_context(name: &str, e: BajzelError) -> BajzelError {
+    match e {
+        BajzelError::Syntax(msg) => {
+            BajzelError::Syntax(format!("{}: {}", name, msg))
+        }
+        x => x,
+    }
+}
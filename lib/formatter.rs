@@ -0,0 +1,545 @@
+use crate::{
+    error::BajzelError,
+    lexer::{lex_spanned, lex_tokens, Spanned, Token, Tokens},
+    parser::{parse_tokens, Program},
+};
+use std::ops::Range;
+
+/// Indentation of fields, attribute updates, steps and parameters
+///
+const INDENT: &str = "    ";
+
+/// Spaces between code and a comment at the end of the same line
+///
+const COMMENT_GAP: &str = "  ";
+
+/// Re-emit a program in the canonical form:
+///
+/// - keywords in upper case, one statement per line,
+/// - statements of blocks indented, a blank line between blocks,
+/// - `AS`, `->`, `=` and trailing comments aligned in columns,
+/// - comments kept where they were, at most one blank line in a row.
+///
+/// Fails when the program doesn't parse.
+///
+pub fn format_source(input: &str) -> Result<String, BajzelError> {
+    let program = parse(input)?;
+    let spanned = lex_spanned(input)
+        .map_err(|_| BajzelError::Syntax("lexer failed".to_owned()))?;
+    let (comments, code): (Vec<_>, Vec<_>) = spanned
+        .into_iter()
+        .partition(|(token, _)| matches!(token, Token::Comment(_)));
+
+    let mut pieces = vec![];
+    let mut pos = 0;
+    while pos < code.len() {
+        let (item, next) = parse_item(&code, pos)?;
+        let span = code[pos].1.start..code[next - 1].1.end;
+        pieces.push((span, Piece::Item(item)));
+        pos = next;
+    }
+    for (token, span) in comments {
+        if let Token::Comment(text) = token {
+            let line_start =
+                input[..span.start].rfind('\n').map_or(0, |x| x + 1);
+            let indented = span.start > line_start;
+            pieces.push((span, Piece::Comment(text.to_owned(), indented)));
+        }
+    }
+    pieces.sort_by_key(|(span, _)| span.start);
+
+    let mut lines = layout(input, pieces);
+    align(&mut lines);
+    let output = render(&lines);
+
+    if parse(&output)? != program {
+        return Err(BajzelError::NotConstructedProperly);
+    }
+    Ok(output)
+}
+
+fn parse(input: &str) -> Result<Program, BajzelError> {
+    let tokens = lex_tokens(input)
+        .map_err(|_| BajzelError::Syntax("lexer failed".to_owned()))?;
+    parse_tokens(Tokens::new(&tokens)).map_err(BajzelError::Syntax)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Import,
+    Const,
+
+    /// `DEFINE`, `SEQUENCE`, `GENERATE` and `ENUM`
+    ///
+    Block,
+    Where,
+    Field,
+
+    /// Attribute updates and sequence steps
+    ///
+    Update,
+    Param,
+
+    /// Member of an enumeration
+    ///
+    Member,
+
+    /// `}` closing an enumeration
+    ///
+    Close,
+}
+
+#[derive(Debug)]
+struct Item {
+    kind: Kind,
+
+    /// Parts aligned with the same parts of neighbouring items
+    ///
+    columns: Vec<String>,
+
+    /// Width of the longest item in the run, where a trailing comment goes
+    ///
+    width: usize,
+}
+
+enum Piece {
+    Item(Item),
+
+    /// Comment text and whether it was indented in the input
+    ///
+    Comment(String, bool),
+}
+
+enum Line {
+    Blank,
+    Comment(String, bool),
+    Item(Item, Option<String>),
+}
+
+/// Parse a single statement starting at `pos`, returning it and the
+/// position of the next one
+///
+fn parse_item(
+    code: &[Spanned],
+    mut pos: usize,
+) -> Result<(Item, usize), BajzelError> {
+    let token = |pos: usize| code.get(pos).map(|(token, _)| token);
+    let unexpected = |pos: usize| {
+        BajzelError::Syntax(format!("cannot format at {:?}", token(pos)))
+    };
+    let ident = |pos: usize| match token(pos) {
+        Some(Token::Ident(x)) => Ok(x.to_string()),
+        _ => Err(unexpected(pos)),
+    };
+
+    let (kind, columns) = match token(pos).ok_or_else(|| unexpected(pos))? {
+        Token::Import => {
+            let path = token(pos + 1).ok_or_else(|| unexpected(pos + 1))?;
+            pos += 2;
+            (Kind::Import, vec![format!("IMPORT {}", text(path))])
+        }
+        Token::Const => {
+            let name = ident(pos + 1)?;
+            let value = token(pos + 3).ok_or_else(|| unexpected(pos + 3))?;
+            pos += 4;
+            (
+                Kind::Const,
+                vec![format!("CONST {}", name), format!("= {}", text(value))],
+            )
+        }
+        Token::Enum => {
+            let name = ident(pos + 1)?;
+            pos += 3;
+            (Kind::Block, vec![format!("ENUM {} {{", name)])
+        }
+        Token::RightBrace => {
+            pos += 1;
+            (Kind::Close, vec!["}".to_owned()])
+        }
+        // Members end with a comma (optional for the last one), unlike
+        // generator parameters
+        Token::Ident(name)
+            if token(pos + 1) == Some(&Token::Assign)
+                && matches!(
+                    token(pos + 3),
+                    Some(Token::Comma | Token::RightBrace)
+                ) =>
+        {
+            let value = token(pos + 2).ok_or_else(|| unexpected(pos + 2))?;
+            pos += 3;
+            if token(pos) == Some(&Token::Comma) {
+                pos += 1;
+            }
+            (
+                Kind::Member,
+                vec![name.to_string(), format!("= {},", text(value))],
+            )
+        }
+        Token::Define | Token::Sequence => {
+            let keyword = match token(pos) {
+                Some(Token::Define) => "DEFINE",
+                _ => "SEQUENCE",
+            };
+            let name = ident(pos + 1)?;
+            pos += 2;
+            let params = parse_args(code, &mut pos);
+            (Kind::Block, vec![format!("{} {}{}", keyword, name, params)])
+        }
+        Token::Generate => {
+            let mut header = format!("GENERATE {}", ident(pos + 1)?);
+            pos += 2;
+            if token(pos) == Some(&Token::With) {
+                pos += 1;
+            }
+            if token(pos + 1) == Some(&Token::Assign) {
+                header.push_str(" WITH");
+            }
+            (Kind::Block, vec![header])
+        }
+        Token::Where => {
+            pos += 1;
+            (Kind::Where, vec!["WHERE".to_owned()])
+        }
+        Token::Ident(name) if token(pos + 1) == Some(&Token::Assign) => {
+            let value = token(pos + 2).ok_or_else(|| unexpected(pos + 2))?;
+            pos += 3;
+            (
+                Kind::Param,
+                vec![name.to_string(), format!("= {}", text(value))],
+            )
+        }
+        // Field of a template whose type is a parameter
+        Token::Ident(name) if token(pos + 1) == Some(&Token::As) => {
+            let def = name.to_string();
+            pos += 1;
+            let alias = parse_alias(code, &mut pos)?;
+            let attrs = parse_attrs(code, &mut pos)?;
+            (Kind::Field, vec![def, alias, attrs])
+        }
+        Token::Ident(name) => {
+            let name = name.to_string();
+            pos += 1;
+            let attrs = parse_attrs(code, &mut pos)?;
+            (Kind::Update, vec![name, attrs])
+        }
+        Token::Reference => {
+            let def = format!("${}", ident(pos + 1)?);
+            pos += 2;
+            let alias = parse_alias(code, &mut pos)?;
+            let attrs = parse_attrs(code, &mut pos)?;
+            (Kind::Field, vec![def, alias, attrs])
+        }
+        def => {
+            let def = text(def);
+            pos += 1;
+            let alias = parse_alias(code, &mut pos)?;
+            let attrs = parse_attrs(code, &mut pos)?;
+            (Kind::Field, vec![def, alias, attrs])
+        }
+    };
+    let item = Item {
+        kind,
+        columns,
+        width: 0,
+    };
+    Ok((item, pos))
+}
+
+/// Parse optional `AS alias` and `FROM group` (or `FROM template(args)`)
+/// of a field
+///
+fn parse_alias(
+    code: &[Spanned],
+    pos: &mut usize,
+) -> Result<String, BajzelError> {
+    let mut parts = vec![];
+    for (keyword, text) in [(Token::As, "AS"), (Token::From, "FROM")] {
+        if code.get(*pos).map(|(token, _)| token) == Some(&keyword) {
+            match code.get(*pos + 1) {
+                Some((Token::Ident(x), _)) => {
+                    *pos += 2;
+                    let args = parse_args(code, pos);
+                    parts.push(format!("{} {}{}", text, x, args));
+                }
+                _ => {
+                    return Err(BajzelError::Syntax(format!(
+                        "missing identifier after {}",
+                        text
+                    )))
+                }
+            }
+        }
+    }
+    Ok(parts.join(" "))
+}
+
+/// Parse optional `(a, b, ...)` parameters or arguments of a template
+///
+fn parse_args(code: &[Spanned], pos: &mut usize) -> String {
+    if code.get(*pos).map(|(token, _)| token) != Some(&Token::LeftParen) {
+        return String::new();
+    }
+    *pos += 1;
+    let mut args = vec![];
+    while let Some((token, _)) = code.get(*pos) {
+        *pos += 1;
+        match token {
+            Token::RightParen => break,
+            Token::Comma => (),
+            x => args.push(text(x)),
+        }
+    }
+    format!("({})", args.join(", "))
+}
+
+/// Parse optional `-> ATTR(args) ... ,` of a field, update or step
+///
+fn parse_attrs(
+    code: &[Spanned],
+    pos: &mut usize,
+) -> Result<String, BajzelError> {
+    if code.get(*pos).map(|(token, _)| token) != Some(&Token::RightArrow) {
+        return Ok(String::new());
+    }
+    *pos += 1;
+    let mut attrs = vec![];
+    loop {
+        match code.get(*pos).map(|(token, _)| token) {
+            Some(Token::Comma) => break,
+            Some(token @ (Token::Ident(_) | Token::Enum)) => {
+                let name = match token {
+                    Token::Ident(name) => name,
+                    _ => "ENUM",
+                };
+                *pos += 1;
+                let mut args = vec![];
+                if code.get(*pos).map(|(token, _)| token)
+                    == Some(&Token::LeftParen)
+                {
+                    *pos += 1;
+                    while let Some((token, _)) = code.get(*pos) {
+                        *pos += 1;
+                        match token {
+                            Token::RightParen => break,
+                            x => args.push(text(x)),
+                        }
+                    }
+                }
+                attrs.push(format!("{}({})", name, args.join(" ")));
+            }
+            x => {
+                return Err(BajzelError::Syntax(format!(
+                    "cannot format attribute at {:?}",
+                    x
+                )))
+            }
+        }
+    }
+    *pos += 1;
+    Ok(format!("-> {},", attrs.join(" ")))
+}
+
+/// Source form of a token appearing in a statement
+///
+fn text(token: &Token) -> String {
+    match token {
+        Token::Bytes(x) => {
+            let bytes: Vec<_> =
+                x.iter().map(|x| format!("{:02x}", x)).collect();
+            format!("`{}`", bytes.join(" "))
+        }
+        Token::Ident(x) | Token::Type(x) => x.to_string(),
+        Token::IntegerLiteral(x) => x.to_string(),
+        Token::ReservedIdent(x) => x.to_string(),
+        Token::StringLiteral(x) => format!("\"{}\"", x),
+        Token::TypeArray(x, size) => format!("{}[{}]", x, size),
+        x => format!("{:?}", x),
+    }
+}
+
+/// Turn statements and comments into lines, keeping comments on the line
+/// of a statement and (single) blank lines of the input
+///
+fn layout(input: &str, pieces: Vec<(Range<usize>, Piece)>) -> Vec<Line> {
+    let mut lines: Vec<Line> = vec![];
+    let mut end = 0;
+    for (span, piece) in pieces {
+        let gap = input.get(end..span.start).unwrap_or("\n");
+        let newlines = gap.matches('\n').count();
+        end = end.max(span.end);
+        match (piece, lines.last_mut()) {
+            (Piece::Comment(x, _), Some(Line::Item(_, comment @ None)))
+                if newlines == 0 =>
+            {
+                *comment = Some(x);
+                continue;
+            }
+            (piece, _) => {
+                if newlines > 1 && !lines.is_empty() {
+                    lines.push(Line::Blank);
+                }
+                lines.push(match piece {
+                    Piece::Item(item) => Line::Item(item, None),
+                    Piece::Comment(x, indented) => Line::Comment(x, indented),
+                });
+            }
+        }
+    }
+
+    // Blocks are separated with a blank line, placed before unindented
+    // comments directly above a block
+    let mut i = 0;
+    while i < lines.len() {
+        if matches!(&lines[i], Line::Item(item, _) if item.kind == Kind::Block)
+        {
+            let mut start = i;
+            while start > 0
+                && matches!(lines[start - 1], Line::Comment(_, false))
+            {
+                start -= 1;
+            }
+            if start > 0 && !matches!(lines[start - 1], Line::Blank) {
+                lines.insert(start, Line::Blank);
+                i += 1;
+            }
+        }
+        i += 1;
+    }
+    lines
+}
+
+/// Pad columns of items so they line up within runs of items of the same
+/// kind (interleaved comments and blank lines don't break the run)
+///
+fn align(lines: &mut [Line]) {
+    let mut start = 0;
+    while start < lines.len() {
+        let kind = match &lines[start] {
+            Line::Item(item, _) => item.kind,
+            _ => {
+                start += 1;
+                continue;
+            }
+        };
+        let mut end = start + 1;
+        while end < lines.len() {
+            match &lines[end] {
+                Line::Item(item, _) if item.kind != kind => break,
+                _ => end += 1,
+            }
+        }
+        let run = &mut lines[start..end];
+
+        let columns = run
+            .iter()
+            .filter_map(|line| match line {
+                Line::Item(item, _) => Some(item.columns.len()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        for column in 0..columns {
+            let width = |line: &Line| match line {
+                Line::Item(item, _) if has_more(item, column) => {
+                    item.columns[column].len()
+                }
+                _ => 0,
+            };
+            let width = run.iter().map(width).max().unwrap_or(0);
+            for line in run.iter_mut() {
+                if let Line::Item(item, _) = line {
+                    if has_more(item, column) {
+                        let cell = &mut item.columns[column];
+                        let pad = width - cell.len();
+                        cell.push_str(&" ".repeat(pad));
+                    }
+                }
+            }
+        }
+
+        // Trailing comments line up after the longest item of the run
+        let code_width = run
+            .iter()
+            .filter_map(|line| match line {
+                Line::Item(item, _) => Some(code(item).len()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        for line in run.iter_mut() {
+            if let Line::Item(item, _) = line {
+                item.width = code_width;
+            }
+        }
+        start = end;
+    }
+}
+
+/// Whether a non-empty column follows the given one
+///
+fn has_more(item: &Item, column: usize) -> bool {
+    item.columns.iter().skip(column + 1).any(|x| !x.is_empty())
+}
+
+fn code(item: &Item) -> String {
+    let columns: Vec<_> = item
+        .columns
+        .iter()
+        .enumerate()
+        .filter(|(i, x)| !x.is_empty() || has_more(item, *i))
+        .map(|(_, x)| x.as_str())
+        .collect();
+    columns.join(" ")
+}
+
+fn render(lines: &[Line]) -> String {
+    let mut output = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        match line {
+            Line::Blank => (),
+            Line::Comment(text, indented) => {
+                // Indented comments stay in the block they're in, others
+                // take indentation of the following statement
+                let item_indent = |line: &Line| match line {
+                    Line::Item(item, _) => Some(indent(item)),
+                    _ => None,
+                };
+                let previous = lines[..i].iter().rev().find_map(item_indent);
+                let next = lines[i..].iter().find_map(item_indent);
+                let indent = match (indented, previous, next) {
+                    (true, Some(_), _) => INDENT,
+                    (_, _, Some(x)) | (_, Some(x), None) => x,
+                    _ => "",
+                };
+                output.push_str(indent);
+                output.push('#');
+                output.push_str(text);
+            }
+            Line::Item(item, comment) => {
+                output.push_str(indent(item));
+                let code = code(item);
+                output.push_str(&code);
+                if let Some(text) = comment {
+                    let pad = item.width.saturating_sub(code.len());
+                    output.push_str(&" ".repeat(pad));
+                    output.push_str(COMMENT_GAP);
+                    output.push('#');
+                    output.push_str(text);
+                }
+            }
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn indent(item: &Item) -> &'static str {
+    match item.kind {
+        Kind::Import
+        | Kind::Const
+        | Kind::Block
+        | Kind::Where
+        | Kind::Close => "",
+        _ => INDENT,
+    }
+}
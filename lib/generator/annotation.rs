@@ -0,0 +1,80 @@
+use std::ops::Range;
+
+/// Heuristic replacing a value following field attributes by an
+/// AFL-style interesting number
+///
+pub const INTERESTING: &str = "interesting";
+
+/// Heuristic replacing a value following field attributes by an edge of
+/// a RANGE
+///
+pub const BOUNDARY: &str = "boundary";
+
+/// Heuristic replacing a random string or bytes by an entry of a global
+/// dictionary (`-x`)
+///
+pub const DICTIONARY: &str = "dictionary";
+
+/// Part of a generated input produced by a single field
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// Group the field belongs to
+    ///
+    pub group: String,
+
+    pub alias: Option<String>,
+
+    /// Bytes of the input written by the field (fewer than the value
+    /// takes when it didn't fit in OUT_MAX)
+    ///
+    pub range: Range<usize>,
+
+    pub value: Value,
+
+    /// Fuzzing heuristic (pixie) that picked the value instead of field
+    /// attributes, such as `INTERESTING`
+    ///
+    pub mutated_by: Option<&'static str>,
+}
+
+/// Value a field was generated with
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Number(i128),
+
+    /// Strings, bytes and constants
+    ///
+    Bytes(Vec<u8>),
+
+    /// Fields of a referenced group (or of a message of a session)
+    ///
+    Group(Vec<Annotation>),
+}
+
+impl Annotation {
+    /// Annotations of fields holding values (not groups), in order
+    ///
+    pub fn leaves(annotations: &[Annotation]) -> Vec<&Annotation> {
+        let mut leaves = vec![];
+        for annotation in annotations {
+            match &annotation.value {
+                Value::Group(fields) => leaves.extend(Self::leaves(fields)),
+                _ => leaves.push(annotation),
+            }
+        }
+        leaves
+    }
+
+    /// Move byte ranges (of the field and the nested ones) by an offset
+    ///
+    pub(crate) fn shift(&mut self, offset: usize) {
+        self.range = self.range.start + offset..self.range.end + offset;
+        if let Value::Group(fields) = &mut self.value {
+            for field in fields {
+                field.shift(offset);
+            }
+        }
+    }
+}
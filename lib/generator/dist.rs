@@ -0,0 +1,70 @@
+use crate::evaluator::structure::ValueDist;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Probability of picking any value of the range instead of a member of
+/// an enumeration, so that handling of unknown values gets exercised too
+///
+const OUTSIDE_ENUM: f64 = 0.1;
+
+/// Pick a value from `min..=max` according to a distribution
+///
+/// Values of `ValueDist::Weights` and members of `ValueDist::Enum` are
+/// picked as they are, all other distributions never leave the range.
+///
+pub(crate) fn sample<R>(
+    dist: &ValueDist,
+    min: i128,
+    max: i128,
+    rng: &mut R,
+) -> i128
+where
+    R: Rng + ?Sized,
+{
+    match dist {
+        ValueDist::Weights(weights) => {
+            match WeightedIndex::new(weights.iter().map(|(_, w)| *w)) {
+                Ok(index) => weights[index.sample(rng)].0,
+                Err(_) => min,
+            }
+        }
+        ValueDist::Enum(values) if !rng.gen_bool(OUTSIDE_ENUM) => {
+            values.choose(rng).copied().unwrap_or(min)
+        }
+        _ if min >= max => min,
+        ValueDist::Uniform | ValueDist::Enum(_) => rng.gen_range(min..=max),
+        ValueDist::Log => sample_log(min, max, rng),
+        ValueDist::Normal { mean, sd } => {
+            let value = mean + sd * standard_normal(rng);
+            (value.round() as i128).clamp(min, max)
+        }
+    }
+}
+
+/// Pick an offset from `min` so that every order of magnitude is equally
+/// likely
+///
+/// Offsets `0..=max - min` are mapped to `1..span + 1`, so that `max` is
+/// reachable too.
+///
+fn sample_log<R>(min: i128, max: i128, rng: &mut R) -> i128
+where
+    R: Rng + ?Sized,
+{
+    let span = (max - min) as f64 + 1.0;
+    let offset =
+        rng.gen_range(0.0..(span + 1.0).ln()).exp().floor() as i128 - 1;
+    min + offset.clamp(0, max - min)
+}
+
+/// Standard normal variable using Box-Muller transform
+///
+fn standard_normal<R>(rng: &mut R) -> f64
+where
+    R: Rng + ?Sized,
+{
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
@@ -0,0 +1,51 @@
+use rand::{Error, RngCore};
+
+/// Random number generator replaying given bytes
+///
+/// Used when a fuzzer (such as libFuzzer) provides the data: every random
+/// choice consumes the following bytes, so the same data always leads to
+/// the same output and small changes of the data change single choices.
+///
+/// After running out of data, zeros are returned (picking the smallest
+/// values).
+///
+pub struct EntropyRng {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl EntropyRng {
+    pub fn new(data: &[u8]) -> Self {
+        Self {
+            data: data.to_vec(),
+            pos: 0,
+        }
+    }
+}
+
+impl RngCore for EntropyRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let available = &self.data[self.pos..];
+        let len = dest.len().min(available.len());
+        dest[..len].copy_from_slice(&available[..len]);
+        dest[len..].fill(0);
+        self.pos += len;
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
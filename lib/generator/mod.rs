@@ -1,18 +1,29 @@
 use crate::evaluator::structure::{
-    AsciiStringDef, ByteNumberDef, BytesDef, TextNumberDef,
+    AsciiStringDef, ByteNumberDef, BytesDef, TextNumberDef, ValueDist,
 };
 use crate::{
+    dictionary::Dictionary,
     error::BajzelError,
     evaluator::{
-        generator::GenDefinition,
+        capture::Variables,
+        generator::{GenDefinition, NumberMix},
         structure::{FieldDefinition, GroupDefinition},
         ProgramEnv,
     },
 };
-use rand::distributions::Alphanumeric;
-use rand::{thread_rng, Rng};
+use annotation::{Annotation, Value, BOUNDARY, DICTIONARY, INTERESTING};
+use rand::distributions::{Alphanumeric, Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore, SeedableRng};
+use std::cell::RefCell;
 use std::ops::ControlFlow;
 
+pub mod annotation;
+mod dist;
+mod entropy;
+pub mod session;
+
 pub trait Pixie {
     /// Determine whether a pixie is happy
     ///
@@ -24,8 +35,81 @@ pub trait Pixie {
     fn is_happy(&self) -> bool;
 }
 
+/// Probability of using an entry from a global dictionary (if provided)
+/// instead of a random value for string and bytes fields
+///
+const GLOBAL_DICT_PROBABILITY: f64 = 0.25;
+
+/// Fuzzing heuristics used while generating a message
+///
+struct Strategy<'a> {
+    mix: NumberMix,
+
+    /// Whether the global dictionary may be used
+    ///
+    global_dict: bool,
+
+    /// Values captured earlier in a session
+    ///
+    vars: Option<&'a Variables>,
+}
+
+impl Strategy<'_> {
+    fn new(gen: &GenDefinition, mutate: bool) -> Self {
+        match mutate {
+            true => Self {
+                mix: gen.num_mix,
+                global_dict: true,
+                vars: None,
+            },
+            false => Self {
+                mix: NumberMix {
+                    uniform: 1,
+                    interesting: 0,
+                    boundary: 0,
+                },
+                global_dict: false,
+                vars: None,
+            },
+        }
+    }
+}
+
+/// Result of generating a single field
+///
+struct Picked {
+    flow: ControlFlow<()>,
+
+    /// Number a numeric field was generated with
+    ///
+    number: Option<i128>,
+
+    /// Heuristic that picked the value (see `Annotation::mutated_by`)
+    ///
+    mutated_by: Option<&'static str>,
+}
+
+impl From<ControlFlow<()>> for Picked {
+    fn from(flow: ControlFlow<()>) -> Self {
+        Self {
+            flow,
+            number: None,
+            mutated_by: None,
+        }
+    }
+}
+
 pub struct Gen {
     _pixies: Vec<Box<dyn Pixie>>,
+
+    /// Global dictionary (AFL's `-x`) used by all string and bytes fields
+    /// that don't have their own `DICT`
+    ///
+    dict: Option<Dictionary>,
+
+    /// Source of all random choices
+    ///
+    rng: RefCell<Box<dyn RngCore>>,
 }
 
 impl Default for Gen {
@@ -38,46 +122,218 @@ impl Gen {
     /// Create Generator from pixie collection
     ///
     pub fn new(_pixies: Vec<Box<dyn Pixie>>) -> Gen {
-        Self { _pixies }
+        Self {
+            _pixies,
+            dict: None,
+            rng: RefCell::new(Box::new(StdRng::from_entropy())),
+        }
+    }
+
+    /// Reset random choices to a sequence determined by a seed
+    ///
+    /// Generating after setting the same seed gives the same output.
+    ///
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = RefCell::new(Box::new(StdRng::seed_from_u64(seed)));
+    }
+
+    /// Drive random choices by given bytes instead of a random number
+    /// generator
+    ///
+    /// Generating after setting the same bytes gives the same output.
+    ///
+    pub fn set_entropy(&mut self, data: &[u8]) {
+        self.rng = RefCell::new(Box::new(entropy::EntropyRng::new(data)));
+    }
+
+    /// Add entries of a dictionary to the global one
+    ///
+    pub fn add_dictionary(&mut self, dict: Dictionary) {
+        match &mut self.dict {
+            Some(current) => current.extend(dict),
+            None => self.dict = Some(dict),
+        }
     }
 
+    /// Generate a single message from a group given by `GENERATE`
+    ///
+    /// Unlike `generate_input`, the message isn't followed by TERM.
+    ///
     pub fn generate(&self, env: &ProgramEnv) -> Result<Vec<u8>, BajzelError> {
+        let gen = env.get_generator()?;
+        self.generate_from(env, &gen.name, &Strategy::new(gen, true), None)
+    }
+
+    /// Generate a single input from a group of a given name (instead of
+    /// the one given by `GENERATE`)
+    ///
+    pub fn generate_named(
+        &self,
+        env: &ProgramEnv,
+        name: &str,
+    ) -> Result<Vec<u8>, BajzelError> {
+        let gen = env.get_generator()?;
+        self.generate_from(env, name, &Strategy::new(gen, true), None)
+    }
+
+    /// Generate like `generate`, returning also which bytes were written
+    /// by which field
+    ///
+    pub fn generate_annotated(
+        &self,
+        env: &ProgramEnv,
+    ) -> Result<(Vec<u8>, Vec<Annotation>), BajzelError> {
+        let gen = env.get_generator()?;
+        let strategy = Strategy::new(gen, true);
+        let mut annotations = vec![];
+        let bytes = self.generate_from(
+            env,
+            &gen.name,
+            &strategy,
+            Some(&mut annotations),
+        )?;
+        Ok((bytes, annotations))
+    }
+
+    /// Generate a single input from a given group, annotating its fields
+    /// if asked to
+    ///
+    fn generate_from(
+        &self,
+        env: &ProgramEnv,
+        name: &str,
+        strategy: &Strategy,
+        annotations: Option<&mut Vec<Annotation>>,
+    ) -> Result<Vec<u8>, BajzelError> {
         let gen = env.get_generator()?;
         let mut bytes: Vec<u8> = Vec::with_capacity(gen.out_max as usize);
-        let group = env.get_group(&gen.name)?;
-        self.generate_group(env, gen, group, &mut bytes)?;
+        let group = env.get_group(&name)?;
+        let _ = self.generate_group(
+            env,
+            strategy,
+            (name, group),
+            &mut bytes,
+            annotations,
+        )?;
 
         Ok(bytes)
     }
 
+    /// Generate fields of a group (and groups it references)
+    ///
+    /// Breaks when there's no more space left in the output.
+    ///
     fn generate_group(
         &self,
-        _env: &ProgramEnv,
-        _gen: &GenDefinition,
-        group: &GroupDefinition,
+        env: &ProgramEnv,
+        strategy: &Strategy,
+        (name, group): (&str, &GroupDefinition),
         bytes: &mut Vec<u8>,
-    ) -> Result<(), BajzelError> {
+        mut annotations: Option<&mut Vec<Annotation>>,
+    ) -> Result<ControlFlow<()>, BajzelError> {
         for field in group.fields_iter() {
-            let cf = match &field.def {
-                FieldDefinition::ConstString(x) => {
-                    self.generate_const_string(x, bytes)
-                }
-                FieldDefinition::TextNumber(x) => {
-                    self.generate_text_number(x, bytes)
-                }
-                FieldDefinition::AsciiString(x) => {
-                    self.generate_ascii_string(x, bytes)
-                }
-                FieldDefinition::ByteNumber(x) => {
-                    self.generate_byte_number(x, bytes)
-                }
-                FieldDefinition::Bytes(x) => self.generate_bytes(x, bytes),
-            };
-            if let ControlFlow::Break(_) = cf {
-                break;
+            let start = bytes.len();
+            let mut fields = annotations.as_ref().map(|_| vec![]);
+            let picked = self.generate_value(
+                env,
+                strategy,
+                &field.def,
+                bytes,
+                fields.as_mut(),
+            )?;
+            if let Some(annotations) = annotations.as_deref_mut() {
+                let value = match (fields, picked.number) {
+                    (Some(fields), _)
+                        if matches!(field.def, FieldDefinition::Ref(_)) =>
+                    {
+                        Value::Group(fields)
+                    }
+                    (_, Some(number)) => Value::Number(number),
+                    _ => Value::Bytes(bytes[start..].to_vec()),
+                };
+                annotations.push(Annotation {
+                    group: name.to_owned(),
+                    alias: field.alias.clone(),
+                    range: start..bytes.len(),
+                    value,
+                    mutated_by: picked.mutated_by,
+                });
+            }
+            if let ControlFlow::Break(_) = picked.flow {
+                return Ok(picked.flow);
             }
         }
-        Ok(())
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Generate a value of a single field (all fields of a referenced
+    /// group)
+    ///
+    fn generate_value(
+        &self,
+        env: &ProgramEnv,
+        strategy: &Strategy,
+        def: &FieldDefinition,
+        bytes: &mut Vec<u8>,
+        annotations: Option<&mut Vec<Annotation>>,
+    ) -> Result<Picked, BajzelError> {
+        Ok(match def {
+            FieldDefinition::ConstString(x) => {
+                self.generate_const_string(x, bytes).into()
+            }
+            FieldDefinition::TextNumber(x) => {
+                self.generate_text_number(x, &strategy.mix, bytes)
+            }
+            FieldDefinition::AsciiString(x) => {
+                self.generate_ascii_string(x, strategy, bytes)
+            }
+            FieldDefinition::ByteNumber(x) => {
+                self.generate_byte_number(x, &strategy.mix, bytes)
+            }
+            FieldDefinition::Bytes(x) => {
+                self.generate_bytes(x, strategy, bytes)
+            }
+            FieldDefinition::Ref(x) => {
+                let name = x
+                    .group
+                    .as_ref()
+                    .ok_or(BajzelError::NotConstructedProperly)?;
+                let group = env.get_group(name)?;
+                self.generate_group(
+                    env,
+                    strategy,
+                    (name, group),
+                    bytes,
+                    annotations,
+                )?
+                .into()
+            }
+            FieldDefinition::Captured(var) => {
+                match strategy.vars.and_then(|x| x.get(var)) {
+                    Some(value) => self.write_bytes(value, bytes),
+                    None => ControlFlow::Continue(()),
+                }
+                .into()
+            }
+        })
+    }
+
+    /// Generate a new value of a single field, at most `max_len` bytes
+    /// long
+    ///
+    /// Captured values are left empty, as there's no session to take them
+    /// from.
+    ///
+    pub(crate) fn generate_field(
+        &self,
+        env: &ProgramEnv,
+        def: &FieldDefinition,
+        max_len: usize,
+    ) -> Result<Vec<u8>, BajzelError> {
+        let strategy = Strategy::new(env.get_generator()?, true);
+        let mut bytes = Vec::with_capacity(max_len);
+        self.generate_value(env, &strategy, def, &mut bytes, None)?;
+        Ok(bytes)
     }
 
     fn generate_const_string(
@@ -85,14 +341,21 @@ impl Gen {
         x: &String,
         bytes: &mut Vec<u8>,
     ) -> ControlFlow<()> {
-        let required_len = x.len();
+        self.write_bytes(x.as_bytes(), bytes)
+    }
+
+    /// Write as much data as available space allows
+    ///
+    /// Breaks when the data didn't fit entirely.
+    ///
+    fn write_bytes(&self, data: &[u8], bytes: &mut Vec<u8>) -> ControlFlow<()> {
+        let required_len = data.len();
         let available_len =
             std::cmp::min(required_len, bytes.capacity() - bytes.len());
-        if available_len == 0 {
+        if available_len == 0 && required_len > 0 {
             return ControlFlow::Break(());
         }
-        let to_write = &x.as_bytes()[0..available_len];
-        bytes.extend_from_slice(to_write);
+        bytes.extend_from_slice(&data[0..available_len]);
         if available_len < required_len {
             return ControlFlow::Break(());
         }
@@ -102,23 +365,23 @@ impl Gen {
     fn generate_ascii_string(
         &self,
         x: &AsciiStringDef,
+        strategy: &Strategy,
         bytes: &mut Vec<u8>,
-    ) -> ControlFlow<()> {
+    ) -> Picked {
         let available_len = bytes.capacity() - bytes.len();
         if available_len == 0 {
-            return ControlFlow::Break(());
+            return ControlFlow::Break(()).into();
+        }
+        if let Some(dict) = self.choose_dictionary(x.dict.as_ref(), strategy) {
+            return self.generate_dict_entry(dict, x.dict.is_none(), bytes);
         }
-        let mut rng = thread_rng();
+        let rng = &mut *self.rng.borrow_mut();
 
         let min_len = std::cmp::min(x.length_min, available_len);
         let max_len = std::cmp::min(x.length_max, available_len);
-        let rng_len: usize = {
-            if min_len == max_len {
-                min_len
-            } else {
-                rng.gen_range(min_len..=max_len)
-            }
-        };
+        let rng_len =
+            dist::sample(&x.dist, min_len as i128, max_len as i128, rng);
+        let rng_len = (rng_len.max(0) as usize).min(available_len);
 
         let data: String = rng
             .sample_iter(&Alphanumeric)
@@ -127,49 +390,162 @@ impl Gen {
             .collect();
 
         bytes.extend_from_slice(data.as_bytes());
-        ControlFlow::Continue(())
+        ControlFlow::Continue(()).into()
     }
 
     fn generate_bytes(
         &self,
         x: &BytesDef,
+        strategy: &Strategy,
         bytes: &mut Vec<u8>,
-    ) -> ControlFlow<()> {
+    ) -> Picked {
         let available_len = bytes.capacity() - bytes.len();
         if available_len == 0 {
-            return ControlFlow::Break(());
+            return ControlFlow::Break(()).into();
+        }
+        if let Some(dict) = self.choose_dictionary(x.dict.as_ref(), strategy) {
+            return self.generate_dict_entry(dict, x.dict.is_none(), bytes);
         }
-        let mut rng = thread_rng();
+        let rng = &mut *self.rng.borrow_mut();
         let min_len = std::cmp::min(x.length_min, available_len);
         let max_len = std::cmp::min(x.length_max, available_len);
-        let rng_len: usize = {
-            if min_len == max_len {
-                min_len
-            } else {
-                rng.gen_range(min_len..=max_len)
-            }
-        };
+        let rng_len =
+            dist::sample(&x.dist, min_len as i128, max_len as i128, rng);
+        let rng_len = (rng_len.max(0) as usize).min(available_len);
         let data: Vec<_> = (0..rng_len).map(|_| rng.gen::<u8>()).collect();
         bytes.extend_from_slice(data.as_slice());
-        ControlFlow::Continue(())
+        ControlFlow::Continue(()).into()
+    }
+
+    /// Pick dictionary to generate a value from
+    ///
+    /// Field's own dictionary is always used. Otherwise global dictionary
+    /// is used from time to time (if a strategy allows it).
+    ///
+    fn choose_dictionary<'a>(
+        &'a self,
+        field_dict: Option<&'a Dictionary>,
+        strategy: &Strategy,
+    ) -> Option<&'a Dictionary> {
+        if field_dict.is_some() {
+            return field_dict;
+        }
+        match &self.dict {
+            Some(dict)
+                if strategy.global_dict
+                    && !dict.is_empty()
+                    && self
+                        .rng
+                        .borrow_mut()
+                        .gen_bool(GLOBAL_DICT_PROBABILITY) =>
+            {
+                Some(dict)
+            }
+            _ => None,
+        }
+    }
+
+    /// Write a random entry of a dictionary (`global` tells whether it's
+    /// the global dictionary rather than field's own DICT)
+    ///
+    fn generate_dict_entry(
+        &self,
+        dict: &Dictionary,
+        global: bool,
+        bytes: &mut Vec<u8>,
+    ) -> Picked {
+        let entries = dict.entries();
+        if entries.is_empty() {
+            return ControlFlow::Continue(()).into();
+        }
+        let entry = &entries[self.rng.borrow_mut().gen_range(0..entries.len())];
+        Picked {
+            mutated_by: global.then_some(DICTIONARY),
+            ..self.write_bytes(entry, bytes).into()
+        }
     }
 
     fn generate_byte_number(
         &self,
-        _x: &ByteNumberDef,
-        _bytes: &mut Vec<u8>,
-    ) -> ControlFlow<()> {
-        todo!()
+        x: &ByteNumberDef,
+        mix: &NumberMix,
+        bytes: &mut Vec<u8>,
+    ) -> Picked {
+        let (value, mutated_by) = self.sample_number(
+            x.min_value,
+            x.max_value,
+            &x.dist,
+            || x.interesting_values(),
+            || x.boundary_values(),
+            mix,
+        );
+        Picked {
+            flow: self.write_bytes(&x.encode(value), bytes),
+            number: Some(value),
+            mutated_by,
+        }
     }
 
     fn generate_text_number(
         &self,
-        _x: &TextNumberDef,
+        x: &TextNumberDef,
+        mix: &NumberMix,
         bytes: &mut Vec<u8>,
-    ) -> ControlFlow<()> {
-        let mut rng = thread_rng();
-        let value = "42";
-        bytes.extend_from_slice(value.as_bytes());
-        ControlFlow::Continue(())
+    ) -> Picked {
+        let (value, mutated_by) = self.sample_number(
+            x.min_value,
+            x.max_value,
+            &x.dist,
+            || x.interesting_values(),
+            || x.boundary_values(),
+            mix,
+        );
+        Picked {
+            flow: self.write_bytes(&x.encode(value), bytes),
+            number: Some(value),
+            mutated_by,
+        }
+    }
+
+    /// Pick a number from `min..=max` using a strategy chosen according
+    /// to weights of a number mix
+    ///
+    /// Returns the number along with a heuristic that picked it (`None`
+    /// when it follows the distribution). Values listed by WEIGHTS or ENUM
+    /// are never replaced by heuristics.
+    ///
+    fn sample_number<I, B>(
+        &self,
+        min: i128,
+        max: i128,
+        dist: &ValueDist,
+        interesting: I,
+        boundary: B,
+        mix: &NumberMix,
+    ) -> (i128, Option<&'static str>)
+    where
+        I: FnOnce() -> Vec<i128>,
+        B: FnOnce() -> Vec<i128>,
+    {
+        let rng = &mut *self.rng.borrow_mut();
+        if matches!(dist, ValueDist::Weights(_) | ValueDist::Enum(_)) {
+            return (dist::sample(dist, min, max, rng), None);
+        }
+        if min == max {
+            return (min, None);
+        }
+        let weights = [mix.uniform, mix.interesting, mix.boundary];
+        let (candidates, heuristic) = match WeightedIndex::new(weights) {
+            Ok(strategy) => match strategy.sample(rng) {
+                1 => (interesting(), Some(INTERESTING)),
+                2 => (boundary(), Some(BOUNDARY)),
+                _ => (vec![], None),
+            },
+            Err(_) => (vec![], None),
+        };
+        match candidates.choose(rng) {
+            Some(value) => (*value, heuristic),
+            None => (dist::sample(dist, min, max, rng), None),
+        }
     }
 }
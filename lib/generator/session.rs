@@ -0,0 +1,257 @@
+use super::{
+    annotation::{Annotation, Value},
+    Gen, Strategy,
+};
+use crate::{
+    error::BajzelError,
+    evaluator::{
+        capture::{CaptureDef, Variables},
+        sequence::END_STEP,
+        ProgramEnv,
+    },
+};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+
+/// Single message of a session
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// Group the message is generated from
+    ///
+    pub group: String,
+
+    /// Whether fuzzing heuristics are used
+    ///
+    pub mutate: bool,
+}
+
+impl Gen {
+    /// Pick messages sent within a session
+    ///
+    /// When GENERATE selects a group (not a SEQUENCE), session consists
+    /// of a single message.
+    ///
+    pub fn plan_session(
+        &self,
+        env: &ProgramEnv,
+    ) -> Result<Vec<Message>, BajzelError> {
+        Ok(self.plan(env)?.into_iter().map(|(x, _)| x).collect())
+    }
+
+    /// Pick messages along with captures of their steps
+    ///
+    fn plan<'a>(
+        &self,
+        env: &'a ProgramEnv,
+    ) -> Result<Vec<(Message, &'a [CaptureDef])>, BajzelError> {
+        let gen = env.get_generator()?;
+        let sequence = match env.find_sequence(&gen.name) {
+            Some(sequence) => sequence,
+            None => {
+                let message = Message {
+                    group: gen.name.clone(),
+                    mutate: true,
+                };
+                return Ok(vec![(message, &[])]);
+            }
+        };
+
+        let max_messages = gen.max_messages as usize;
+        let mut rng = self.rng.borrow_mut();
+        let mut messages = Vec::new();
+        let mut pos = Some(0);
+        while let Some(step) = pos.and_then(|x| sequence.steps.get(x)) {
+            let count = rng.gen_range(step.repeat_min..=step.repeat_max);
+            for _ in 0..count {
+                if messages.len() >= max_messages {
+                    return Ok(messages);
+                }
+                let message = Message {
+                    group: step.group.clone(),
+                    mutate: step.mutate,
+                };
+                messages.push((message, step.captures.as_slice()));
+            }
+            pos = match &step.next {
+                None => pos.map(|x| x + 1),
+                Some(next) => match next.choose(&mut *rng) {
+                    Some(name) if name != END_STEP => sequence.find_step(name),
+                    _ => None,
+                },
+            };
+        }
+        Ok(messages)
+    }
+
+    /// Generate a single message of a session
+    ///
+    pub fn generate_message(
+        &self,
+        env: &ProgramEnv,
+        message: &Message,
+    ) -> Result<Vec<u8>, BajzelError> {
+        let strategy = Strategy::new(env.get_generator()?, message.mutate);
+        self.generate_from(env, &message.group, &strategy, None)
+    }
+
+    /// Plan and generate all messages of a session
+    ///
+    pub fn generate_session(
+        &self,
+        env: &ProgramEnv,
+    ) -> Result<Vec<Vec<u8>>, BajzelError> {
+        self.plan_session(env)?
+            .iter()
+            .map(|message| self.generate_message(env, message))
+            .collect()
+    }
+
+    /// Generate a whole session as a single input (all messages followed
+    /// by TERM, joined together)
+    ///
+    pub fn generate_input(
+        &self,
+        env: &ProgramEnv,
+    ) -> Result<Vec<u8>, BajzelError> {
+        let mut session = self.start_session(env)?;
+        while session.next_message()?.is_some() {}
+        Ok(session.sent().concat())
+    }
+
+    /// Generate like `generate_input`, annotating every message (as
+    /// a group holding annotations of its fields)
+    ///
+    pub fn generate_input_annotated(
+        &self,
+        env: &ProgramEnv,
+    ) -> Result<(Vec<u8>, Vec<Annotation>), BajzelError> {
+        let gen = env.get_generator()?;
+        let mut bytes = vec![];
+        let mut annotations = vec![];
+        for (message, _) in self.plan(env)? {
+            let strategy = Strategy::new(gen, message.mutate);
+            let mut fields = vec![];
+            let data = self.generate_from(
+                env,
+                &message.group,
+                &strategy,
+                Some(&mut fields),
+            )?;
+            let mut annotation = Annotation {
+                group: message.group,
+                alias: None,
+                range: 0..data.len(),
+                value: Value::Group(fields),
+                mutated_by: None,
+            };
+            annotation.shift(bytes.len());
+            annotations.push(annotation);
+            bytes.extend_from_slice(&data);
+            bytes.extend_from_slice(&gen.term);
+        }
+        Ok((bytes, annotations))
+    }
+
+    /// Plan a session whose messages are generated one by one, so values
+    /// captured from replies can be used by later messages
+    ///
+    pub fn start_session<'a>(
+        &'a self,
+        env: &'a ProgramEnv,
+    ) -> Result<Session<'a>, BajzelError> {
+        Ok(Session {
+            gen: self,
+            env,
+            plan: self.plan(env)?.into_iter(),
+            captures: &[],
+            vars: Variables::new(),
+            sent: vec![],
+        })
+    }
+}
+
+/// Session in progress
+///
+/// Example (delivering messages to a server):
+///
+/// ```ignore
+/// let mut session = gen.start_session(&env)?;
+/// while let Some(message) = session.next_message()? {
+///     send(&message);
+///     if session.expects_reply() {
+///         session.handle_reply(&receive())?;
+///     }
+/// }
+/// ```
+///
+pub struct Session<'a> {
+    gen: &'a Gen,
+    env: &'a ProgramEnv,
+    plan: std::vec::IntoIter<(Message, &'a [CaptureDef])>,
+
+    /// Captures of the most recent message
+    ///
+    captures: &'a [CaptureDef],
+
+    vars: Variables,
+
+    /// Messages generated so far
+    ///
+    sent: Vec<Vec<u8>>,
+}
+
+impl Session<'_> {
+    /// Generate next message (followed by TERM), `None` at the end of
+    /// a session
+    ///
+    pub fn next_message(&mut self) -> Result<Option<Vec<u8>>, BajzelError> {
+        let (message, captures) = match self.plan.next() {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let gen = self.env.get_generator()?;
+        let strategy = Strategy {
+            vars: Some(&self.vars),
+            ..Strategy::new(gen, message.mutate)
+        };
+        let mut bytes = self.gen.generate_from(
+            self.env,
+            &message.group,
+            &strategy,
+            None,
+        )?;
+        bytes.extend_from_slice(&gen.term);
+        self.captures = captures;
+        self.sent.push(bytes.clone());
+        Ok(Some(bytes))
+    }
+
+    /// Whether the most recent message has a reply to capture values from
+    ///
+    pub fn expects_reply(&self) -> bool {
+        !self.captures.is_empty()
+    }
+
+    /// Capture values from a reply to the most recent message
+    ///
+    pub fn handle_reply(&mut self, reply: &[u8]) -> Result<(), BajzelError> {
+        for capture in self.captures {
+            capture.apply(reply, &mut self.vars)?;
+        }
+        Ok(())
+    }
+
+    /// Captured values by variable name
+    ///
+    pub fn vars(&self) -> &HashMap<String, Vec<u8>> {
+        &self.vars
+    }
+
+    /// Messages generated so far
+    ///
+    pub fn sent(&self) -> &[Vec<u8>] {
+        &self.sent
+    }
+}
@@ -3,16 +3,18 @@ use nom::bytes::complete::{is_not, tag, take};
 use nom::character::complete::{
     alpha1, alphanumeric1, char, digit1, multispace0,
 };
-use nom::combinator::{map, map_res, opt, recognize, value};
+use nom::combinator::{map, map_res, opt, recognize};
 use nom::multi::many0;
-use nom::sequence::{delimited, pair, tuple};
+use nom::sequence::{delimited, pair, preceded, tuple};
 use nom::IResult;
 use std::borrow::Cow;
 use std::num::ParseIntError;
 
 use super::Token;
 
-static TYPES: &[&str; 21] = &[
+/// Names of field types (matched case-insensitively)
+///
+pub static TYPES: &[&str; 21] = &[
     "i8", "i32", "i64", "u8", "u32", "u64", "le_u16", "le_u32", "le_u64",
     "le_i16", "le_i32", "le_i64", "be_u16", "be_u32", "be_u64", "be_i16",
     "be_i32", "be_i64", "bytes", "ref", "string",
@@ -21,10 +23,12 @@ static RESERVED: &[&str; 2] = &["null", "lf"];
 
 /// Matches any number of whitespaces followed by # character followed by anything that's not \n
 ///
+/// The comment keeps text after `#`, without trailing whitespace.
+///
 pub(crate) fn comment_to_eol(input: &str) -> IResult<&str, Token<'_>> {
-    value(
-        Token::Comment,
-        delimited(multispace0, char('#'), is_not("\n")),
+    map(
+        preceded(pair(multispace0, char('#')), is_not("\n")),
+        |x: &str| Token::Comment(x.trim_end()),
     )(input)
 }
 
@@ -95,9 +99,13 @@ fn lex_ident_array(input: &str) -> IResult<&str, Token<'_>> {
 
 /// Lex input into an indentifier
 ///
+/// Names of imported groups are prefixed with a namespace, such as
+/// `tlv::record`.
+///
 fn lex_ident(input: &str) -> IResult<&str, Token<'_>> {
+    let name = || pair(alpha1, many0(alt((alphanumeric1, tag("_")))));
     map(
-        recognize(pair(alpha1, many0(alt((alphanumeric1, tag("_")))))),
+        recognize(pair(name(), many0(pair(tag("::"), name())))),
         |x: &str| {
             let icase_x = x.to_lowercase();
             let icase_x = icase_x.as_str();
@@ -110,9 +118,13 @@ fn lex_ident(input: &str) -> IResult<&str, Token<'_>> {
             match icase_x {
                 // Keywords
                 "as" => Token::As,
+                "const" => Token::Const,
                 "define" => Token::Define,
+                "enum" => Token::Enum,
                 "from" => Token::From,
                 "generate" => Token::Generate,
+                "import" => Token::Import,
+                "sequence" => Token::Sequence,
                 "where" => Token::Where,
                 "with" => Token::With,
                 _ => Token::Ident(x),
@@ -126,8 +138,7 @@ fn lex_ident(input: &str) -> IResult<&str, Token<'_>> {
 fn lex_integer(input: &str) -> IResult<&str, Token<'_>> {
     map(
         map_res(recognize(pair(opt(char('-')), digit1)), |x: &str| {
-            // i64::from_str_radix(x, 10)
-            x.parse::<i64>()
+            x.parse::<i128>()
         }),
         Token::IntegerLiteral,
     )(input)
@@ -158,6 +169,8 @@ fn lex_punctuations(input: &str) -> IResult<&str, Token<'_>> {
     alt((
         map(tag("("), |_| Token::LeftParen),
         map(tag(")"), |_| Token::RightParen),
+        map(tag("{"), |_| Token::LeftBrace),
+        map(tag("}"), |_| Token::RightBrace),
         map(tag(","), |_| Token::Comma),
         map(tag("$"), |_| Token::Reference),
         map(tag(":"), |_| Token::Colon),
@@ -165,5 +178,5 @@ fn lex_punctuations(input: &str) -> IResult<&str, Token<'_>> {
 }
 
 fn lex_comment(input: &str) -> IResult<&str, Token<'_>> {
-    map(tag("#"), |_| Token::Comment)(input)
+    map(tag("#"), |_| Token::Comment(""))(input)
 }
@@ -4,32 +4,59 @@ mod funcs;
 
 use itertools::Itertools;
 use nom::branch::*;
-use nom::multi::many0;
 use std::borrow::Cow;
 use std::iter::Enumerate;
 use std::ops::{Range, RangeFrom, RangeFull, RangeTo};
 
 use self::funcs::{comment_to_eol, space_separated_token};
 
+pub use self::funcs::TYPES;
+
 /// Entrypoint - lex input into tokens
 ///
 pub fn lex_tokens(
     input: &str,
 ) -> Result<Vec<Token<'_>>, nom::Err<nom::error::Error<&str>>> {
-    many0(alt((comment_to_eol, space_separated_token)))(input).map(
-        |(_, tokens)| {
-            itertools::chain(
-                tokens.into_iter().filter(|token| match token {
-                    Token::Comment => false,
+    lex_spanned(input).map(|tokens| {
+        itertools::chain(
+            tokens.into_iter().map(|(token, _)| token).filter(|token| {
+                match token {
+                    Token::Comment(_) => false,
                     _ => true,
-                }),
-                Some(Token::Eof).into_iter(),
-            )
-            .collect_vec()
-        },
-    )
+                }
+            }),
+            Some(Token::Eof),
+        )
+        .collect_vec()
+    })
+}
+
+/// Lex input into tokens along with their byte ranges in the input
+///
+/// Unlike `lex_tokens`, comments are kept and there's no `Eof` at the end.
+///
+pub fn lex_spanned(
+    input: &str,
+) -> Result<Vec<Spanned<'_>>, nom::Err<nom::error::Error<&str>>> {
+    let mut tokens = vec![];
+    let mut rest = input;
+    while let Ok((next, token)) =
+        alt((comment_to_eol, space_separated_token))(rest)
+    {
+        let offset = input.len() - rest.len();
+        let consumed = &rest[..rest.len() - next.len()];
+        let start = offset + consumed.len() - consumed.trim_start().len();
+        let end = offset + consumed.trim_end().len();
+        tokens.push((token, start..end));
+        rest = next;
+    }
+    Ok(tokens)
 }
 
+/// Token with its byte range in the input
+///
+pub type Spanned<'a> = (Token<'a>, Range<usize>);
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Token<'a> {
     Add,
@@ -38,20 +65,26 @@ pub enum Token<'a> {
     Bytes(Vec<u8>),
     Colon,
     Comma,
-    Comment,
+    Comment(&'a str),
+    Const,
     Define,
+    Enum,
     Eof,
     From,
     Generate,
     Ident(&'a str),
     Illegal(&'a str),
-    IntegerLiteral(i64),
+    Import,
+    IntegerLiteral(i128),
+    LeftBrace,
     LeftParen,
     Multiply,
     Reference,
     ReservedIdent(Cow<'a, str>),
     RightArrow,
+    RightBrace,
     RightParen,
+    Sequence,
     StringLiteral(&'a str),
     Subtract,
     Type(&'a str),
@@ -1,5 +1,17 @@
+pub mod afl;
+pub mod covering;
+pub mod dictionary;
+pub mod enumerator;
 pub mod error;
 pub mod evaluator;
+pub mod formatter;
 pub mod generator;
 pub mod lexer;
+pub mod libfuzzer;
+pub mod lsp;
+pub mod minimizer;
+pub mod output;
 pub mod parser;
+pub mod repl;
+pub mod runner;
+pub mod watch;
@@ -0,0 +1,124 @@
+use crate::{
+    error::BajzelError,
+    evaluator::{evaluate_file, evaluate_source_in, ProgramEnv},
+    generator::Gen,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::path::Path;
+
+/// Inputs decoded from data provided by libFuzzer (or `cargo fuzz`)
+///
+/// The data is not an input itself, but a record of random choices made
+/// while generating one (see `Gen::set_entropy`). Whatever libFuzzer
+/// mutates, the decoded input follows the `.fuzl` file, and coverage
+/// feedback still guides the choices.
+///
+/// Example:
+///
+/// ```ignore
+/// #![no_main]
+/// use bajzel_lib::libfuzzer::{custom_mutator, Grammar};
+/// use libfuzzer_sys::{fuzz_mutator, fuzz_target};
+/// use std::sync::Mutex;
+///
+/// static GRAMMAR: Mutex<Option<Grammar>> = Mutex::new(None);
+///
+/// fuzz_target!(|data: &[u8]| {
+///     let mut grammar = GRAMMAR.lock().unwrap();
+///     let grammar = grammar.get_or_insert_with(|| {
+///         Grammar::from_source(include_str!("cmd.fuzl")).unwrap()
+///     });
+///     let input = grammar.generate(data).unwrap();
+///     my_crate::parse_command(&input);
+/// });
+///
+/// fuzz_mutator!(|data: &mut [u8], size: usize, max_size: usize, seed: u32| {
+///     custom_mutator(data, size, max_size, seed)
+/// });
+/// ```
+///
+pub struct Grammar {
+    env: ProgramEnv,
+    gen: Gen,
+}
+
+impl Grammar {
+    /// Load a `.fuzl` file
+    ///
+    pub fn load<P>(path: P) -> Result<Self, BajzelError>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self::new(evaluate_file(path)?))
+    }
+
+    /// Load source of a `.fuzl` program (relative paths are resolved
+    /// against the current working directory)
+    ///
+    pub fn from_source(input: &str) -> Result<Self, BajzelError> {
+        Ok(Self::new(evaluate_source_in(input, "")?))
+    }
+
+    fn new(env: ProgramEnv) -> Self {
+        Self {
+            env,
+            gen: Gen::default(),
+        }
+    }
+
+    /// Decode an input (a whole session, all messages followed by TERM)
+    /// from fuzzer data
+    ///
+    /// The same data always gives the same input.
+    ///
+    pub fn generate(&mut self, data: &[u8]) -> Result<Vec<u8>, BajzelError> {
+        self.gen.set_entropy(data);
+        self.gen.generate_input(&self.env)
+    }
+}
+
+/// Mutate fuzzer data decoded by `Grammar`, for `LLVMFuzzerCustomMutator`
+/// (`fuzz_mutator!` in `cargo fuzz`)
+///
+/// Choices are consumed in order, so a mutation either changes a few
+/// choices in place (leaving the rest of an input as it was), regenerates
+/// all choices after some point, or adds new ones at the end.
+///
+/// Returns new size of the data (at most `max_size`).
+///
+pub fn custom_mutator(
+    data: &mut [u8],
+    size: usize,
+    max_size: usize,
+    seed: u32,
+) -> usize {
+    let max_size = max_size.min(data.len());
+    let size = size.min(max_size);
+    if max_size == 0 {
+        return 0;
+    }
+    let mut rng = StdRng::seed_from_u64(seed as u64);
+    match rng.gen_range(0..3) {
+        // Change a single choice (up to 8 bytes)
+        0 if size > 0 => {
+            let pos = rng.gen_range(0..size);
+            let end = (pos + rng.gen_range(1..=8)).min(size);
+            rng.fill(&mut data[pos..end]);
+            size
+        }
+        // Regenerate choices after some point
+        1 if size > 0 => {
+            let pos = rng.gen_range(0..size);
+            let end = rng.gen_range(pos + 1..=max_size);
+            rng.fill(&mut data[pos..end]);
+            end
+        }
+        // Add new choices
+        _ => {
+            let end = rng.gen_range(size..=max_size);
+            rng.fill(&mut data[size..end]);
+            end
+        }
+    }
+}
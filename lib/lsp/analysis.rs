@@ -0,0 +1,718 @@
+use crate::{
+    evaluator::{
+        evaluate_source_in,
+        generator::GenDefinition,
+        sequence::{StepDefinition, END_STEP},
+        structure::{FieldDefinition, ValueDist},
+        ProgramEnv,
+    },
+    lexer::{lex_spanned, lex_tokens, Spanned, Token, Tokens, TYPES},
+    output::escape,
+    parser::parse_tokens_at,
+};
+use std::ops::Range;
+use std::path::Path;
+
+/// Keywords starting a block (or a declaration) of a program
+///
+const BLOCK_KEYWORDS: [&str; 5] =
+    ["CONST", "ENUM", "DEFINE", "SEQUENCE", "GENERATE"];
+
+/// Keywords allowed before the first block
+///
+const TOP_KEYWORDS: [&str; 6] =
+    ["IMPORT", "CONST", "ENUM", "DEFINE", "SEQUENCE", "GENERATE"];
+
+/// Attributes taking group names as arguments
+///
+const GROUP_ATTRIBUTES: [&str; 3] = ["TO", "REPLY", "NEXT"];
+
+/// Distributions accepted by `DIST`
+///
+const DISTRIBUTIONS: [&str; 3] = ["uniform", "log", "normal"];
+
+/// Problem found in a program source
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Bytes of the source the problem is reported at
+    ///
+    pub range: Range<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Keyword,
+    Type,
+    Attribute,
+
+    /// Group or sequence name
+    ///
+    Group,
+
+    /// Field alias or generator parameter
+    ///
+    Field,
+    Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+}
+
+/// Report the first problem found by the lexer, the parser or the
+/// evaluator
+///
+/// Evaluator errors don't carry a position, so they're reported at the
+/// beginning of the source.
+///
+pub fn diagnostics(source: &str, base_dir: &Path) -> Vec<Diagnostic> {
+    let code = code_tokens(source);
+    let illegal: Vec<_> = code
+        .iter()
+        .filter_map(|(token, span)| match token {
+            Token::Illegal(x) => Some(Diagnostic {
+                range: span.clone(),
+                message: format!("unexpected character ({})", x),
+            }),
+            _ => None,
+        })
+        .collect();
+    if !illegal.is_empty() {
+        return illegal;
+    }
+
+    let tokens = match lex_tokens(source) {
+        Ok(tokens) => tokens,
+        Err(_) => return vec![diagnostic(0..0, "lexer failed")],
+    };
+    if let Err((index, _)) = parse_tokens_at(Tokens::new(&tokens)) {
+        return vec![match code.get(index) {
+            Some((token, span)) => diagnostic(
+                span.clone(),
+                &format!("syntax error: unexpected {}", describe_token(token)),
+            ),
+            None => diagnostic(
+                source.len()..source.len(),
+                "syntax error: unexpected end of program",
+            ),
+        }];
+    }
+    match evaluate(source, base_dir) {
+        Ok(_) => vec![],
+        Err(message) => vec![diagnostic(0..0, &message)],
+    }
+}
+
+/// Suggest what can be typed at a given byte offset of the source
+///
+/// Suggestions depend on where the cursor is: types at the beginning of
+/// a field, attributes valid for a field (or a step) after `->`, group
+/// names after `FROM`, in `TO(...)`, `NEXT(...)` and as steps of a
+/// sequence, parameters in a `GENERATE` block.
+///
+pub fn completions(source: &str, offset: usize) -> Vec<Completion> {
+    let code = code_tokens(source);
+    let outline = Outline::new(&code);
+    let mut before = code
+        .iter()
+        .take_while(|(_, span)| span.end <= offset)
+        .count();
+    // A word being typed is completed, not followed
+    if before > 0
+        && code[before - 1].1.end == offset
+        && is_word(&code[before - 1].0)
+    {
+        before -= 1;
+    }
+    let prev = before.checked_sub(1).map(|i| (i, &code[i]));
+    let block = outline.block_at(offset);
+    let groups = || outline.names(CompletionKind::Group);
+
+    let (i, (token, span)) = match prev {
+        Some(prev) => prev,
+        None => return keywords(&TOP_KEYWORDS),
+    };
+    match token {
+        Token::From => return groups(),
+        Token::Generate => return groups(),
+        Token::Ident(_) if i > 0 && code[i - 1].0 == Token::Generate => {
+            return keywords(&["WITH"])
+        }
+        _ => (),
+    }
+    if let Some(attr) = enclosing_attribute(&code[..before]) {
+        return match attr {
+            "NEXT" => {
+                let mut names = groups();
+                if let Some(block) = block {
+                    names.extend(step_labels(&code, block));
+                }
+                names.extend(named(&[END_STEP], CompletionKind::Value));
+                names
+            }
+            x if GROUP_ATTRIBUTES.contains(&x) => groups(),
+            "DIST" => named(&DISTRIBUTIONS, CompletionKind::Value),
+            "ENUM" => outline.enums(),
+            _ => vec![],
+        };
+    }
+    if let Some(arrow) = enclosing_arrow(&code[..before]) {
+        return named(
+            outline.attributes(&code, arrow),
+            CompletionKind::Attribute,
+        );
+    }
+
+    let same_line = !source[span.end..offset].contains('\n');
+    let before_prev = |n: usize| i.checked_sub(n).map(|i| &code[i].0);
+    match (same_line, block) {
+        (true, _) => match token {
+            Token::Type(_) | Token::StringLiteral(_) | Token::Bytes(_) => {
+                keywords(&["AS"])
+            }
+            Token::Ident(_) if before_prev(1) == Some(&Token::Reference) => {
+                keywords(&["AS"])
+            }
+            Token::Ident(_)
+                if before_prev(1) == Some(&Token::As)
+                    && matches!(before_prev(2), Some(Token::Type(x))
+                        if x.eq_ignore_ascii_case("ref")) =>
+            {
+                keywords(&["FROM"])
+            }
+            _ => vec![],
+        },
+        (false, None) => keywords(&TOP_KEYWORDS),
+        (false, Some(block)) => {
+            let mut names = match block.keyword {
+                Token::Define if block.in_where(offset) => {
+                    outline.aliases(block.name)
+                }
+                Token::Define => {
+                    let mut names = named(TYPES, CompletionKind::Type);
+                    names.extend(keywords(&["WHERE"]));
+                    names
+                }
+                Token::Sequence => groups(),
+                Token::Generate => {
+                    named(&GenDefinition::PARAMS, CompletionKind::Field)
+                }
+                _ => vec![],
+            };
+            names.extend(keywords(&BLOCK_KEYWORDS));
+            names
+        }
+    }
+}
+
+/// Find where a group (a sequence, a constant or an enumeration)
+/// referenced at a given byte offset is defined
+///
+pub fn definition(source: &str, offset: usize) -> Option<Range<usize>> {
+    let code = code_tokens(source);
+    let outline = Outline::new(&code);
+    let (token, span) = token_at(&code, offset)?;
+    let block = outline.find_block(token)?;
+    match block.name_span == *span {
+        true => None,
+        false => Some(block.name_span.clone()),
+    }
+}
+
+/// Describe a field (or a group) at a given byte offset, returning bytes
+/// of the source described and the description
+///
+/// Fields are described with their evaluated ranges and lengths, so the
+/// program needs to evaluate.
+///
+pub fn hover(
+    source: &str,
+    base_dir: &Path,
+    offset: usize,
+) -> Option<(Range<usize>, String)> {
+    let code = code_tokens(source);
+    let outline = Outline::new(&code);
+    let env = evaluate(source, base_dir).ok()?;
+
+    if let Some((token, span)) = token_at(&code, offset) {
+        if let Some(block) = outline.find_block(token) {
+            let text = match block.keyword {
+                Token::Define if env.is_template(block.name) => {
+                    format!("template `{}`", block.name)
+                }
+                Token::Define => {
+                    let count =
+                        env.get_group(&block.name).ok()?.fields_iter().count();
+                    format!("group `{}`: {} field(s)", block.name, count)
+                }
+                Token::Enum => {
+                    let count = env.find_enum(block.name)?.len();
+                    format!("enumeration `{}`: {} member(s)", block.name, count)
+                }
+                Token::Const => format!("constant `{}`", block.name),
+                _ => format!("sequence `{}`", block.name),
+            };
+            return Some((span.clone(), text));
+        }
+        // Field updated in a WHERE section
+        if let (Token::Ident(alias), Some(block)) =
+            (token, outline.block_at(span.start))
+        {
+            if block.in_where(span.start) {
+                let group = env.get_group(&block.name).ok()?;
+                let field = group
+                    .fields_iter()
+                    .find(|x| x.alias.as_deref() == Some(*alias))?;
+                let text = format!("`{}`: {}", alias, describe(&field.def));
+                return Some((span.clone(), text));
+            }
+        }
+    }
+
+    let field = outline
+        .fields
+        .iter()
+        .find(|x| x.span.start <= offset && offset <= x.span.end)?;
+    let def = &env
+        .get_group(&field.group)
+        .ok()?
+        .fields_iter()
+        .nth(field.index)?
+        .def;
+    let name = field.alias.unwrap_or("_");
+    Some((field.span.clone(), format!("`{}`: {}", name, describe(def))))
+}
+
+/// Evaluate a program, turning errors into messages
+///
+fn evaluate(source: &str, base_dir: &Path) -> Result<ProgramEnv, String> {
+    evaluate_source_in(source, base_dir).map_err(|e| e.to_string())
+}
+
+/// Tokens without comments
+///
+fn code_tokens(source: &str) -> Vec<Spanned<'_>> {
+    lex_spanned(source)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(token, _)| !matches!(token, Token::Comment(_)))
+        .collect()
+}
+
+fn token_at<'a, 'b>(
+    code: &'b [Spanned<'a>],
+    offset: usize,
+) -> Option<&'b Spanned<'a>> {
+    code.iter()
+        .find(|(_, span)| span.start <= offset && offset <= span.end)
+}
+
+fn is_word(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Ident(_)
+            | Token::Type(_)
+            | Token::ReservedIdent(_)
+            | Token::As
+            | Token::Const
+            | Token::Define
+            | Token::Enum
+            | Token::From
+            | Token::Generate
+            | Token::Sequence
+            | Token::Where
+            | Token::With
+    )
+}
+
+/// Name of an attribute whose arguments are being typed
+///
+fn enclosing_attribute<'a>(code: &[Spanned<'a>]) -> Option<&'a str> {
+    for (i, (token, _)) in code.iter().enumerate().rev() {
+        match token {
+            Token::LeftParen => {
+                return match code.get(i.checked_sub(1)?) {
+                    Some((Token::Ident(x), _)) => Some(x),
+                    Some((Token::Enum, _)) => Some("ENUM"),
+                    _ => None,
+                }
+            }
+            Token::RightParen | Token::Comma | Token::RightArrow => {
+                return None
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Index of `->` starting an attribute list being typed
+///
+fn enclosing_arrow(code: &[Spanned]) -> Option<usize> {
+    for (i, (token, _)) in code.iter().enumerate().rev() {
+        match token {
+            Token::RightArrow => return Some(i),
+            Token::Ident(_)
+            | Token::Enum
+            | Token::LeftParen
+            | Token::RightParen
+            | Token::IntegerLiteral(_)
+            | Token::StringLiteral(_)
+            | Token::ReservedIdent(_) => (),
+            _ => return None,
+        }
+    }
+    None
+}
+
+fn diagnostic(range: Range<usize>, message: &str) -> Diagnostic {
+    Diagnostic {
+        range,
+        message: message.to_owned(),
+    }
+}
+
+fn describe_token(token: &Token) -> String {
+    match token {
+        Token::Ident(x) | Token::Type(x) => format!("`{}`", x),
+        Token::StringLiteral(x) => format!("\"{}\"", x),
+        Token::IntegerLiteral(x) => x.to_string(),
+        x => format!("{:?}", x),
+    }
+}
+
+fn keywords(names: &[&str]) -> Vec<Completion> {
+    named(names, CompletionKind::Keyword)
+}
+
+fn named(names: &[&str], kind: CompletionKind) -> Vec<Completion> {
+    names
+        .iter()
+        .map(|x| Completion {
+            label: x.to_string(),
+            kind,
+        })
+        .collect()
+}
+
+/// Labels given to steps (`group AS label`) of a sequence
+///
+fn step_labels(code: &[Spanned], block: &Block) -> Vec<Completion> {
+    if block.keyword != Token::Sequence {
+        return vec![];
+    }
+    code.windows(2)
+        .filter(|x| block.span.contains(&x[0].1.start))
+        .filter_map(|x| match (&x[0].0, &x[1].0) {
+            (Token::As, Token::Ident(label)) => Some(Completion {
+                label: label.to_string(),
+                kind: CompletionKind::Field,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Evaluated range or length of a field
+///
+fn describe(def: &FieldDefinition) -> String {
+    let dist = |dist: &ValueDist| match dist {
+        ValueDist::Uniform => String::new(),
+        ValueDist::Log => ", log distribution".to_owned(),
+        ValueDist::Normal { mean, sd } => {
+            format!(", normal distribution (mean {}, sd {})", mean, sd)
+        }
+        ValueDist::Weights(x) => format!(", {} weighted values", x.len()),
+        ValueDist::Enum(x) => format!(", {} enumerated values", x.len()),
+    };
+    match def {
+        FieldDefinition::ConstString(x) => {
+            format!("constant \"{}\", length {}", escape(x.as_bytes()), x.len())
+        }
+        FieldDefinition::TextNumber(x) => format!(
+            "number as text, range {} to {}{}",
+            x.min_value,
+            x.max_value,
+            dist(&x.dist)
+        ),
+        FieldDefinition::ByteNumber(x) => format!(
+            "{}-byte {:?} number, range {} to {}{}",
+            x.format.bits() / 8,
+            x.endianess,
+            x.min_value,
+            x.max_value,
+            dist(&x.dist)
+        ),
+        FieldDefinition::AsciiString(x) => match &x.dict {
+            Some(dict) => {
+                format!("string from a dictionary ({} entries)", dict.len())
+            }
+            None => format!(
+                "string, length {} to {}{}",
+                x.length_min,
+                x.length_max,
+                dist(&x.dist)
+            ),
+        },
+        FieldDefinition::Bytes(x) => match &x.dict {
+            Some(dict) => {
+                format!("bytes from a dictionary ({} entries)", dict.len())
+            }
+            None => format!(
+                "bytes, length {} to {}{}",
+                x.length_min,
+                x.length_max,
+                dist(&x.dist)
+            ),
+        },
+        FieldDefinition::Ref(x) => match &x.group {
+            Some(group) => format!("fields of group `{}`", group),
+            None => "reference without a group".to_owned(),
+        },
+        FieldDefinition::Captured(x) => format!("value captured as `${}`", x),
+    }
+}
+
+/// `DEFINE`, `SEQUENCE` or `GENERATE` block of a source, or a `CONST` or
+/// `ENUM` declaration
+///
+struct Block<'a> {
+    keyword: Token<'a>,
+    name: &'a str,
+    name_span: Range<usize>,
+
+    /// Bytes from the keyword to the next block
+    ///
+    span: Range<usize>,
+
+    /// Offset of `WHERE` of a group
+    ///
+    where_at: Option<usize>,
+}
+
+impl<'a> Block<'a> {
+    fn in_where(&self, offset: usize) -> bool {
+        self.where_at.is_some_and(|x| x < offset)
+    }
+}
+
+/// Field definition of a source
+///
+struct FieldSource<'a> {
+    group: &'a str,
+
+    /// Position of the field in its group
+    ///
+    index: usize,
+    def: Token<'a>,
+    alias: Option<&'a str>,
+
+    /// Bytes from the field type (or value) to the end of its attributes
+    ///
+    span: Range<usize>,
+}
+
+/// Blocks and fields of a source, found without parsing it (so that it
+/// works for programs being typed)
+///
+struct Outline<'a> {
+    blocks: Vec<Block<'a>>,
+    fields: Vec<FieldSource<'a>>,
+}
+
+impl<'a> Outline<'a> {
+    fn new(code: &[Spanned<'a>]) -> Self {
+        let mut blocks: Vec<Block> = vec![];
+        let mut fields: Vec<FieldSource> = vec![];
+        let mut depth = 0;
+        for (i, (token, span)) in code.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|i| &code[i].0);
+            match token {
+                Token::Define
+                | Token::Sequence
+                | Token::Generate
+                | Token::Const
+                | Token::Enum => {
+                    let (name, name_span) = match code.get(i + 1) {
+                        Some((Token::Ident(x), span)) => (*x, span.clone()),
+                        _ => ("", span.clone()),
+                    };
+                    blocks.push(Block {
+                        keyword: token.clone(),
+                        name,
+                        name_span,
+                        span: span.start..usize::MAX,
+                        where_at: None,
+                    });
+                    continue;
+                }
+                Token::Where => {
+                    if let Some(block) = blocks.last_mut() {
+                        block.where_at = Some(span.start);
+                    }
+                    continue;
+                }
+                Token::LeftParen => depth += 1,
+                Token::RightParen => depth -= 1,
+                _ => (),
+            }
+
+            // Fields span up to the last token before the next statement
+            let block = match blocks.last() {
+                Some(x) if x.keyword == Token::Define => x,
+                _ => continue,
+            };
+            // Parameters of templates used as types are followed by `AS`
+            let param_type = matches!(token, Token::Ident(_))
+                && matches!(code.get(i + 1), Some((Token::As, _)));
+            let starts_field = depth == 0
+                && block.where_at.is_none()
+                && prev != Some(&Token::Reference)
+                && (param_type
+                    || matches!(
+                        token,
+                        Token::Type(_)
+                            | Token::TypeArray(..)
+                            | Token::StringLiteral(_)
+                            | Token::Bytes(_)
+                            | Token::IntegerLiteral(_)
+                            | Token::ReservedIdent(_)
+                            | Token::Reference
+                    ));
+            if starts_field {
+                let index =
+                    fields.iter().filter(|x| x.group == block.name).count();
+                let def = match (token, code.get(i + 1)) {
+                    (Token::Reference, Some((Token::Ident(x), _))) => {
+                        Token::Ident(x)
+                    }
+                    (x, _) => x.clone(),
+                };
+                let alias = match (code.get(i + 1), code.get(i + 2)) {
+                    (_, Some((Token::Ident(x), _)))
+                        if code[i + 1].0 == Token::As =>
+                    {
+                        Some(*x)
+                    }
+                    (Some((Token::Ident(_), _)), Some((Token::As, _)))
+                        if *token == Token::Reference =>
+                    {
+                        match code.get(i + 3) {
+                            Some((Token::Ident(x), _)) => Some(*x),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                fields.push(FieldSource {
+                    group: block.name,
+                    index,
+                    def,
+                    alias,
+                    span: span.clone(),
+                });
+            } else if let Some(field) = fields.last_mut() {
+                if field.group == block.name && block.where_at.is_none() {
+                    field.span.end = span.end;
+                }
+            }
+        }
+        for i in 1..blocks.len() {
+            blocks[i - 1].span.end = blocks[i].span.start;
+        }
+        Outline { blocks, fields }
+    }
+
+    fn block_at(&self, offset: usize) -> Option<&Block<'a>> {
+        self.blocks
+            .iter()
+            .find(|x| x.span.start < offset && offset <= x.span.end)
+    }
+
+    /// Group, sequence, constant or enumeration named by an identifier
+    ///
+    fn find_block(&self, token: &Token) -> Option<&Block<'a>> {
+        match token {
+            Token::Ident(name) => self
+                .blocks
+                .iter()
+                .find(|x| x.keyword != Token::Generate && x.name == *name),
+            _ => None,
+        }
+    }
+
+    /// Names of groups and sequences
+    ///
+    fn names(&self, kind: CompletionKind) -> Vec<Completion> {
+        self.blocks
+            .iter()
+            .filter(|x| matches!(x.keyword, Token::Define | Token::Sequence))
+            .filter(|x| !x.name.is_empty())
+            .map(|x| Completion {
+                label: x.name.to_owned(),
+                kind,
+            })
+            .collect()
+    }
+
+    fn enums(&self) -> Vec<Completion> {
+        self.blocks
+            .iter()
+            .filter(|x| x.keyword == Token::Enum && !x.name.is_empty())
+            .map(|x| Completion {
+                label: x.name.to_owned(),
+                kind: CompletionKind::Value,
+            })
+            .collect()
+    }
+
+    fn aliases(&self, group: &str) -> Vec<Completion> {
+        self.fields
+            .iter()
+            .filter(|x| x.group == group)
+            .filter_map(|x| x.alias)
+            .map(|x| Completion {
+                label: x.to_owned(),
+                kind: CompletionKind::Field,
+            })
+            .collect()
+    }
+
+    /// Attributes valid for a field (or a step) `->` at a given index
+    /// belongs to
+    ///
+    fn attributes(
+        &self,
+        code: &[Spanned],
+        arrow: usize,
+    ) -> &'static [&'static str] {
+        let (arrow_token, span) = &code[arrow];
+        debug_assert_eq!(*arrow_token, Token::RightArrow);
+        let block = match self.block_at(span.start) {
+            Some(x) => x,
+            None => return &[],
+        };
+        if block.keyword == Token::Sequence {
+            return &StepDefinition::ATTRIBUTES;
+        }
+        let subject = arrow.checked_sub(1).map(|i| &code[i].0);
+        let field = match subject {
+            Some(Token::Ident(alias)) if block.in_where(span.start) => self
+                .fields
+                .iter()
+                .find(|x| x.group == block.name && x.alias == Some(*alias)),
+            _ => self.fields.iter().rev().find(|x| x.span.start < span.start),
+        };
+        match field.map(|x| &x.def) {
+            Some(Token::Type(kind)) | Some(Token::TypeArray(kind, _)) => {
+                FieldDefinition::from_type(kind)
+                    .map_or(&[], |def| def.attributes())
+            }
+            _ => &[],
+        }
+    }
+}
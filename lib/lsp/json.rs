@@ -0,0 +1,243 @@
+use crate::{error::BajzelError, output::json_string};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take, take_while1},
+    character::complete::{char, multispace0, none_of},
+    combinator::{all_consuming, map, map_opt, recognize, value},
+    multi::{many0, separated_list0},
+    number::complete::double,
+    sequence::{delimited, preceded, separated_pair},
+    IResult,
+};
+use std::fmt;
+
+/// JSON value of a message exchanged with an editor
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+
+    /// Members in order they were given
+    ///
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(input: &str) -> Result<Json, BajzelError> {
+        all_consuming(delimited(multispace0, parse_value, multispace0))(input)
+            .map(|(_, json)| json)
+            .map_err(|e| BajzelError::Conversion(format!("json: {}", e)))
+    }
+
+    /// Object with members of given names
+    ///
+    pub fn object<const N: usize>(members: [(&str, Json); N]) -> Json {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value))
+                .collect(),
+        )
+    }
+
+    /// Member of an object (`Null` when there's no such member)
+    ///
+    pub fn get(&self, name: &str) -> &Json {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(x, _)| x == name)
+                .map_or(&Json::Null, |(_, value)| value),
+            _ => &Json::Null,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(x) if *x >= 0.0 && x.fract() == 0.0 => {
+                Some(*x as usize)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(x: &str) -> Self {
+        Json::String(x.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(x: String) -> Self {
+        Json::String(x)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(x: usize) -> Self {
+        Json::Number(x as f64)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(x: bool) -> Self {
+        Json::Bool(x)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(x: Vec<Json>) -> Self {
+        Json::Array(x)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(x) => write!(f, "{}", x),
+            Json::Number(x) if x.fract() == 0.0 && x.abs() < 1e15 => {
+                write!(f, "{}", *x as i64)
+            }
+            Json::Number(x) => write!(f, "{}", x),
+            Json::String(x) => write!(f, "{}", json_string(x)),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", json_string(name), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn parse_value(input: &str) -> IResult<&str, Json> {
+    delimited(
+        multispace0,
+        alt((
+            value(Json::Null, tag("null")),
+            value(Json::Bool(true), tag("true")),
+            value(Json::Bool(false), tag("false")),
+            map(double, Json::Number),
+            map(parse_string, Json::String),
+            map(parse_array, Json::Array),
+            map(parse_object, Json::Object),
+        )),
+        multispace0,
+    )(input)
+}
+
+fn parse_array(input: &str) -> IResult<&str, Vec<Json>> {
+    delimited(
+        char('['),
+        delimited(
+            multispace0,
+            separated_list0(char(','), parse_value),
+            multispace0,
+        ),
+        char(']'),
+    )(input)
+}
+
+fn parse_object(input: &str) -> IResult<&str, Vec<(String, Json)>> {
+    let member = separated_pair(
+        delimited(multispace0, parse_string, multispace0),
+        char(':'),
+        parse_value,
+    );
+    delimited(
+        char('{'),
+        delimited(multispace0, separated_list0(char(','), member), multispace0),
+        char('}'),
+    )(input)
+}
+
+/// Parse a quoted string, decoding escapes (including surrogate pairs)
+///
+fn parse_string(input: &str) -> IResult<&str, String> {
+    let unescaped = map(
+        recognize(take_while1(|c| c != '"' && c != '\\')),
+        |x: &str| x.chars().map(|c| c as u32).collect::<Vec<_>>(),
+    );
+    let escaped = preceded(
+        char('\\'),
+        alt((
+            map(none_of("u"), |c| {
+                let c = match c {
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    x => x,
+                };
+                vec![c as u32]
+            }),
+            map_opt(preceded(char('u'), take(4usize)), |x: &str| {
+                u32::from_str_radix(x, 16).ok().map(|x| vec![x])
+            }),
+        )),
+    );
+    map(
+        delimited(char('"'), many0(alt((unescaped, escaped))), char('"')),
+        |parts| {
+            let units: Vec<u32> = parts.into_iter().flatten().collect();
+            decode_units(&units)
+        },
+    )(input)
+}
+
+/// Join characters and UTF-16 surrogate pairs (from `\uXXXX` escapes)
+///
+fn decode_units(units: &[u32]) -> String {
+    let mut output = String::new();
+    let mut i = 0;
+    while i < units.len() {
+        let unit = units[i];
+        let c = match (unit, units.get(i + 1)) {
+            (0xD800..=0xDBFF, Some(low @ 0xDC00..=0xDFFF)) => {
+                i += 1;
+                char::from_u32(
+                    0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00),
+                )
+            }
+            (x, _) => char::from_u32(x),
+        };
+        output.push(c.unwrap_or(char::REPLACEMENT_CHARACTER));
+        i += 1;
+    }
+    output
+}
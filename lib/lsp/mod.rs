@@ -0,0 +1,372 @@
+use self::{
+    analysis::{Completion, CompletionKind, Diagnostic},
+    json::Json,
+};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+pub mod analysis;
+pub mod json;
+
+/// JSON-RPC error code of requests the server doesn't handle
+///
+const METHOD_NOT_FOUND: i32 = -32601;
+
+/// LSP severity of diagnostics
+///
+const SEVERITY_ERROR: usize = 1;
+
+/// Serve Language Server Protocol over given streams (stdin and stdout
+/// of an editor) until `exit` is received or input ends
+///
+/// Documents are synchronized in full on every change. Supported requests
+/// are completion, go-to-definition and hover; diagnostics are published
+/// whenever a document is opened or changed.
+///
+pub fn serve<R, W>(input: &mut R, output: &mut W) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write,
+{
+    let mut server = Server::default();
+    while let Some(message) = read_message(input)? {
+        let message = match Json::parse(&message) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        if !server.handle(&message, output)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Server {
+    /// Map URI of an open document to its text
+    ///
+    documents: HashMap<String, String>,
+}
+
+impl Server {
+    /// Handle a single request or notification, returning whether to
+    /// carry on
+    ///
+    fn handle<W: Write>(
+        &mut self,
+        message: &Json,
+        output: &mut W,
+    ) -> io::Result<bool> {
+        let params = message.get("params");
+        let uri = params.get("textDocument").get("uri").as_str();
+        let result = match message.get("method").as_str().unwrap_or("") {
+            "initialize" => Some(capabilities()),
+            "shutdown" => Some(Json::Null),
+            "exit" => return Ok(false),
+            "textDocument/didOpen" => {
+                let text = params.get("textDocument").get("text").as_str();
+                if let (Some(uri), Some(text)) = (uri, text) {
+                    self.documents.insert(uri.to_owned(), text.to_owned());
+                    self.publish_diagnostics(uri, output)?;
+                }
+                None
+            }
+            "textDocument/didChange" => {
+                let changes = params.get("contentChanges").as_array();
+                let text = changes
+                    .and_then(|x| x.last())
+                    .and_then(|x| x.get("text").as_str());
+                if let (Some(uri), Some(text)) = (uri, text) {
+                    self.documents.insert(uri.to_owned(), text.to_owned());
+                    self.publish_diagnostics(uri, output)?;
+                }
+                None
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = uri {
+                    self.documents.remove(uri);
+                    let params = Json::object([
+                        ("uri", uri.into()),
+                        ("diagnostics", Json::Array(vec![])),
+                    ]);
+                    notify(output, "textDocument/publishDiagnostics", params)?;
+                }
+                None
+            }
+            "textDocument/completion" => {
+                Some(self.at_position(params, |text, offset, _| {
+                    let items = analysis::completions(text, offset);
+                    Json::Array(items.iter().map(completion_json).collect())
+                }))
+            }
+            "textDocument/definition" => {
+                Some(self.at_position(params, |text, offset, uri| {
+                    match analysis::definition(text, offset) {
+                        Some(range) => Json::object([
+                            ("uri", uri.into()),
+                            ("range", range_json(text, &range)),
+                        ]),
+                        None => Json::Null,
+                    }
+                }))
+            }
+            "textDocument/hover" => {
+                Some(self.at_position(params, |text, offset, uri| {
+                    let base_dir = base_dir(uri);
+                    match analysis::hover(text, &base_dir, offset) {
+                        Some((range, value)) => Json::object([
+                            (
+                                "contents",
+                                Json::object([
+                                    ("kind", "markdown".into()),
+                                    ("value", value.into()),
+                                ]),
+                            ),
+                            ("range", range_json(text, &range)),
+                        ]),
+                        None => Json::Null,
+                    }
+                }))
+            }
+            method => {
+                let id = message.get("id");
+                if *id != Json::Null {
+                    let error = Json::object([
+                        ("code", Json::Number(METHOD_NOT_FOUND as f64)),
+                        (
+                            "message",
+                            format!("unsupported method ({})", method).into(),
+                        ),
+                    ]);
+                    let response = Json::object([
+                        ("jsonrpc", "2.0".into()),
+                        ("id", id.clone()),
+                        ("error", error),
+                    ]);
+                    write_message(output, &response)?;
+                }
+                None
+            }
+        };
+        if let Some(result) = result {
+            let response = Json::object([
+                ("jsonrpc", "2.0".into()),
+                ("id", message.get("id").clone()),
+                ("result", result),
+            ]);
+            write_message(output, &response)?;
+        }
+        Ok(true)
+    }
+
+    /// Answer a request about a position in a document (`Null` for
+    /// unknown documents)
+    ///
+    fn at_position<F>(&self, params: &Json, f: F) -> Json
+    where
+        F: FnOnce(&str, usize, &str) -> Json,
+    {
+        let uri = params.get("textDocument").get("uri").as_str();
+        let text = uri.and_then(|x| self.documents.get(x));
+        let position = params.get("position");
+        let line = position.get("line").as_usize();
+        let character = position.get("character").as_usize();
+        match (uri, text, line, character) {
+            (Some(uri), Some(text), Some(line), Some(character)) => {
+                f(text, offset_of(text, line, character), uri)
+            }
+            _ => Json::Null,
+        }
+    }
+
+    fn publish_diagnostics<W: Write>(
+        &self,
+        uri: &str,
+        output: &mut W,
+    ) -> io::Result<()> {
+        let text = match self.documents.get(uri) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        let diagnostics = analysis::diagnostics(text, &base_dir(uri));
+        let params = Json::object([
+            ("uri", uri.into()),
+            (
+                "diagnostics",
+                Json::Array(
+                    diagnostics
+                        .iter()
+                        .map(|x| diagnostic_json(text, x))
+                        .collect(),
+                ),
+            ),
+        ]);
+        notify(output, "textDocument/publishDiagnostics", params)
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object([
+        (
+            "capabilities",
+            Json::object([
+                // Full text of a document is sent on every change
+                ("textDocumentSync", 1.into()),
+                (
+                    "completionProvider",
+                    Json::object([(
+                        "triggerCharacters",
+                        vec!["(".into(), " ".into()].into(),
+                    )]),
+                ),
+                ("definitionProvider", true.into()),
+                ("hoverProvider", true.into()),
+            ]),
+        ),
+        ("serverInfo", Json::object([("name", "bajzel".into())])),
+    ])
+}
+
+fn completion_json(completion: &Completion) -> Json {
+    // LSP CompletionItemKind
+    let kind: usize = match completion.kind {
+        CompletionKind::Keyword => 14,
+        CompletionKind::Type => 25,
+        CompletionKind::Attribute => 10,
+        CompletionKind::Group => 22,
+        CompletionKind::Field => 5,
+        CompletionKind::Value => 12,
+    };
+    Json::object([
+        ("label", completion.label.as_str().into()),
+        ("kind", kind.into()),
+    ])
+}
+
+fn diagnostic_json(text: &str, diagnostic: &Diagnostic) -> Json {
+    Json::object([
+        ("range", range_json(text, &diagnostic.range)),
+        ("severity", SEVERITY_ERROR.into()),
+        ("source", "bajzel".into()),
+        ("message", diagnostic.message.as_str().into()),
+    ])
+}
+
+fn range_json(text: &str, range: &std::ops::Range<usize>) -> Json {
+    Json::object([
+        ("start", position_json(text, range.start)),
+        ("end", position_json(text, range.end)),
+    ])
+}
+
+/// LSP position (line and UTF-16 column) of a byte offset
+///
+fn position_json(text: &str, offset: usize) -> Json {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+    let character: usize =
+        before[line_start..].chars().map(char::len_utf16).sum();
+    Json::object([
+        ("line", before.matches('\n').count().into()),
+        ("character", character.into()),
+    ])
+}
+
+/// Byte offset of an LSP position (line and UTF-16 column)
+///
+fn offset_of(text: &str, line: usize, character: usize) -> usize {
+    let line_start = match line {
+        0 => 0,
+        n => match text.match_indices('\n').nth(n - 1) {
+            Some((x, _)) => x + 1,
+            None => return text.len(),
+        },
+    };
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// Directory of a `file://` document, against which paths of the program
+/// are resolved
+///
+fn base_dir(uri: &str) -> PathBuf {
+    let path = match uri.strip_prefix("file://") {
+        Some(path) => PathBuf::from(percent_decode(path)),
+        None => return PathBuf::new(),
+    };
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut output = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = s.get(i + 1..i + 3).map(|x| u8::from_str_radix(x, 16));
+        match (bytes[i], hex) {
+            (b'%', Some(Ok(x))) => {
+                output.push(x);
+                i += 3;
+            }
+            (x, _) => {
+                output.push(x);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+/// Read a message framed with a `Content-Length` header, `None` at the
+/// end of input
+///
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let mut body = vec![0; length.unwrap_or(0)];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+}
+
+fn write_message<W: Write>(output: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn notify<W: Write>(
+    output: &mut W,
+    method: &str,
+    params: Json,
+) -> io::Result<()> {
+    let message = Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ]);
+    write_message(output, &message)
+}
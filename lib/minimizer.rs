@@ -0,0 +1,408 @@
+use crate::{
+    error::BajzelError,
+    evaluator::{
+        capture::{alternatives, escape_bytes},
+        structure::{FieldDefinition, GroupDefinition, ValueDist},
+        ProgramEnv,
+    },
+    runner::{triage, Executor, Outcome},
+};
+use regex::bytes::Regex;
+
+/// Crashing input shrunk by `minimize`
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Minimized {
+    pub input: Vec<u8>,
+
+    /// Target executions it took (including the first, checking that the
+    /// input crashes)
+    ///
+    pub executions: u64,
+}
+
+/// Shrink a crashing input field by field, keeping it valid per a program
+///
+/// The input is dissected into messages (split on TERM when GENERATE
+/// selects a SEQUENCE) and fields of their groups. Then, as long as the
+/// target keeps crashing the same way (see `triage::bucket`):
+///
+/// - repeated messages are dropped (down to REPEAT min),
+/// - strings and bytes are shortened (down to LEN min) or replaced by
+///   shorter DICT entries,
+/// - numbers are set to zero (or the value closest to it allowed by RANGE
+///   or WEIGHTS).
+///
+/// Returns `None` if the input doesn't crash the target at all.
+///
+pub fn minimize(
+    env: &ProgramEnv,
+    target: &mut dyn Executor,
+    input: &[u8],
+    max_executions: u64,
+) -> Result<Option<Minimized>, BajzelError> {
+    let mut messages = dissect(env, input)?;
+    let term = env.get_generator()?.term.clone();
+    let mut oracle = Oracle {
+        target,
+        bucket: String::new(),
+        executions: 0,
+        max_executions,
+    };
+    oracle.bucket = match oracle.crash_bucket(input)? {
+        Some(bucket) => bucket,
+        None => return Ok(None),
+    };
+
+    let mut progress = true;
+    while progress && oracle.executions < max_executions {
+        progress = drop_messages(env, &mut messages, &term, &mut oracle)?;
+        for i in 0..messages.len() {
+            for j in 0..messages[i].parts.len() {
+                progress |=
+                    shrink_part(&mut messages, i, j, &term, &mut oracle)?;
+            }
+        }
+    }
+    Ok(Some(Minimized {
+        input: render(&messages, &term),
+        executions: oracle.executions,
+    }))
+}
+
+/// Single message of a dissected input
+///
+pub(crate) struct Message<'a> {
+    pub(crate) group: &'a str,
+    pub(crate) parts: Vec<Part<'a>>,
+}
+
+/// Value of a single field (groups referenced by `ref` are flattened)
+///
+pub(crate) struct Part<'a> {
+    pub(crate) def: &'a FieldDefinition,
+    pub(crate) value: Vec<u8>,
+}
+
+/// Runs a target telling whether a candidate crashes it the same way
+///
+struct Oracle<'a> {
+    target: &'a mut dyn Executor,
+    bucket: String,
+    executions: u64,
+    max_executions: u64,
+}
+
+impl Oracle<'_> {
+    fn crash_bucket(
+        &mut self,
+        input: &[u8],
+    ) -> Result<Option<String>, BajzelError> {
+        self.executions += 1;
+        match self.target.execute(input)? {
+            Outcome::Crashed(crash) => {
+                let stderr = self.target.stderr().unwrap_or_default();
+                Ok(Some(triage::bucket(crash, stderr)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn same_crash(&mut self, input: &[u8]) -> Result<bool, BajzelError> {
+        if self.executions >= self.max_executions {
+            return Ok(false);
+        }
+        Ok(self.crash_bucket(input)?.as_ref() == Some(&self.bucket))
+    }
+}
+
+/// Split an input into messages and fields
+///
+pub(crate) fn dissect<'a>(
+    env: &'a ProgramEnv,
+    input: &[u8],
+) -> Result<Vec<Message<'a>>, BajzelError> {
+    let gen = env.get_generator()?;
+    let groups: Vec<&str> = match env.find_sequence(&gen.name) {
+        Some(sequence) => {
+            sequence.steps.iter().map(|x| x.group.as_str()).collect()
+        }
+        None => vec![gen.name.as_str()],
+    };
+    let no_match = || {
+        BajzelError::Conversion("input doesn't match the program".to_owned())
+    };
+    let chunks = match (groups.len(), gen.term.is_empty()) {
+        (1, _) => {
+            vec![input.strip_suffix(gen.term.as_slice()).unwrap_or(input)]
+        }
+        (_, false) => split_messages(input, &gen.term),
+        (_, true) => return Err(no_match()),
+    };
+    chunks
+        .into_iter()
+        .map(|chunk| {
+            groups
+                .iter()
+                .find_map(|group| {
+                    dissect_message(env, group, chunk).transpose()
+                })
+                .unwrap_or_else(|| Err(no_match()))
+        })
+        .collect()
+}
+
+/// Split an input into messages, each followed by TERM
+///
+fn split_messages<'b>(input: &'b [u8], term: &[u8]) -> Vec<&'b [u8]> {
+    let mut messages = vec![];
+    let mut rest = input;
+    while !rest.is_empty() {
+        match rest.windows(term.len()).position(|x| x == term) {
+            Some(pos) => {
+                messages.push(&rest[..pos]);
+                rest = &rest[pos + term.len()..];
+            }
+            None => {
+                messages.push(rest);
+                break;
+            }
+        }
+    }
+    messages
+}
+
+/// Match a message against a group, `None` if it doesn't match
+///
+fn dissect_message<'a>(
+    env: &'a ProgramEnv,
+    group: &'a str,
+    message: &[u8],
+) -> Result<Option<Message<'a>>, BajzelError> {
+    let mut defs = vec![];
+    flatten(env, env.get_group(&group)?, &mut defs)?;
+    let mut pattern = String::from("(?s-u)^");
+    for def in &defs {
+        pattern.push_str(&format!("({})", field_pattern(def)));
+    }
+    pattern.push('$');
+    let regex = Regex::new(&pattern).map_err(|e| {
+        BajzelError::Conversion(format!("{}: invalid pattern ({})", group, e))
+    })?;
+    let captures = match regex.captures(message) {
+        Some(captures) => captures,
+        None => return Ok(None),
+    };
+    let parts = defs
+        .into_iter()
+        .enumerate()
+        .map(|(i, def)| Part {
+            def,
+            value: captures
+                .get(i + 1)
+                .map(|x| x.as_bytes().to_vec())
+                .unwrap_or_default(),
+        })
+        .collect();
+    Ok(Some(Message { group, parts }))
+}
+
+fn flatten<'a>(
+    env: &'a ProgramEnv,
+    group: &'a GroupDefinition,
+    defs: &mut Vec<&'a FieldDefinition>,
+) -> Result<(), BajzelError> {
+    for field in group.fields_iter() {
+        match &field.def {
+            FieldDefinition::Ref(x) => {
+                let name = x
+                    .group
+                    .as_ref()
+                    .ok_or(BajzelError::NotConstructedProperly)?;
+                flatten(env, env.get_group(name)?, defs)?;
+            }
+            def => defs.push(def),
+        }
+    }
+    Ok(())
+}
+
+/// Pattern of values a field could have been generated with
+///
+/// Strings and bytes without DICT match anything, as they may come from
+/// a global dictionary.
+///
+fn field_pattern(def: &FieldDefinition) -> String {
+    match def {
+        FieldDefinition::ConstString(x) => escape_bytes(x.as_bytes()),
+        FieldDefinition::TextNumber(_) => "-?[0-9]+".to_owned(),
+        FieldDefinition::ByteNumber(x) => {
+            format!(".{{{}}}", x.format.bits() / 8)
+        }
+        FieldDefinition::AsciiString(x) => match &x.dict {
+            Some(dict) => alternatives(dict.entries()),
+            None => ".*".to_owned(),
+        },
+        FieldDefinition::Bytes(x) => match &x.dict {
+            Some(dict) => alternatives(dict.entries()),
+            None => ".*".to_owned(),
+        },
+        FieldDefinition::Captured(_) => ".*".to_owned(),
+        FieldDefinition::Ref(_) => unreachable!("references are flattened"),
+    }
+}
+
+pub(crate) fn render(messages: &[Message], term: &[u8]) -> Vec<u8> {
+    let mut output = vec![];
+    for message in messages {
+        for part in &message.parts {
+            output.extend_from_slice(&part.value);
+        }
+        output.extend_from_slice(term);
+    }
+    output
+}
+
+/// Drop messages repeated more than REPEAT min requires
+///
+/// Returns whether any message was dropped.
+///
+fn drop_messages(
+    env: &ProgramEnv,
+    messages: &mut Vec<Message>,
+    term: &[u8],
+    oracle: &mut Oracle,
+) -> Result<bool, BajzelError> {
+    let gen = env.get_generator()?;
+    let sequence = match env.find_sequence(&gen.name) {
+        Some(sequence) => sequence,
+        None => return Ok(false),
+    };
+    let mut dropped = false;
+    let mut i = messages.len();
+    while i > 0 {
+        i -= 1;
+        let group = messages[i].group;
+        let repeat_min = sequence
+            .steps
+            .iter()
+            .find(|x| x.group == group)
+            .map(|x| x.repeat_min)
+            .unwrap_or_default();
+        let start = messages[..i]
+            .iter()
+            .rposition(|x| x.group != group)
+            .map_or(0, |x| x + 1);
+        let end = messages[i..]
+            .iter()
+            .position(|x| x.group != group)
+            .map_or(messages.len(), |x| x + i);
+        if end - start <= (repeat_min as usize).max(1) {
+            continue;
+        }
+        let removed = messages.remove(i);
+        match oracle.same_crash(&render(messages, term))? {
+            true => dropped = true,
+            false => messages.insert(i, removed),
+        }
+    }
+    Ok(dropped)
+}
+
+/// Replace a value of a field by smaller ones as long as the target
+/// keeps crashing
+///
+/// Returns whether the value was replaced.
+///
+fn shrink_part(
+    messages: &mut [Message],
+    i: usize,
+    j: usize,
+    term: &[u8],
+    oracle: &mut Oracle,
+) -> Result<bool, BajzelError> {
+    let mut shrunk = false;
+    'retry: loop {
+        let part = &messages[i].parts[j];
+        for value in smaller_values(part.def, &part.value) {
+            let current =
+                std::mem::replace(&mut messages[i].parts[j].value, value);
+            if oracle.same_crash(&render(messages, term))? {
+                shrunk = true;
+                continue 'retry;
+            }
+            messages[i].parts[j].value = current;
+        }
+        return Ok(shrunk);
+    }
+}
+
+/// Smaller values a field can take, the smallest first
+///
+fn smaller_values(def: &FieldDefinition, value: &[u8]) -> Vec<Vec<u8>> {
+    match def {
+        FieldDefinition::AsciiString(x) => match &x.dict {
+            Some(dict) => shorter_entries(dict.entries(), value),
+            None => shorter_prefixes(value, x.length_min),
+        },
+        FieldDefinition::Bytes(x) => match &x.dict {
+            Some(dict) => shorter_entries(dict.entries(), value),
+            None => shorter_prefixes(value, x.length_min),
+        },
+        FieldDefinition::TextNumber(x) => {
+            let zero = closest_to_zero(x.min_value, x.max_value, &x.dist);
+            vec![x.encode(zero)]
+                .into_iter()
+                .filter(|x| x != value)
+                .collect()
+        }
+        FieldDefinition::ByteNumber(x) => {
+            let zero = closest_to_zero(x.min_value, x.max_value, &x.dist);
+            vec![x.encode(zero)]
+                .into_iter()
+                .filter(|x| x != value)
+                .collect()
+        }
+        FieldDefinition::ConstString(_)
+        | FieldDefinition::Captured(_)
+        | FieldDefinition::Ref(_) => vec![],
+    }
+}
+
+/// Prefixes of LEN min, half and all but one byte of a value
+///
+fn shorter_prefixes(value: &[u8], min_len: usize) -> Vec<Vec<u8>> {
+    let mut lens = vec![min_len, value.len() / 2, value.len().wrapping_sub(1)];
+    lens.retain(|x| (min_len..value.len()).contains(x));
+    lens.dedup();
+    lens.into_iter().map(|x| value[..x].to_vec()).collect()
+}
+
+/// DICT entries shorter than a value
+///
+fn shorter_entries(entries: &[Vec<u8>], value: &[u8]) -> Vec<Vec<u8>> {
+    let mut shorter: Vec<_> = entries
+        .iter()
+        .filter(|x| x.len() < value.len())
+        .cloned()
+        .collect();
+    shorter.sort_by_key(|x| x.len());
+    shorter
+}
+
+fn closest_to_zero(min: i128, max: i128, dist: &ValueDist) -> i128 {
+    match dist {
+        ValueDist::Weights(weights) => weights
+            .iter()
+            .filter(|(_, weight)| *weight > 0)
+            .map(|(value, _)| *value)
+            .min_by_key(|x| x.abs())
+            .unwrap_or(min),
+        ValueDist::Enum(values) => values
+            .iter()
+            .copied()
+            .min_by_key(|x| x.abs())
+            .unwrap_or(min),
+        _ => 0.clamp(min, max),
+    }
+}
@@ -0,0 +1,330 @@
+use crate::{
+    error::BajzelError,
+    generator::annotation::{Annotation, Value},
+};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// Names of formats accepted by `Format::from_str`
+///
+pub const FORMATS: [&str; 6] =
+    ["raw", "hexdump", "c", "rust", "escaped", "jsonl"];
+
+/// Bytes per line of `hexdump`, `c` and `rust` formats
+///
+const BYTES_PER_LINE: usize = 16;
+
+/// ANSI colors that fields of an annotated hex dump cycle through
+///
+const FIELD_COLORS: [u8; 6] = [31, 32, 33, 34, 35, 36];
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Way generated samples are written out
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Bytes as they are
+    ///
+    #[default]
+    Raw,
+
+    /// Offset, hex bytes and printable characters (like `hexdump -C`)
+    ///
+    /// ```text
+    /// 00000000  43 4d 44 20 31 0a                                 |CMD 1.|
+    /// 00000006
+    /// ```
+    ///
+    Hexdump,
+
+    /// `uint8_t sample_N[] = { ... };`
+    ///
+    C,
+
+    /// `const SAMPLE_N: &[u8] = &[ ... ];`
+    ///
+    Rust,
+
+    /// Printable characters, other bytes (and `\`) escaped as `\xNN`,
+    /// a sample per line
+    ///
+    Escaped,
+
+    /// JSON object per line: `{"seed":7,"length":6,"data":"Q01EIDEK"}`
+    ///
+    /// Data is base64-encoded, seed is `null` when unknown.
+    ///
+    Jsonl,
+}
+
+impl FromStr for Format {
+    type Err = BajzelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(Format::Raw),
+            "hexdump" => Ok(Format::Hexdump),
+            "c" => Ok(Format::C),
+            "rust" => Ok(Format::Rust),
+            "escaped" => Ok(Format::Escaped),
+            "jsonl" => Ok(Format::Jsonl),
+            _ => Err(BajzelError::Conversion(format!(
+                "unknown output format ({})",
+                s
+            ))),
+        }
+    }
+}
+
+/// Write a sample in a given format
+///
+/// Samples are numbered from zero (`no` names C and Rust arrays), `seed`
+/// is the one the sample was generated from (if any).
+///
+pub fn write_sample<W>(
+    out: &mut W,
+    format: Format,
+    no: usize,
+    data: &[u8],
+    seed: Option<u64>,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    match format {
+        Format::Raw => out.write_all(data),
+        Format::Hexdump => write_hexdump(out, data),
+        Format::C => {
+            writeln!(out, "uint8_t sample_{}[] = {{", no)?;
+            write_array(out, data)?;
+            writeln!(out, "}};")
+        }
+        Format::Rust => {
+            writeln!(out, "const SAMPLE_{}: &[u8] = &[", no)?;
+            write_array(out, data)?;
+            writeln!(out, "];")
+        }
+        Format::Escaped => writeln!(out, "{}", escape(data)),
+        Format::Jsonl => {
+            let seed = seed.map_or("null".to_owned(), |x| x.to_string());
+            writeln!(
+                out,
+                r#"{{"seed":{},"length":{},"data":"{}"}}"#,
+                seed,
+                data.len(),
+                base64(data)
+            )
+        }
+    }
+}
+
+/// Write a hex dump where bytes of every field have their own color,
+/// followed by a legend of fields with their values
+///
+/// ```text
+/// 00000000  43 4d 44 20 37 33 34 0a                          |CMD 734.|
+/// 00000008
+/// cmd._ [0..4] = "CMD "
+/// cmd.id [4..7] = 734 (boundary)
+/// ```
+///
+pub fn write_annotated_hexdump<W>(
+    out: &mut W,
+    data: &[u8],
+    annotations: &[Annotation],
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let leaves = Annotation::leaves(annotations);
+    let mut colors = vec![None; data.len()];
+    for (i, leaf) in leaves.iter().enumerate() {
+        let color = FIELD_COLORS[i % FIELD_COLORS.len()];
+        for pos in leaf.range.clone().filter(|x| *x < data.len()) {
+            colors[pos] = Some(color);
+        }
+    }
+    write_colored_hexdump(out, data, &colors)?;
+    for (i, leaf) in leaves.iter().enumerate() {
+        let color = FIELD_COLORS[i % FIELD_COLORS.len()];
+        write!(
+            out,
+            "\x1b[{}m{}.{}\x1b[0m [{}..{}] = ",
+            color,
+            leaf.group,
+            leaf.alias.as_deref().unwrap_or("_"),
+            leaf.range.start,
+            leaf.range.end
+        )?;
+        match &leaf.value {
+            Value::Number(x) => write!(out, "{}", x)?,
+            Value::Bytes(x) => write!(out, "\"{}\"", escape(x))?,
+            Value::Group(_) => (),
+        }
+        match leaf.mutated_by {
+            Some(heuristic) => writeln!(out, " ({})", heuristic)?,
+            None => writeln!(out)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write annotations of a sample as a single line of JSON:
+///
+/// ```text
+/// {"seed":7,"fields":[{"group":"cmd","alias":"id","start":4,"end":7,
+/// "value":734,"mutated_by":"boundary"}, ...]}
+/// ```
+///
+/// Values of strings and bytes are escaped like in `Format::Escaped`,
+/// referenced groups have `fields` instead of a `value`.
+///
+pub fn write_annotations<W>(
+    out: &mut W,
+    annotations: &[Annotation],
+    seed: Option<u64>,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let seed = seed.map_or("null".to_owned(), |x| x.to_string());
+    writeln!(
+        out,
+        r#"{{"seed":{},"fields":{}}}"#,
+        seed,
+        annotations_json(annotations)
+    )
+}
+
+fn annotations_json(annotations: &[Annotation]) -> String {
+    let fields: Vec<_> = annotations.iter().map(annotation_json).collect();
+    format!("[{}]", fields.join(","))
+}
+
+fn annotation_json(annotation: &Annotation) -> String {
+    let null = || "null".to_owned();
+    let value = match &annotation.value {
+        Value::Number(x) => format!(r#""value":{}"#, x),
+        Value::Bytes(x) => format!(r#""value":{}"#, json_string(&escape(x))),
+        Value::Group(x) => format!(r#""fields":{}"#, annotations_json(x)),
+    };
+    format!(
+        r#"{{"group":{},"alias":{},"start":{},"end":{},{},"mutated_by":{}}}"#,
+        json_string(&annotation.group),
+        annotation.alias.as_deref().map_or_else(null, json_string),
+        annotation.range.start,
+        annotation.range.end,
+        value,
+        annotation.mutated_by.map_or_else(null, json_string)
+    )
+}
+
+/// Quote a string for JSON
+///
+pub(crate) fn json_string(s: &str) -> String {
+    let mut output = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                output.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+fn write_hexdump<W: Write>(out: &mut W, data: &[u8]) -> io::Result<()> {
+    write_colored_hexdump(out, data, &vec![None; data.len()])
+}
+
+/// Write a hex dump with an optional ANSI color of every byte
+///
+fn write_colored_hexdump<W: Write>(
+    out: &mut W,
+    data: &[u8],
+    colors: &[Option<u8>],
+) -> io::Result<()> {
+    let paint = |text: String, color: Option<u8>| match color {
+        Some(color) => format!("\x1b[{}m{}\x1b[0m", color, text),
+        None => text,
+    };
+    for (line, chunk) in data.chunks(BYTES_PER_LINE).enumerate() {
+        let offset = line * BYTES_PER_LINE;
+        write!(out, "{:08x} ", offset)?;
+        for i in 0..BYTES_PER_LINE {
+            if i % 8 == 0 {
+                write!(out, " ")?;
+            }
+            match chunk.get(i) {
+                Some(x) => {
+                    let hex = paint(format!("{:02x}", x), colors[offset + i]);
+                    write!(out, "{} ", hex)?
+                }
+                None => write!(out, "   ")?,
+            }
+        }
+        let text: String = chunk
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let c = match x.is_ascii_graphic() || *x == b' ' {
+                    true => *x as char,
+                    false => '.',
+                };
+                paint(c.to_string(), colors[offset + i])
+            })
+            .collect();
+        writeln!(out, "|{}|", text)?;
+    }
+    writeln!(out, "{:08x}", data.len())
+}
+
+/// Elements of a C or Rust array literal, indented
+///
+fn write_array<W: Write>(out: &mut W, data: &[u8]) -> io::Result<()> {
+    for chunk in data.chunks(BYTES_PER_LINE) {
+        let bytes: Vec<_> =
+            chunk.iter().map(|x| format!("0x{:02x}", x)).collect();
+        writeln!(out, "    {},", bytes.join(", "))?;
+    }
+    Ok(())
+}
+
+/// Printable characters as they are, other bytes (and `\`) as `\xNN`
+///
+pub fn escape(data: &[u8]) -> String {
+    data.iter()
+        .map(|x| match x.is_ascii_graphic() || *x == b' ' {
+            true if *x != b'\\' => (*x as char).to_string(),
+            _ => format!("\\x{:02x}", x),
+        })
+        .collect()
+}
+
+/// Standard base64 with padding
+///
+pub fn base64(data: &[u8]) -> String {
+    let mut output = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, x)| bits | (*x as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => {
+                    let index = (bits >> (18 - 6 * i)) & 0x3F;
+                    output.push(BASE64_ALPHABET[index as usize] as char);
+                }
+                false => output.push('='),
+            }
+        }
+    }
+    output
+}
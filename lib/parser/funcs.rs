@@ -6,8 +6,8 @@ use nom::{
     bytes::complete::take,
     combinator::{map, opt, verify},
     error::{Error, ErrorKind},
-    multi::{many0, many1},
-    sequence::{delimited, pair, preceded, separated_pair, terminated},
+    multi::{many0, many1, separated_list1},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
     Err, IResult,
 };
 
@@ -24,15 +24,73 @@ pub(crate) fn parse_program(input: Tokens) -> IResult<Tokens, Program> {
 
 fn parse_statement(input: Tokens) -> IResult<Tokens, Vec<Statement>> {
     alt((
+        map(parse_import_statement, single_to_vec),
+        map(parse_const_statement, single_to_vec),
+        map(parse_enum_statement, single_to_vec),
+        map(parse_define_template_statement, single_to_vec),
         map(parse_define_group_statement, single_to_vec),
         map(parse_define_group_where, single_to_vec),
+        map(parse_define_ref_field, single_to_vec),
         parse_define_fields,
         parse_update_attrs,
         map(parse_define_generator_statement, single_to_vec),
         map(parse_set_param_statement, single_to_vec),
+        parse_define_sequence,
     ))(input)
 }
 
+/// Parse import of another program
+///
+/// Input: `IMPORT "path"`
+/// Output: Import(path)
+///
+fn parse_import_statement(input: Tokens) -> IResult<Tokens, Statement> {
+    map(
+        preceded(
+            import_tag,
+            verify(parse_literal, |x| matches!(x, Literal::StringLiteral(_))),
+        ),
+        |x| match x {
+            Literal::StringLiteral(path) => Statement::Import(path),
+            _ => unreachable!("verified to be a string"),
+        },
+    )(input)
+}
+
+/// Parse constant declaration
+///
+/// Input: `CONST NAME = value`
+/// Output: DefineConst(NAME, value)
+///
+fn parse_const_statement(input: Tokens) -> IResult<Tokens, Statement> {
+    map(preceded(const_tag, parse_assignment), |(name, expr)| {
+        Statement::DefineConst(name, expr)
+    })(input)
+}
+
+/// Parse enumeration declaration, with an optional trailing comma
+///
+/// Input: `ENUM name { MEMBER = value, ... }`
+/// Output: DefineEnum(name, [(MEMBER, value), ...])
+///
+fn parse_enum_statement(input: Tokens) -> IResult<Tokens, Statement> {
+    let members = terminated(
+        separated_list1(comma_tag, parse_assignment),
+        opt(comma_tag),
+    );
+    map(
+        pair(
+            preceded(enum_tag, parse_ident),
+            delimited(open_brace_tag, members, close_brace_tag),
+        ),
+        |(name, members)| Statement::DefineEnum(name, members),
+    )(input)
+}
+
+fn parse_assignment(input: Tokens) -> IResult<Tokens, (Ident, Expr)> {
+    separated_pair(parse_ident, assign_tag, parse_attr_expr)(input)
+}
+
 /// Parse group definition statement
 ///
 /// Input: `DEFINE group_name`
@@ -45,6 +103,67 @@ fn parse_define_group_statement(input: Tokens) -> IResult<Tokens, Statement> {
     )(input)
 }
 
+/// Parse group template definition statement
+///
+/// Input: `DEFINE template_name(param, ...)`
+/// Output: StartTemplateDefinition(template_name, params)
+///
+fn parse_define_template_statement(
+    input: Tokens,
+) -> IResult<Tokens, Statement> {
+    map(
+        pair(
+            preceded(define_tag, parse_ident),
+            delimited(
+                open_paren_tag,
+                separated_list1(comma_tag, parse_ident),
+                close_paren_tag,
+            ),
+        ),
+        |(name, params)| Statement::StartTemplateDefinition(name, params),
+    )(input)
+}
+
+/// Parse reference field definition statement
+///
+/// Input: `ref [AS alias] FROM group_name`
+/// Output: DefineRefField(group_name, alias)
+///
+/// Input: `ref [AS alias] FROM template_name(arg, ...)`
+/// Output: DefineTemplateRefField(template_name, args, alias)
+///
+fn parse_define_ref_field(input: Tokens) -> IResult<Tokens, Statement> {
+    map(
+        tuple((
+            ref_type_tag,
+            opt(preceded(as_tag, parse_ident)),
+            preceded(from_tag, parse_ident),
+            opt(parse_template_args),
+        )),
+        |(_, alias, group, args)| match args {
+            Some(args) => Statement::DefineTemplateRefField(group, args, alias),
+            None => Statement::DefineRefField(group, alias),
+        },
+    )(input)
+}
+
+/// Parse arguments of a group template instance, where types are given
+/// as identifiers
+///
+/// Input: `(le_u16, 255)`
+///
+fn parse_template_args(input: Tokens) -> IResult<Tokens, Vec<Expr>> {
+    let arg = alt((
+        map(parse_type, |x| Expr::IdentExpr(Ident(x))),
+        parse_attr_expr,
+    ));
+    delimited(
+        open_paren_tag,
+        separated_list1(comma_tag, arg),
+        close_paren_tag,
+    )(input)
+}
+
 /// Parse field definition statement
 ///
 /// Input: field_def [AS alias] [-> ATTR(attr_params)]
@@ -63,6 +182,12 @@ fn parse_define_fields(input: Tokens) -> IResult<Tokens, Vec<Statement>> {
                     Statement::DefineConstField(x, None) => {
                         Statement::DefineConstField(x, Some(ident.clone()))
                     }
+                    Statement::DefineCapturedField(x, None) => {
+                        Statement::DefineCapturedField(x, Some(ident.clone()))
+                    }
+                    Statement::DefineParamField(x, None) => {
+                        Statement::DefineParamField(x, Some(ident.clone()))
+                    }
                     _ => panic!("unexpected statement"),
                 }
             } else {
@@ -84,13 +209,24 @@ fn parse_define_fields(input: Tokens) -> IResult<Tokens, Vec<Statement>> {
 fn parse_field_definition_with_alias(
     input: Tokens,
 ) -> IResult<Tokens, (Statement, Option<Ident>)> {
-    pair(parse_field_definition, opt(preceded(as_tag, parse_ident)))(input)
+    alt((
+        pair(parse_field_definition, opt(preceded(as_tag, parse_ident))),
+        // A parameter of a template used as a type needs an alias, so it's
+        // not mistaken for an attribute update
+        map(
+            separated_pair(parse_ident, as_tag, parse_ident),
+            |(x, alias)| (Statement::DefineParamField(x, None), Some(alias)),
+        ),
+    ))(input)
 }
 
 fn parse_field_definition(input: Tokens) -> IResult<Tokens, Statement> {
     alt((
         map(parse_type, |x| Statement::DefineVariableField(x, None)),
         map(parse_literal, |x| Statement::DefineConstField(x, None)),
+        map(preceded(reference_tag, parse_ident), |x| {
+            Statement::DefineCapturedField(x, None)
+        }),
     ))(input)
 }
 
@@ -112,13 +248,13 @@ fn parse_attrs_list(input: Tokens) -> IResult<Tokens, Vec<Statement>> {
 
 fn parse_single_attr(input: Tokens) -> IResult<Tokens, (Ident, Expr)> {
     pair(
-        parse_ident,
+        parse_attr_name,
         delimited(open_paren_tag, new_parse_expr_list, close_paren_tag),
     )(input)
 }
 
 fn new_parse_expr_list(input: Tokens) -> IResult<Tokens, Expr> {
-    map(many1(parse_expr), |list| {
+    map(many1(parse_attr_expr), |list| {
         if list.len() == 1 {
             list.into_iter().next().expect("size just checked")
         } else {
@@ -136,10 +272,37 @@ fn parse_define_generator_statement(
     )(input)
 }
 
+/// Parse sequence definition with its steps
+///
+/// Input: `SEQUENCE name (group [AS label] [-> ATTR(attr_params)])+`
+/// Output: StartSequenceDefinition(name), then DefineStep(group, label)
+/// followed by UpdateField(...) for every step
+///
+fn parse_define_sequence(input: Tokens) -> IResult<Tokens, Vec<Statement>> {
+    map(
+        pair(
+            preceded(sequence_tag, parse_ident),
+            many1(tuple((
+                parse_ident,
+                opt(preceded(as_tag, parse_ident)),
+                parse_opt_attrs,
+            ))),
+        ),
+        |(name, steps)| {
+            let mut out = vec![Statement::StartSequenceDefinition(name)];
+            for (group, label, attrs) in steps {
+                out.push(Statement::DefineStep(group, label));
+                out.extend(attrs.unwrap_or_default());
+            }
+            out
+        },
+    )(input)
+}
+
 fn parse_update_attrs(input: Tokens) -> IResult<Tokens, Vec<Statement>> {
     map(pair(parse_ident, parse_req_attrs), |(ident, attrs)| {
         let mut out = vec![Statement::MakeCurrentField(ident)];
-        out.extend(attrs.into_iter());
+        out.extend(attrs);
         out
     })(input)
 }
@@ -149,8 +312,9 @@ fn parse_define_group_where(input: Tokens) -> IResult<Tokens, Statement> {
 }
 
 fn parse_set_param_statement(input: Tokens) -> IResult<Tokens, Statement> {
-    let p = separated_pair(parse_ident, assign_tag, parse_expr);
-    map(p, |(ident, expr)| Statement::UpdateParam(ident, expr))(input)
+    map(parse_assignment, |(ident, expr)| {
+        Statement::UpdateParam(ident, expr)
+    })(input)
 }
 
 fn parse_ident(input: Tokens) -> IResult<Tokens, Ident> {
@@ -165,6 +329,12 @@ fn parse_ident(input: Tokens) -> IResult<Tokens, Ident> {
     }
 }
 
+/// Parse name of an attribute, which may be the `ENUM` keyword as well
+///
+fn parse_attr_name(input: Tokens) -> IResult<Tokens, Ident> {
+    alt((parse_ident, map(enum_tag, |_| Ident::from("ENUM"))))(input)
+}
+
 fn parse_type(input: Tokens) -> IResult<Tokens, String> {
     let (rest, t) = take(1usize)(input)?;
     if t.tokens.is_empty() {
@@ -200,6 +370,10 @@ fn parse_expr(input: Tokens) -> IResult<Tokens, Expr> {
     alt((map(parse_literal, Expr::LiteralExpr),))(input)
 }
 
+fn parse_attr_expr(input: Tokens) -> IResult<Tokens, Expr> {
+    alt((parse_expr, map(parse_ident, Expr::IdentExpr)))(input)
+}
+
 // https://github.com/Rydgel/monkey-rust/blob/master/lib/parser/mod.rs#L15
 macro_rules! tag_token (
     ($func_name:ident, $tag:expr) => (
@@ -211,16 +385,31 @@ macro_rules! tag_token (
 
 tag_token!(as_tag, Token::As);
 tag_token!(assign_tag, Token::Assign);
+tag_token!(close_brace_tag, Token::RightBrace);
 tag_token!(close_paren_tag, Token::RightParen);
 tag_token!(comma_tag, Token::Comma);
+tag_token!(const_tag, Token::Const);
 tag_token!(define_tag, Token::Define);
+tag_token!(enum_tag, Token::Enum);
 tag_token!(generate_tag, Token::Generate);
 tag_token!(eof_tag, Token::Eof);
+tag_token!(from_tag, Token::From);
+tag_token!(import_tag, Token::Import);
+tag_token!(open_brace_tag, Token::LeftBrace);
 tag_token!(open_paren_tag, Token::LeftParen);
+tag_token!(reference_tag, Token::Reference);
 tag_token!(right_arrow_tag, Token::RightArrow);
+tag_token!(sequence_tag, Token::Sequence);
 tag_token!(where_tag, Token::Where);
 tag_token!(with_tag, Token::With);
 
+fn ref_type_tag(tokens: Tokens) -> IResult<Tokens, Tokens> {
+    verify(
+        take(1usize),
+        |t: &Tokens| matches!(t.tokens[0], Token::Type(x) if x.eq_ignore_ascii_case("ref")),
+    )(tokens)
+}
+
 fn single_to_vec<T>(single: T) -> Vec<T> {
     vec![single]
 }
@@ -3,25 +3,34 @@ use std::ops::Deref;
 
 mod funcs;
 
-///
+/// Entrypoint - parse tokens into a program
 ///
 pub fn parse_tokens(tokens: Tokens) -> Result<Program, String> {
+    parse_tokens_at(tokens).map_err(|(_, e)| e)
+}
+
+/// Parse tokens into a program, reporting an error along with the index
+/// of a token parsing failed at
+///
+pub fn parse_tokens_at(tokens: Tokens) -> Result<Program, (usize, String)> {
+    let count = tokens.tokens.len();
     funcs::parse_program(tokens)
         .map(|(_tokens, program)| program)
         .map_err(|e| match e {
             nom::Err::Incomplete(e) => {
-                format!("Incomplete: {:?}", e)
+                (count.saturating_sub(1), format!("Incomplete: {:?}", e))
             }
-            nom::Err::Error(e) => {
+            nom::Err::Error(e) => (
+                count - e.input.tokens.len(),
                 format!(
                     "Error ({:?}) at token: {:?}, next: {:?}",
                     e.code,
                     e.input.tokens[0],
                     &e.input.tokens[1..]
-                )
-            }
+                ),
+            ),
             nom::Err::Failure(e) => {
-                format!("Failure: {:?}", e)
+                (count - e.input.tokens.len(), format!("Failure: {:?}", e))
             }
         })
 }
@@ -63,6 +72,41 @@ impl From<&str> for Ident {
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Statement {
+    /// Import groups and sequences of another program, prefixing their
+    /// names with a namespace (name of the file without extension)
+    ///
+    /// Example:
+    ///
+    /// ```fuzl
+    /// IMPORT "common/tlv.fuzl"    # groups such as tlv::record
+    /// ```
+    ///
+    Import(String),
+
+    /// Declare a named constant usable in place of a literal in attributes
+    /// and generator parameters
+    ///
+    /// Example:
+    ///
+    /// ```fuzl
+    /// CONST HEADER_SIZE = 40
+    /// ```
+    ///
+    DefineConst(Ident, Expr),
+
+    /// Declare an enumeration of named integer values
+    ///
+    /// Members are constants as well, and the enumeration is a set of values
+    /// a number field can be restricted to with `ENUM(name)`.
+    ///
+    /// Example:
+    ///
+    /// ```fuzl
+    /// ENUM compression { BI_RGB = 0, BI_RLE8 = 1, BI_RLE4 = 2 }
+    /// ```
+    ///
+    DefineEnum(Ident, Vec<(Ident, Expr)>),
+
     /// Create a new definition group and set it as active
     ///
     /// Example:
@@ -73,6 +117,22 @@ pub enum Statement {
     ///
     StartGroupDefinition(Ident),
 
+    /// Create a new group template with given parameters and set it as
+    /// active
+    ///
+    /// Fields of a template are defined like fields of a group, but they
+    /// can use parameters as types, group names and attribute values.
+    ///
+    /// Example:
+    ///
+    /// ```fuzl
+    /// DEFINE tlv(T, max)
+    ///     T     AS tag
+    ///     bytes AS value -> LEN(0 max),
+    /// ```
+    ///
+    StartTemplateDefinition(Ident, Vec<Ident>),
+
     /// Starts a generator definition
     ///
     /// Example:
@@ -106,6 +166,81 @@ pub enum Statement {
     ///
     DefineConstField(Literal, Option<Ident>),
 
+    /// Define new field in an active group that is filled with fields of
+    /// other group
+    ///
+    /// Example
+    ///
+    /// ```fuzl
+    /// DEFINE file
+    ///     ref AS header FROM file_header
+    /// ```
+    ///
+    DefineRefField(Ident, Option<Ident>),
+
+    /// Define new field in an active group that is filled with fields of
+    /// an instance of a group template
+    ///
+    /// Example
+    ///
+    /// ```fuzl
+    /// DEFINE file
+    ///     ref AS record FROM tlv(le_u16, 255)
+    /// ```
+    ///
+    DefineTemplateRefField(Ident, Vec<Expr>, Option<Ident>),
+
+    /// Define new field of an active template whose type is a parameter
+    ///
+    /// Example
+    ///
+    /// ```fuzl
+    /// DEFINE tlv(T, max)
+    ///     T AS tag
+    /// ```
+    ///
+    DefineParamField(Ident, Option<Ident>),
+
+    /// Define new field in an active group that is filled with a value
+    /// captured from a reply
+    ///
+    /// Example
+    ///
+    /// ```fuzl
+    /// DEFINE command
+    ///     "CMD " $session_id
+    /// ```
+    ///
+    DefineCapturedField(Ident, Option<Ident>),
+
+    /// Create a new sequence of messages sent within a single session and
+    /// set it as active
+    ///
+    /// Example:
+    ///
+    /// ```fuzl
+    /// SEQUENCE session
+    /// ```
+    ///
+    StartSequenceDefinition(Ident),
+
+    /// Add a step generating a message from a given group to an active
+    /// sequence and make it active, optionally labelled (so `NEXT` can
+    /// tell apart steps of the same group)
+    ///
+    /// Attributes of a step are updated with `UpdateField`.
+    ///
+    /// Example:
+    ///
+    /// ```fuzl
+    /// SEQUENCE session
+    ///     login                   # "login" part
+    ///     command -> REPEAT(1 5),
+    ///     login AS relogin
+    /// ```
+    ///
+    DefineStep(Ident, Option<Ident>),
+
     /// Make a field of a current group as active
     ///
     /// This is called in WHERE section of a `DEFINE` statement
@@ -179,12 +314,20 @@ pub enum Expr {
     ///
     LiteralExpr(Literal),
 
+    /// Single identifier, such as a name of distribution
+    ///
+    /// Examples:
+    ///
+    /// - `log` in `DIST(log)`
+    ///
+    IdentExpr(Ident),
+
     Group(Vec<Expr>),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Literal {
-    IntegerLiteral(i64),
+    IntegerLiteral(i128),
     StringLiteral(String),
     BytesLiteral(Vec<u8>),
     Reserved(Ident),
@@ -0,0 +1,313 @@
+use crate::{
+    error::BajzelError,
+    evaluator::{evaluate_source_in, ProgramEnv},
+    generator::Gen,
+    lexer::{lex_spanned, Token},
+    output::{write_sample, Format},
+};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const PROMPT: &str = "> ";
+
+const HELP: &str = "\
+gen [NAME] [N]                  generate N inputs (from a group NAME)
+show [NAME]                     show all definitions (or a single one)
+show group|sequence NAME        show a group or a sequence
+show source                     show the program with redefinitions
+set seed N                      generate from seed N again
+set format FORMAT               write inputs as raw, hexdump, c, rust,
+                                escaped or jsonl
+redefine field GROUP.ALIAS DEF  replace a field with a new definition,
+                                such as `u8 AS id`
+reload                          load the file again
+help                            show this message
+quit                            leave
+";
+
+/// Whether to carry on reading commands
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Quit,
+}
+
+/// Interactive session exploring a program: generating from its groups,
+/// showing its definitions and redefining fields without editing a file
+///
+/// Every generated input has its own seed (`seed` + number of inputs
+/// generated since), so `set seed 7` and `gen` gives the same input as
+/// `bajzel gen -s 7`.
+///
+pub struct Repl {
+    /// Source with all redefinitions applied
+    ///
+    source: String,
+
+    /// File the program was loaded from, if any
+    ///
+    path: Option<PathBuf>,
+
+    /// Directory against which relative paths are resolved
+    ///
+    base_dir: PathBuf,
+    env: ProgramEnv,
+    gen: Gen,
+    seed: u64,
+
+    /// Number of inputs generated since the seed was set
+    ///
+    generated: u64,
+    format: Format,
+}
+
+impl Repl {
+    /// Start a session with a program from a given source
+    ///
+    pub fn new<P>(
+        source: &str,
+        base_dir: P,
+        gen: Gen,
+    ) -> Result<Self, BajzelError>
+    where
+        P: AsRef<Path>,
+    {
+        let env = evaluate_source_in(source, &base_dir)?;
+        Ok(Repl {
+            source: source.to_owned(),
+            path: None,
+            base_dir: base_dir.as_ref().to_path_buf(),
+            env,
+            gen,
+            seed: 0,
+            generated: 0,
+            format: Format::Escaped,
+        })
+    }
+
+    /// Start a session with a program loaded from a file
+    ///
+    pub fn load<P>(path: P, gen: Gen) -> Result<Self, BajzelError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let source = read_source(path)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut repl = Repl::new(&source, base_dir, gen)?;
+        repl.path = Some(path.to_path_buf());
+        Ok(repl)
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.generated = 0;
+    }
+
+    /// Read and execute commands until `quit` or the end of input,
+    /// reporting errors without stopping
+    ///
+    pub fn run<R, W>(&mut self, input: &mut R, output: &mut W) -> io::Result<()>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        loop {
+            write!(output, "{}", PROMPT)?;
+            output.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(output)?;
+                return Ok(());
+            }
+            match self.execute(&line, output) {
+                Ok(Flow::Continue) => (),
+                Ok(Flow::Quit) => return Ok(()),
+                Err(e) => writeln!(output, "[-] {}", e)?,
+            }
+        }
+    }
+
+    /// Execute a single command
+    ///
+    pub fn execute<W: Write>(
+        &mut self,
+        line: &str,
+        output: &mut W,
+    ) -> Result<Flow, BajzelError> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => (),
+            ["quit"] | ["exit"] => return Ok(Flow::Quit),
+            ["help"] => write!(output, "{}", HELP).map_err(io_err)?,
+            ["gen", args @ ..] => self.generate(args, output)?,
+            ["show"] => write!(output, "{}", self.env).map_err(io_err)?,
+            ["show", "source"] => {
+                write!(output, "{}", self.source).map_err(io_err)?
+            }
+            ["show", "group" | "sequence", name] | ["show", name] => {
+                let summary = self.env.summary_of(name).ok_or_else(|| {
+                    BajzelError::Syntax(format!(
+                        "no such definition ({})",
+                        name
+                    ))
+                })?;
+                write!(output, "{}", summary).map_err(io_err)?
+            }
+            ["set", "seed", seed] => self.set_seed(parse_number(seed)?),
+            ["set", "format", format] => self.format = format.parse()?,
+            ["redefine", "field", target, ..] => {
+                let definition = line
+                    .split_once(target)
+                    .map(|(_, x)| x.trim())
+                    .unwrap_or_default();
+                self.redefine_field(target, definition)?;
+                writeln!(output, "[*] {} redefined", target).map_err(io_err)?
+            }
+            ["reload"] => {
+                let path = self.path.clone().ok_or_else(|| {
+                    BajzelError::Syntax("not loaded from a file".to_owned())
+                })?;
+                self.replace_source(read_source(&path)?)?;
+                writeln!(output, "[*] {} reloaded", path.display())
+                    .map_err(io_err)?
+            }
+            _ => {
+                return Err(BajzelError::Syntax(format!(
+                    "unknown command ({}), try `help`",
+                    line.trim()
+                )))
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Generate inputs given `gen [NAME] [N]` arguments
+    ///
+    fn generate<W: Write>(
+        &mut self,
+        args: &[&str],
+        output: &mut W,
+    ) -> Result<(), BajzelError> {
+        let (name, count) = match args {
+            [] => (None, 1),
+            [count] if count.parse::<usize>().is_ok() => {
+                (None, parse_number(count)?)
+            }
+            [name] => (Some(*name), 1),
+            [name, count] => (Some(*name), parse_number(count)?),
+            _ => return syntax_err("usage: gen [NAME] [N]"),
+        };
+        let gen_name = &self.env.get_generator()?.name;
+        for no in 0..count {
+            let seed = self.seed.wrapping_add(self.generated);
+            self.generated += 1;
+            self.gen.set_seed(seed);
+            let data = match name {
+                Some(name) if name != gen_name => {
+                    if self.env.find_sequence(&name).is_some() {
+                        return syntax_err(
+                            "sequences are generated only through GENERATE",
+                        );
+                    }
+                    self.env.get_group(&name).map_err(|_| {
+                        BajzelError::Syntax(format!("no such group ({})", name))
+                    })?;
+                    self.gen.generate_named(&self.env, name)?
+                }
+                // Like `bajzel gen`, followed by TERM
+                _ => self.gen.generate_input(&self.env)?,
+            };
+            write_sample(output, self.format, no, &data, Some(seed))
+                .map_err(io_err)?;
+        }
+        Ok(())
+    }
+
+    /// Replace a line defining a field (`GROUP.ALIAS`) of the source with
+    /// a new definition
+    ///
+    /// The source is left untouched when the new one doesn't evaluate.
+    ///
+    fn redefine_field(
+        &mut self,
+        target: &str,
+        definition: &str,
+    ) -> Result<(), BajzelError> {
+        let (group, alias) = target.split_once('.').ok_or_else(|| {
+            BajzelError::Syntax(
+                "usage: redefine field GROUP.ALIAS DEFINITION".to_owned(),
+            )
+        })?;
+        if definition.is_empty() {
+            return syntax_err("usage: redefine field GROUP.ALIAS DEFINITION");
+        }
+        let at = find_field(&self.source, group, alias).ok_or_else(|| {
+            BajzelError::Syntax(format!("no such field ({})", target))
+        })?;
+        let start = self.source[..at].rfind('\n').map_or(0, |x| x + 1);
+        let end = self.source[at..]
+            .find('\n')
+            .map_or(self.source.len(), |x| at + x);
+        let source = format!(
+            "{}    {}{}",
+            &self.source[..start],
+            definition,
+            &self.source[end..]
+        );
+        self.replace_source(source)
+    }
+
+    fn replace_source(&mut self, source: String) -> Result<(), BajzelError> {
+        self.env = evaluate_source_in(&source, &self.base_dir)?;
+        self.source = source;
+        Ok(())
+    }
+}
+
+/// Offset of `AS alias` of a field of a given group
+///
+fn find_field(source: &str, group: &str, alias: &str) -> Option<usize> {
+    let tokens: Vec<_> = lex_spanned(source)
+        .ok()?
+        .into_iter()
+        .filter(|(token, _)| !matches!(token, Token::Comment(_)))
+        .collect();
+    let start = tokens.windows(2).position(|x| {
+        x[0].0 == Token::Define && x[1].0 == Token::Ident(group)
+    })? + 2;
+    tokens[start..]
+        .iter()
+        .take_while(|(token, _)| {
+            !matches!(
+                token,
+                Token::Define
+                    | Token::Sequence
+                    | Token::Generate
+                    | Token::Where
+            )
+        })
+        .collect::<Vec<_>>()
+        .windows(2)
+        .find(|x| x[0].0 == Token::As && x[1].0 == Token::Ident(alias))
+        .map(|x| x[0].1.start)
+}
+
+fn read_source(path: &Path) -> Result<String, BajzelError> {
+    std::fs::read_to_string(path)
+        .map_err(|e| BajzelError::Io(format!("{}: {}", path.display(), e)))
+}
+
+fn parse_number<T: std::str::FromStr>(x: &str) -> Result<T, BajzelError> {
+    x.parse()
+        .map_err(|_| BajzelError::Conversion(format!("not a number ({})", x)))
+}
+
+fn syntax_err<T>(msg: &str) -> Result<T, BajzelError> {
+    Err(BajzelError::Syntax(msg.to_owned()))
+}
+
+fn io_err(e: io::Error) -> BajzelError {
+    BajzelError::Io(e.to_string())
+}
@@ -0,0 +1,212 @@
+use crate::{error::BajzelError, libfuzzer::custom_mutator};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// Size of a coverage map of AFL-instrumented binaries
+///
+pub const MAP_SIZE: usize = 1 << 16;
+
+/// Environment variable through which AFL-instrumented binaries find
+/// a coverage map
+///
+pub const SHM_ENV_VAR: &str = "__AFL_SHM_ID";
+
+/// Environment variable telling AFL++ binaries the size of a coverage map
+///
+pub const MAP_SIZE_ENV_VAR: &str = "AFL_MAP_SIZE";
+
+/// Length of fuzzer data generating a new input (see `Gen::set_entropy`)
+///
+const FRESH_ENTROPY_LEN: usize = 16 * 1024;
+
+/// Maximum length of fuzzer data after mutations
+///
+const MAX_ENTROPY_LEN: usize = 64 * 1024;
+
+/// Probability of mutating an input from a corpus instead of generating
+/// a new one
+///
+const MUTATE_PROBABILITY: f64 = 0.8;
+
+/// SysV shared memory with edge hit counts written by an AFL-instrumented
+/// target
+///
+/// The memory is removed when the map is dropped.
+///
+#[derive(Debug)]
+pub struct CoverageMap {
+    id: i32,
+    ptr: *mut u8,
+}
+
+impl CoverageMap {
+    #[cfg(unix)]
+    pub fn new() -> Result<Self, BajzelError> {
+        let io_err = |call: &str| {
+            BajzelError::Io(format!(
+                "{}: {}",
+                call,
+                std::io::Error::last_os_error()
+            ))
+        };
+        // SAFETY: plain SysV calls, the memory is only accessed through
+        // `ptr` within MAP_SIZE
+        unsafe {
+            let id = libc::shmget(
+                libc::IPC_PRIVATE,
+                MAP_SIZE,
+                libc::IPC_CREAT | libc::IPC_EXCL | 0o600,
+            );
+            if id < 0 {
+                return Err(io_err("shmget"));
+            }
+            let ptr = libc::shmat(id, std::ptr::null(), 0);
+            if ptr as isize == -1 {
+                let e = io_err("shmat");
+                libc::shmctl(id, libc::IPC_RMID, std::ptr::null_mut());
+                return Err(e);
+            }
+            let map = Self {
+                id,
+                ptr: ptr as *mut u8,
+            };
+            map.clear();
+            Ok(map)
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn new() -> Result<Self, BajzelError> {
+        Err(BajzelError::Io(
+            "coverage is only supported on Unix".to_owned(),
+        ))
+    }
+
+    /// Identifier passed to a target in `__AFL_SHM_ID`
+    ///
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    /// Reset hit counts before an execution
+    ///
+    pub fn clear(&self) {
+        // SAFETY: the map is MAP_SIZE bytes long
+        unsafe { std::ptr::write_bytes(self.ptr, 0, MAP_SIZE) }
+    }
+
+    /// Hit counts of the last execution
+    ///
+    pub fn bytes(&self) -> &[u8] {
+        // SAFETY: the map is MAP_SIZE bytes long and lives as long as self
+        unsafe { std::slice::from_raw_parts(self.ptr, MAP_SIZE) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for CoverageMap {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` and `id` come from `shmat` and `shmget`
+        unsafe {
+            libc::shmdt(self.ptr as *const libc::c_void);
+            libc::shmctl(self.id, libc::IPC_RMID, std::ptr::null_mut());
+        }
+    }
+}
+
+/// Inputs that reached new coverage, along with coverage seen so far
+///
+/// Inputs are kept as fuzzer data they were generated from (see
+/// `Gen::set_entropy`), so mutating them keeps following a `.fuzl` file
+/// while reusing most choices that led to new coverage.
+///
+pub struct Feedback {
+    /// Bits of hit count classes not seen yet (AFL's "virgin map")
+    ///
+    virgin: Vec<u8>,
+
+    corpus: Vec<Vec<u8>>,
+}
+
+impl Default for Feedback {
+    fn default() -> Self {
+        Self {
+            virgin: vec![0xFF; MAP_SIZE],
+            corpus: vec![],
+        }
+    }
+}
+
+impl Feedback {
+    /// Pick fuzzer data for the next input
+    ///
+    /// Usually it's a mutation of an input from the corpus, sometimes
+    /// (and always while the corpus is empty) it's new random data.
+    ///
+    pub fn next_entropy(&self, seed: u64) -> Vec<u8> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        match self.corpus.choose(&mut rng) {
+            Some(parent) if rng.gen_bool(MUTATE_PROBABILITY) => {
+                let mut data = parent.clone();
+                let size = data.len();
+                data.resize(MAX_ENTROPY_LEN, 0);
+                let size =
+                    custom_mutator(&mut data, size, MAX_ENTROPY_LEN, rng.gen());
+                data.truncate(size);
+                data
+            }
+            _ => {
+                let mut data = vec![0; FRESH_ENTROPY_LEN];
+                rng.fill(data.as_mut_slice());
+                data
+            }
+        }
+    }
+
+    /// Add an input to the corpus if its execution reached new coverage
+    ///
+    /// Returns whether the input was added.
+    ///
+    pub fn update(&mut self, trace: &[u8], entropy: Vec<u8>) -> bool {
+        let mut new_bits = false;
+        for (virgin, count) in self.virgin.iter_mut().zip(trace) {
+            let class = count_class(*count);
+            if class & *virgin != 0 {
+                *virgin &= !class;
+                new_bits = true;
+            }
+        }
+        if new_bits {
+            self.corpus.push(entropy);
+        }
+        new_bits
+    }
+
+    pub fn corpus_len(&self) -> usize {
+        self.corpus.len()
+    }
+
+    /// Number of edges hit at least once
+    ///
+    pub fn edges(&self) -> usize {
+        self.virgin.iter().filter(|x| **x != 0xFF).count()
+    }
+}
+
+/// Bucket a hit count like AFL does, so loops running a few more times
+/// don't count as new coverage
+///
+fn count_class(count: u8) -> u8 {
+    match count {
+        0 => 0,
+        1 => 1,
+        2 => 2,
+        3 => 4,
+        4..=7 => 8,
+        8..=15 => 16,
+        16..=31 => 32,
+        32..=127 => 64,
+        128..=255 => 128,
+    }
+}
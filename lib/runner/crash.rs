@@ -0,0 +1,251 @@
+use super::Crash;
+use crate::error::BajzelError;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Extension of files describing saved inputs
+///
+pub const META_EXTENSION: &str = "meta";
+
+/// Information needed to reproduce a crash
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashRecord {
+    /// Seed the input was generated from
+    ///
+    pub seed: u64,
+
+    /// `.fuzl` file the input was generated from
+    ///
+    pub source: PathBuf,
+
+    pub crash: Crash,
+    pub generated: Generation,
+
+    /// Dictionaries (`-x`) the generator was given
+    ///
+    pub dicts: Vec<PathBuf>,
+}
+
+/// How a saved input was generated
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generation {
+    /// From the seed alone, so setting it again gives the same input
+    ///
+    Seed,
+
+    /// From fuzzer data derived from the seed and a corpus of a run with
+    /// coverage feedback (see `Runner::enable_feedback`), so only the input
+    /// file reproduces the crash
+    ///
+    Feedback,
+}
+
+/// Directory of crashing inputs
+///
+/// Every input is saved as `BUCKET/id-NNNNNN,seed-S,REASON` (where reason
+/// is `sig-N`, `code-N` or `refused`, and bucket groups crashes likely
+/// caused by the same bug, see `triage::bucket`) next to a `.meta` file
+/// with its `CrashRecord`:
+///
+/// ```text
+/// seed = 1234
+/// source = examples/example1.fuzl
+/// crash = sig-11
+/// generated = seed
+/// dict = http.dict
+/// ```
+///
+/// There's a `dict` line for every dictionary the generator was given.
+///
+/// Inputs generated with coverage feedback are saved as
+/// `BUCKET/id-NNNNNN,feedback-S,REASON` instead, with `generated =
+/// feedback`.
+///
+pub struct CrashStore {
+    dir: PathBuf,
+    next_id: usize,
+}
+
+impl CrashStore {
+    /// Open (and create if needed) a crash directory
+    ///
+    /// Numbering continues after inputs saved earlier (in any bucket).
+    ///
+    pub fn open<P>(dir: P) -> Result<Self, BajzelError>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref().to_path_buf();
+        let io_err = |e: std::io::Error| {
+            BajzelError::Io(format!("{}: {}", dir.display(), e))
+        };
+        std::fs::create_dir_all(&dir).map_err(io_err)?;
+        let next_id = saved_inputs(&dir)?.len();
+        Ok(Self { dir, next_id })
+    }
+
+    /// Save a crashing input to a given bucket, returning its path
+    ///
+    pub fn save(
+        &mut self,
+        input: &[u8],
+        record: &CrashRecord,
+        bucket: &str,
+    ) -> Result<PathBuf, BajzelError> {
+        let name = format!(
+            "id-{:06},{}-{},{}",
+            self.next_id, record.generated, record.seed, record.crash
+        );
+        let bucket_dir = self.dir.join(bucket);
+        let path = bucket_dir.join(&name);
+        let io_err =
+            |e: std::io::Error| BajzelError::Io(format!("{}: {}", name, e));
+        std::fs::create_dir_all(&bucket_dir).map_err(io_err)?;
+        std::fs::write(&path, input).map_err(io_err)?;
+        std::fs::write(path.with_extension(META_EXTENSION), record.to_string())
+            .map_err(io_err)?;
+        self.next_id += 1;
+        Ok(path)
+    }
+}
+
+impl fmt::Display for Crash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Crash::Signal(x) => write!(f, "sig-{}", x),
+            Crash::ExitCode(x) => write!(f, "code-{}", x),
+            Crash::Refused => write!(f, "refused"),
+        }
+    }
+}
+
+impl fmt::Display for Generation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Generation::Seed => write!(f, "seed"),
+            Generation::Feedback => write!(f, "feedback"),
+        }
+    }
+}
+
+impl fmt::Display for CrashRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "seed = {}", self.seed)?;
+        writeln!(f, "source = {}", self.source.display())?;
+        writeln!(f, "crash = {}", self.crash)?;
+        writeln!(f, "generated = {}", self.generated)?;
+        for dict in &self.dicts {
+            writeln!(f, "dict = {}", dict.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Generation {
+    type Err = BajzelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "seed" => Ok(Generation::Seed),
+            "feedback" => Ok(Generation::Feedback),
+            _ => Err(BajzelError::Conversion(format!(
+                "invalid generation ({})",
+                s
+            ))),
+        }
+    }
+}
+
+impl FromStr for Crash {
+    type Err = BajzelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || BajzelError::Conversion(format!("invalid crash ({})", s));
+        let number = |x: &str| x.parse().map_err(|_| err());
+        match s {
+            "refused" => Ok(Crash::Refused),
+            _ => match s.split_once('-') {
+                Some(("sig", x)) => Ok(Crash::Signal(number(x)?)),
+                Some(("code", x)) => Ok(Crash::ExitCode(number(x)?)),
+                _ => Err(err()),
+            },
+        }
+    }
+}
+
+/// Parse contents of a `.meta` file
+///
+impl FromStr for CrashRecord {
+    type Err = BajzelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut seed = None;
+        let mut source = None;
+        let mut crash = None;
+        let mut generated = None;
+        let mut dicts = vec![];
+        for line in s.lines().filter(|x| !x.trim().is_empty()) {
+            let (key, value) = line.split_once('=').ok_or_else(|| {
+                BajzelError::Conversion(format!("invalid line ({})", line))
+            })?;
+            let value = value.trim();
+            match key.trim() {
+                "seed" => {
+                    seed = Some(value.parse().map_err(|_| {
+                        BajzelError::Conversion(format!(
+                            "invalid seed ({})",
+                            value
+                        ))
+                    })?)
+                }
+                "source" => source = Some(PathBuf::from(value)),
+                "crash" => crash = Some(value.parse()?),
+                "generated" => generated = Some(value.parse()?),
+                "dict" => dicts.push(PathBuf::from(value)),
+                // Unknown keys are skipped
+                _ => (),
+            }
+        }
+        match (seed, source, crash, generated) {
+            (Some(seed), Some(source), Some(crash), Some(generated)) => {
+                Ok(CrashRecord {
+                    seed,
+                    source,
+                    crash,
+                    generated,
+                    dicts,
+                })
+            }
+            _ => Err(BajzelError::Conversion(
+                "expected seed, source, crash and generated".to_owned(),
+            )),
+        }
+    }
+}
+
+/// Paths of `.meta` files in a crash directory and its buckets
+///
+pub fn saved_inputs(dir: &Path) -> Result<Vec<PathBuf>, BajzelError> {
+    let io_err = |e: std::io::Error| {
+        BajzelError::Io(format!("{}: {}", dir.display(), e))
+    };
+    let mut metas = vec![];
+    for entry in std::fs::read_dir(dir).map_err(io_err)? {
+        let path = entry.map_err(io_err)?.path();
+        if path.is_dir() {
+            for entry in std::fs::read_dir(&path).map_err(io_err)? {
+                metas.push(entry.map_err(io_err)?.path());
+            }
+        } else {
+            metas.push(path);
+        }
+    }
+    metas.retain(|x| {
+        x.extension().and_then(|x| x.to_str()) == Some(META_EXTENSION)
+    });
+    metas.sort();
+    Ok(metas)
+}
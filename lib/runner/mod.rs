@@ -0,0 +1,532 @@
+use crate::{
+    error::BajzelError,
+    evaluator::ProgramEnv,
+    generator::{session::Session, Gen},
+};
+use std::ffi::{OsStr, OsString};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+pub mod coverage;
+pub mod crash;
+pub mod net;
+pub mod replay;
+pub mod triage;
+
+use coverage::{
+    CoverageMap, Feedback, MAP_SIZE, MAP_SIZE_ENV_VAR, SHM_ENV_VAR,
+};
+use crash::{CrashRecord, CrashStore, Generation};
+
+/// Argument replaced by an input (or by a path to a file holding it)
+///
+pub const INPUT_PLACEHOLDER: &str = "@@";
+
+/// How long to wait between checks whether a target has finished
+///
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// How much of an error output of a target is kept
+///
+const MAX_STDERR_LEN: usize = 64 * 1024;
+
+/// How long to wait for the rest of an error output after a target exits
+///
+const STDERR_GRACE: Duration = Duration::from_millis(100);
+
+/// Way of passing an input to a target
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delivery {
+    /// Input is written to the standard input
+    ///
+    Stdin,
+
+    /// Input is written to a file and its path replaces `@@`
+    ///
+    File(PathBuf),
+
+    /// Input replaces `@@` in arguments (appended when there's no `@@`)
+    ///
+    /// Arguments can't contain NUL bytes, so the input is cut at the
+    /// first one.
+    ///
+    Argv,
+}
+
+/// Reason of a crash
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crash {
+    /// Target was killed by a signal
+    ///
+    Signal(i32),
+
+    /// Target exited with a code marked as a crash
+    ///
+    ExitCode(i32),
+
+    /// Server refused a connection (it's likely dead)
+    ///
+    /// The crash is blamed on the input delivered before.
+    ///
+    Refused,
+}
+
+/// Result of a single target execution
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Target finished with a given exit code
+    ///
+    Exited(i32),
+
+    /// Input was sent to a server, with its reply if it was read
+    ///
+    Delivered(Option<Vec<u8>>),
+
+    Crashed(Crash),
+
+    /// Target didn't finish in time and was killed
+    ///
+    TimedOut,
+}
+
+/// Something inputs are fed to
+///
+pub trait Executor {
+    /// Feed a single input and wait for the result
+    ///
+    fn execute(&mut self, input: &[u8]) -> Result<Outcome, BajzelError>;
+
+    /// Feed all messages of a session
+    ///
+    /// Every input is a session: GENERATE of a group makes a session of
+    /// a single message, and each message is followed by TERM (the same
+    /// bytes as `Gen::generate_input` gives for a seed). By default
+    /// messages are joined and fed as a single input (there are no replies
+    /// to capture values from).
+    ///
+    fn execute_session(
+        &mut self,
+        session: &mut Session,
+    ) -> Result<Outcome, BajzelError> {
+        while session.next_message()?.is_some() {}
+        self.execute(&session.sent().concat())
+    }
+
+    /// Coverage map filled by the last execution (if collected)
+    ///
+    fn coverage(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Error output of the last execution (if captured)
+    ///
+    fn stderr(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+/// Local process executed once per input
+///
+#[derive(Debug, Clone)]
+pub struct Target {
+    program: OsString,
+    args: Vec<OsString>,
+    delivery: Delivery,
+    timeout: Duration,
+    crash_codes: Vec<i32>,
+    coverage: Option<Rc<CoverageMap>>,
+
+    /// Beginning of the error output of the last execution
+    ///
+    stderr: Vec<u8>,
+}
+
+impl Target {
+    /// Create target from a command line (program followed by arguments)
+    ///
+    /// Input is passed through a standard input, 1 second timeout is used
+    /// and only signals are considered crashes.
+    ///
+    pub fn new<S>(command: &[S]) -> Result<Self, BajzelError>
+    where
+        S: AsRef<OsStr>,
+    {
+        let (program, args) = command.split_first().ok_or_else(|| {
+            BajzelError::Io("target command is empty".to_owned())
+        })?;
+        Ok(Self {
+            program: program.as_ref().to_owned(),
+            args: args.iter().map(|x| x.as_ref().to_owned()).collect(),
+            delivery: Delivery::Stdin,
+            timeout: Duration::from_secs(1),
+            crash_codes: vec![],
+            coverage: None,
+            stderr: vec![],
+        })
+    }
+
+    /// Whether any argument is the `@@` placeholder
+    ///
+    pub fn has_placeholder(&self) -> bool {
+        self.args.iter().any(|x| x == INPUT_PLACEHOLDER)
+    }
+
+    pub fn set_delivery(&mut self, delivery: Delivery) {
+        self.delivery = delivery;
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Consider exiting with a given code a crash (e.g. sanitizers'
+    /// `exitcode`)
+    ///
+    pub fn add_crash_code(&mut self, code: i32) {
+        self.crash_codes.push(code);
+    }
+
+    /// Collect coverage of an AFL-instrumented target through a shared
+    /// memory map
+    ///
+    pub fn enable_coverage(&mut self) -> Result<(), BajzelError> {
+        self.coverage = Some(Rc::new(CoverageMap::new()?));
+        Ok(())
+    }
+
+    /// Execute target with a given input and wait for it to finish
+    ///
+    pub fn run(&self, input: &[u8]) -> Result<Outcome, BajzelError> {
+        self.run_capturing(input).map(|(outcome, _)| outcome)
+    }
+
+    /// Execute target like `run`, returning also the beginning of its
+    /// error output (where sanitizers report crashes)
+    ///
+    pub fn run_capturing(
+        &self,
+        input: &[u8],
+    ) -> Result<(Outcome, Vec<u8>), BajzelError> {
+        let mut command = Command::new(&self.program);
+        command
+            .args(self.args_for(input))
+            .stdin(match self.delivery {
+                Delivery::Stdin => Stdio::piped(),
+                _ => Stdio::null(),
+            })
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        if let Some(map) = &self.coverage {
+            map.clear();
+            command
+                .env(SHM_ENV_VAR, map.id().to_string())
+                .env(MAP_SIZE_ENV_VAR, MAP_SIZE.to_string());
+        }
+        if let Delivery::File(path) = &self.delivery {
+            std::fs::write(path, input).map_err(|e| {
+                BajzelError::Io(format!("{}: {}", path.display(), e))
+            })?;
+        }
+
+        let mut child = command.spawn().map_err(|e| {
+            BajzelError::Io(format!(
+                "could not start {}: {}",
+                self.program.to_string_lossy(),
+                e
+            ))
+        })?;
+        let feeder = child.stdin.take().map(|mut stdin| {
+            let input = input.to_vec();
+            // Target may exit without reading everything, so the write
+            // error is ignored
+            std::thread::spawn(move || {
+                let _ = stdin.write_all(&input);
+            })
+        });
+
+        // Processes started by a target may keep the error output open
+        // after it exits, so the output isn't waited for too long
+        let (sender, receiver) = mpsc::channel();
+        if let Some(mut stderr) = child.stderr.take() {
+            std::thread::spawn(move || {
+                let mut output = vec![];
+                let _ = (&mut stderr)
+                    .take(MAX_STDERR_LEN as u64)
+                    .read_to_end(&mut output);
+                let _ = sender.send(output);
+                // Keep draining, so the target doesn't block on a full pipe
+                let _ = std::io::copy(&mut stderr, &mut std::io::sink());
+            });
+        }
+
+        let status = self.wait(&mut child)?;
+        if let Some(feeder) = feeder {
+            let _ = feeder.join();
+        }
+        Ok(match status {
+            Some(status) => {
+                let stderr = receiver.recv_timeout(STDERR_GRACE);
+                (self.classify(status), stderr.unwrap_or_default())
+            }
+            None => (Outcome::TimedOut, vec![]),
+        })
+    }
+
+    /// Arguments with `@@` substituted according to delivery
+    ///
+    fn args_for(&self, input: &[u8]) -> Vec<OsString> {
+        let replacement = match &self.delivery {
+            Delivery::Stdin => return self.args.clone(),
+            Delivery::File(path) => path.clone().into_os_string(),
+            Delivery::Argv => {
+                let end = input.iter().position(|x| *x == 0);
+                bytes_to_os_string(&input[..end.unwrap_or(input.len())])
+            }
+        };
+        let mut args = self
+            .args
+            .iter()
+            .map(|x| match x == INPUT_PLACEHOLDER {
+                true => replacement.clone(),
+                false => x.clone(),
+            })
+            .collect::<Vec<_>>();
+        if self.delivery == Delivery::Argv && !self.has_placeholder() {
+            args.push(replacement);
+        }
+        args
+    }
+
+    /// Wait for a child to exit, killing it after timeout
+    ///
+    /// Returns `None` if the child was killed.
+    ///
+    fn wait(
+        &self,
+        child: &mut Child,
+    ) -> Result<Option<ExitStatus>, BajzelError> {
+        let io_err = |e: std::io::Error| BajzelError::Io(e.to_string());
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            if let Some(status) = child.try_wait().map_err(io_err)? {
+                return Ok(Some(status));
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                child.wait().map_err(io_err)?;
+                return Ok(None);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn classify(&self, status: ExitStatus) -> Outcome {
+        if let Some(signal) = exit_signal(&status) {
+            return Outcome::Crashed(Crash::Signal(signal));
+        }
+        let code = status.code().unwrap_or_default();
+        match self.crash_codes.contains(&code) {
+            true => Outcome::Crashed(Crash::ExitCode(code)),
+            false => Outcome::Exited(code),
+        }
+    }
+}
+
+impl Executor for Target {
+    fn execute(&mut self, input: &[u8]) -> Result<Outcome, BajzelError> {
+        let (outcome, stderr) = self.run_capturing(input)?;
+        self.stderr = stderr;
+        Ok(outcome)
+    }
+
+    fn coverage(&self) -> Option<&[u8]> {
+        self.coverage.as_ref().map(|x| x.bytes())
+    }
+
+    fn stderr(&self) -> Option<&[u8]> {
+        Some(&self.stderr)
+    }
+}
+
+#[cfg(unix)]
+fn exit_signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+#[cfg(unix)]
+fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStrExt;
+    OsStr::from_bytes(bytes).to_owned()
+}
+
+#[cfg(not(unix))]
+fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
+
+/// Counters of a runner session
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub executions: u64,
+    pub crashes: u64,
+    pub timeouts: u64,
+
+    /// Inputs that reached new coverage (with feedback enabled)
+    ///
+    pub corpus: u64,
+
+    /// Edges hit so far (with feedback enabled)
+    ///
+    pub edges: u64,
+}
+
+/// Fuzzing session: generates inputs, feeds them to a target and saves
+/// the crashing ones
+///
+/// Every input is generated from its own seed (`seed + execution number`),
+/// so a saved crash can be regenerated from the `.fuzl` file and the seed.
+///
+/// When GENERATE selects a SEQUENCE, an input is a whole session (all
+/// messages followed by TERM, joined together).
+///
+pub struct Runner {
+    gen: Gen,
+    target: Box<dyn Executor>,
+    store: CrashStore,
+    source: PathBuf,
+    seed: u64,
+    stats: Stats,
+
+    /// Previous input and its seed, blamed when a server goes down
+    ///
+    last: Option<(u64, Vec<u8>)>,
+
+    /// Corpus of inputs reaching new coverage (when enabled)
+    ///
+    feedback: Option<Feedback>,
+
+    /// Dictionaries (`-x`) the generator was given, recorded with crashes
+    ///
+    dicts: Vec<PathBuf>,
+}
+
+impl Runner {
+    /// Create runner for inputs described by a `source` file
+    ///
+    pub fn new(
+        gen: Gen,
+        target: Box<dyn Executor>,
+        store: CrashStore,
+        source: PathBuf,
+        seed: u64,
+    ) -> Self {
+        Self {
+            gen,
+            target,
+            store,
+            source,
+            seed,
+            stats: Stats::default(),
+            last: None,
+            feedback: None,
+            dicts: vec![],
+        }
+    }
+
+    /// Record paths of dictionaries added to the generator with every
+    /// saved crash, as inputs can't be regenerated without them
+    ///
+    pub fn set_dictionaries(&mut self, dicts: Vec<PathBuf>) {
+        self.dicts = dicts;
+    }
+
+    /// Keep inputs reaching new coverage of a target and mutate them
+    /// instead of only generating new ones
+    ///
+    /// Inputs are then generated from fuzzer data (see `Gen::set_entropy`)
+    /// derived from the seed and the corpus, so a saved crash is
+    /// reproduced by its input file rather than by its seed.
+    ///
+    pub fn enable_feedback(&mut self) {
+        self.feedback = Some(Feedback::default());
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Generate and execute a single input
+    ///
+    /// When a server refuses the very first connection, it's an error
+    /// (there's no input to blame).
+    ///
+    pub fn step(&mut self, env: &ProgramEnv) -> Result<Outcome, BajzelError> {
+        let seed = self.seed.wrapping_add(self.stats.executions);
+        let entropy = self.feedback.as_ref().map(|x| x.next_entropy(seed));
+        match &entropy {
+            Some(data) => self.gen.set_entropy(data),
+            None => self.gen.set_seed(seed),
+        }
+        let mut session = self.gen.start_session(env)?;
+        let outcome = self.target.execute_session(&mut session)?;
+        let input = session.sent().concat();
+        self.stats.executions += 1;
+        if let (Some(feedback), Some(trace), Some(entropy)) =
+            (&mut self.feedback, self.target.coverage(), entropy)
+        {
+            feedback.update(trace, entropy);
+            self.stats.corpus = feedback.corpus_len() as u64;
+            self.stats.edges = feedback.edges() as u64;
+        }
+        match &outcome {
+            Outcome::Crashed(Crash::Refused) => {
+                let (seed, input) = self.last.take().ok_or_else(|| {
+                    BajzelError::Io("connection refused".to_owned())
+                })?;
+                self.save_crash(seed, &input, Crash::Refused)?;
+            }
+            Outcome::Crashed(crash) => self.save_crash(seed, &input, *crash)?,
+            Outcome::TimedOut => self.stats.timeouts += 1,
+            Outcome::Exited(_) | Outcome::Delivered(_) => (),
+        }
+        self.last = Some((seed, input));
+        Ok(outcome)
+    }
+
+    fn save_crash(
+        &mut self,
+        seed: u64,
+        input: &[u8],
+        crash: Crash,
+    ) -> Result<(), BajzelError> {
+        self.stats.crashes += 1;
+        let record = CrashRecord {
+            seed,
+            source: self.source.clone(),
+            crash,
+            generated: match self.feedback {
+                Some(_) => Generation::Feedback,
+                None => Generation::Seed,
+            },
+            dicts: self.dicts.clone(),
+        };
+        let stderr = self.target.stderr().unwrap_or_default();
+        self.store
+            .save(input, &record, &triage::bucket(crash, stderr))?;
+        Ok(())
+    }
+}
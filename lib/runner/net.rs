@@ -0,0 +1,257 @@
+use super::{Crash, Executor, Outcome};
+use crate::{error::BajzelError, generator::session::Session};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// Maximum size of a reply that is read
+///
+const MAX_REPLY_LEN: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// Server listening on a network endpoint
+///
+/// Each input is sent as a single message: over a new TCP connection or
+/// as a single UDP datagram. A refused connection means that the server
+/// is dead.
+///
+/// Messages of a session are sent over a single TCP connection (or as
+/// separate UDP datagrams), waiting for a reply after each one if needed
+/// (with `--reply`, or when the step captures values from it).
+///
+/// UDP datagrams are sent from the same socket, so a refusal (ICMP port
+/// unreachable) caused by one message is reported when sending the next.
+///
+#[derive(Debug)]
+pub struct NetTarget {
+    protocol: Protocol,
+    addr: SocketAddr,
+    timeout: Duration,
+    read_reply: bool,
+    udp: Option<UdpSocket>,
+}
+
+impl NetTarget {
+    /// Create target from an URL like `tcp://127.0.0.1:8080` or
+    /// `udp://localhost:53`
+    ///
+    pub fn from_url(url: &str) -> Result<Self, BajzelError> {
+        let (protocol, addr) = match url.split_once("://") {
+            Some(("tcp", addr)) => (Protocol::Tcp, addr),
+            Some(("udp", addr)) => (Protocol::Udp, addr),
+            _ => {
+                return Err(BajzelError::Io(format!(
+                    "{}: expected tcp://host:port or udp://host:port",
+                    url
+                )))
+            }
+        };
+        let addr = addr
+            .to_socket_addrs()
+            .map_err(|e| BajzelError::Io(format!("{}: {}", url, e)))?
+            .next()
+            .ok_or_else(|| {
+                BajzelError::Io(format!("{}: no address found", url))
+            })?;
+        Ok(Self {
+            protocol,
+            addr,
+            timeout: Duration::from_secs(1),
+            read_reply: false,
+            udp: None,
+        })
+    }
+
+    /// Timeout of connecting, sending and reading a reply
+    ///
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Wait for a reply after sending a message
+    ///
+    /// Not getting any reply in time counts as a timeout.
+    ///
+    pub fn set_read_reply(&mut self, read_reply: bool) {
+        self.read_reply = read_reply;
+    }
+
+    pub fn send(&mut self, input: &[u8]) -> Result<Outcome, BajzelError> {
+        self.send_session(&[input.to_vec()])
+    }
+
+    /// Send messages of a session, returning the last reply (if read)
+    ///
+    pub fn send_session(
+        &mut self,
+        messages: &[Vec<u8>],
+    ) -> Result<Outcome, BajzelError> {
+        self.send_messages(&mut messages.iter())
+    }
+
+    fn send_messages(
+        &mut self,
+        messages: &mut dyn Messages,
+    ) -> Result<Outcome, BajzelError> {
+        let result = match self.protocol {
+            Protocol::Tcp => self.send_tcp(messages),
+            Protocol::Udp => self.send_udp(messages),
+        };
+        let e = match result {
+            Ok(reply) => return Ok(Outcome::Delivered(reply)),
+            Err(SendError::Gen(e)) => return Err(e),
+            Err(SendError::Io(e)) => e,
+        };
+        match e.kind() {
+            ErrorKind::ConnectionRefused => {
+                Ok(Outcome::Crashed(Crash::Refused))
+            }
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => {
+                Ok(Outcome::TimedOut)
+            }
+            // Server closed the connection without replying
+            ErrorKind::ConnectionReset | ErrorKind::BrokenPipe => {
+                Ok(Outcome::Delivered(None))
+            }
+            _ => Err(BajzelError::Io(format!("{}: {}", self.addr, e))),
+        }
+    }
+
+    fn send_tcp(
+        &self,
+        messages: &mut dyn Messages,
+    ) -> Result<Option<Vec<u8>>, SendError> {
+        let mut stream = TcpStream::connect_timeout(&self.addr, self.timeout)?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        let mut reply = None;
+        while let Some(message) = messages.next_message()? {
+            stream.write_all(&message)?;
+            if self.read_reply || messages.expects_reply() {
+                let data = read_reply(&mut stream)?;
+                messages.handle_reply(&data)?;
+                reply = Some(data);
+            }
+        }
+        Ok(reply)
+    }
+
+    fn send_udp(
+        &mut self,
+        messages: &mut dyn Messages,
+    ) -> Result<Option<Vec<u8>>, SendError> {
+        let socket = match &self.udp {
+            Some(socket) => socket,
+            None => {
+                let local: SocketAddr = match self.addr {
+                    SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+                    SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+                };
+                let socket = UdpSocket::bind(local)?;
+                socket.connect(self.addr)?;
+                socket.set_read_timeout(Some(self.timeout))?;
+                self.udp.insert(socket)
+            }
+        };
+        let mut reply = None;
+        while let Some(message) = messages.next_message()? {
+            socket.send(&message)?;
+            if self.read_reply || messages.expects_reply() {
+                let mut data = vec![0; MAX_REPLY_LEN];
+                let len = socket.recv(&mut data)?;
+                data.truncate(len);
+                messages.handle_reply(&data)?;
+                reply = Some(data);
+            }
+        }
+        Ok(reply)
+    }
+}
+
+fn read_reply(stream: &mut TcpStream) -> std::io::Result<Vec<u8>> {
+    let mut reply = vec![0; MAX_REPLY_LEN];
+    let len = stream.read(&mut reply)?;
+    reply.truncate(len);
+    Ok(reply)
+}
+
+/// Source of messages sent one by one
+///
+trait Messages {
+    fn next_message(&mut self) -> Result<Option<Vec<u8>>, BajzelError>;
+
+    fn expects_reply(&self) -> bool;
+
+    fn handle_reply(&mut self, reply: &[u8]) -> Result<(), BajzelError>;
+}
+
+/// Messages known up front (nothing is captured from replies)
+///
+impl Messages for std::slice::Iter<'_, Vec<u8>> {
+    fn next_message(&mut self) -> Result<Option<Vec<u8>>, BajzelError> {
+        Ok(self.next().cloned())
+    }
+
+    fn expects_reply(&self) -> bool {
+        false
+    }
+
+    fn handle_reply(&mut self, _reply: &[u8]) -> Result<(), BajzelError> {
+        Ok(())
+    }
+}
+
+/// Messages generated as the session goes, with values captured from
+/// replies
+///
+impl Messages for Session<'_> {
+    fn next_message(&mut self) -> Result<Option<Vec<u8>>, BajzelError> {
+        Session::next_message(self)
+    }
+
+    fn expects_reply(&self) -> bool {
+        Session::expects_reply(self)
+    }
+
+    fn handle_reply(&mut self, reply: &[u8]) -> Result<(), BajzelError> {
+        Session::handle_reply(self, reply)
+    }
+}
+
+/// Failure of sending a session: either the network or generating the
+/// next message failed
+///
+enum SendError {
+    Io(std::io::Error),
+    Gen(BajzelError),
+}
+
+impl From<std::io::Error> for SendError {
+    fn from(e: std::io::Error) -> Self {
+        SendError::Io(e)
+    }
+}
+
+impl From<BajzelError> for SendError {
+    fn from(e: BajzelError) -> Self {
+        SendError::Gen(e)
+    }
+}
+
+impl Executor for NetTarget {
+    fn execute(&mut self, input: &[u8]) -> Result<Outcome, BajzelError> {
+        self.send(input)
+    }
+
+    fn execute_session(
+        &mut self,
+        session: &mut Session,
+    ) -> Result<Outcome, BajzelError> {
+        self.send_messages(session)
+    }
+}
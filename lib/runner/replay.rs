@@ -0,0 +1,185 @@
+use super::{
+    crash::{saved_inputs, CrashRecord, Generation},
+    Crash, Executor, Outcome,
+};
+use crate::{
+    error::BajzelError,
+    evaluator::{evaluate_file, ProgramEnv},
+    generator::Gen,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Result of feeding a saved crash to a target again
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Target still crashes (possibly for a different reason)
+    ///
+    Crashes(Crash),
+
+    /// Target handles the input fine now
+    ///
+    Fixed,
+
+    TimedOut,
+
+    /// Input file is missing and the input can't be regenerated the same
+    /// way (for a given reason)
+    ///
+    NotReproducible(&'static str),
+}
+
+/// Saved crash fed to a target again
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replayed {
+    /// Path of the input (it may be missing when regenerated)
+    ///
+    pub input: PathBuf,
+
+    pub record: CrashRecord,
+    pub verdict: Verdict,
+
+    /// Whether the input was regenerated from its seed and `.fuzl` file
+    ///
+    pub regenerated: bool,
+}
+
+/// Results of replaying a crash directory, in order of saved inputs
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub inputs: Vec<Replayed>,
+}
+
+impl Report {
+    /// Number of inputs with a given kind of verdict
+    ///
+    pub fn count(&self, verdict: fn(&Verdict) -> bool) -> usize {
+        self.inputs.iter().filter(|x| verdict(&x.verdict)).count()
+    }
+
+    /// Whether any saved crash still crashes the target
+    ///
+    pub fn has_regressions(&self) -> bool {
+        self.count(|x| matches!(x, Verdict::Crashes(_))) > 0
+    }
+}
+
+/// Feed every input saved in a crash directory (see `CrashStore`) to
+/// a target again
+///
+/// Inputs whose files are missing are regenerated from the seed and the
+/// `.fuzl` file in their `.meta` files (a relative path is resolved
+/// against the current working directory), like `Runner` generated them.
+/// Messages of a session are then sent one by one, so values can be
+/// captured from replies.
+///
+/// An input is regenerated only when it was generated from its seed alone
+/// (not with coverage feedback) and the generator was given the same
+/// dictionaries (`dicts`, as paths recorded by `Runner`), otherwise it's
+/// reported as not reproducible.
+///
+pub fn replay(
+    dir: &Path,
+    target: &mut dyn Executor,
+    gen: &mut Gen,
+    dicts: &[PathBuf],
+) -> Result<Report, BajzelError> {
+    let io_err = |path: &Path, e: std::io::Error| {
+        BajzelError::Io(format!("{}: {}", path.display(), e))
+    };
+    let mut envs: HashMap<PathBuf, ProgramEnv> = HashMap::new();
+    let mut report = Report::default();
+    for meta in saved_inputs(dir)? {
+        let record: CrashRecord = std::fs::read_to_string(&meta)
+            .map_err(|e| io_err(&meta, e))?
+            .parse()?;
+        let input = meta.with_extension("");
+        let regenerated = !input.exists();
+        let reason = match (record.generated, record.dicts == dicts) {
+            _ if !regenerated => None,
+            (Generation::Feedback, _) => {
+                Some("generated with coverage feedback")
+            }
+            (Generation::Seed, false) => {
+                Some("generated with other dictionaries")
+            }
+            (Generation::Seed, true) => None,
+        };
+        if let Some(reason) = reason {
+            report.inputs.push(Replayed {
+                input,
+                record,
+                verdict: Verdict::NotReproducible(reason),
+                regenerated: false,
+            });
+            continue;
+        }
+        let outcome = match regenerated {
+            false => target.execute(
+                &std::fs::read(&input).map_err(|e| io_err(&input, e))?,
+            )?,
+            true => {
+                if !envs.contains_key(&record.source) {
+                    let env = evaluate_file(&record.source)?;
+                    envs.insert(record.source.clone(), env);
+                }
+                let env = &envs[&record.source];
+                gen.set_seed(record.seed);
+                target.execute_session(&mut gen.start_session(env)?)?
+            }
+        };
+        let verdict = match outcome {
+            Outcome::Crashed(crash) => Verdict::Crashes(crash),
+            Outcome::TimedOut => Verdict::TimedOut,
+            Outcome::Exited(_) | Outcome::Delivered(_) => Verdict::Fixed,
+        };
+        report.inputs.push(Replayed {
+            input,
+            record,
+            verdict,
+            regenerated,
+        });
+    }
+    Ok(report)
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Crashes(crash) => write!(f, "still crashes ({})", crash),
+            Verdict::Fixed => write!(f, "fixed"),
+            Verdict::TimedOut => write!(f, "timed out"),
+            Verdict::NotReproducible(reason) => {
+                write!(f, "input missing, not reproducible ({})", reason)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for replayed in &self.inputs {
+            write!(f, "{}: {}", replayed.input.display(), replayed.verdict)?;
+            match replayed.regenerated {
+                true => writeln!(
+                    f,
+                    " (regenerated, seed {})",
+                    replayed.record.seed
+                )?,
+                false => writeln!(f)?,
+            }
+        }
+        writeln!(
+            f,
+            "{} still crash, {} fixed, {} timed out, {} not reproducible",
+            self.count(|x| matches!(x, Verdict::Crashes(_))),
+            self.count(|x| *x == Verdict::Fixed),
+            self.count(|x| *x == Verdict::TimedOut),
+            self.count(|x| matches!(x, Verdict::NotReproducible(_)))
+        )
+    }
+}
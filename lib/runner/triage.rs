@@ -0,0 +1,213 @@
+use super::{
+    crash::{saved_inputs, CrashRecord, Generation},
+    Crash,
+};
+use crate::error::BajzelError;
+use regex::Regex;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Report of unique crashes written to a crash directory
+///
+pub const SUMMARY_FILE: &str = "summary.txt";
+
+/// Number of stack frames identifying a bug
+///
+pub const TOP_FRAMES: usize = 3;
+
+/// Frame of a sanitizer stack trace, such as
+///
+/// ```text
+///     #0 0x4f5e3a in parse_header /src/parser.c:42:7
+///     #1 0x7f12a3c in (/lib/x86_64-linux-gnu/libc.so.6+0x29d8f)
+/// ```
+///
+const FRAME_PATTERN: &str =
+    r"^\s*#(\d+)\s+0x[0-9a-fA-F]+\s+(?:in\s+(\S+)|\(([^+)]+))";
+
+/// UBSan report without a stack trace, such as
+///
+/// ```text
+/// parser.c:12:5: runtime error: signed integer overflow
+/// ```
+///
+const UBSAN_PATTERN: &str = r"^(\S+:\d+):\d+: runtime error:";
+
+/// Frames of sanitizers themselves (not a part of a target)
+///
+const RUNTIME_PREFIXES: &[&str] = &[
+    "__asan",
+    "__ubsan",
+    "__sanitizer",
+    "__interceptor_",
+    "__msan",
+];
+
+/// Functions (or modules) of the top frames of a first stack trace in
+/// a sanitizer report
+///
+/// Frames of sanitizers are skipped. When there's no stack trace, a source
+/// location of an UBSan report is used instead.
+///
+pub fn stack_frames(stderr: &[u8]) -> Vec<String> {
+    static FRAME: OnceLock<Regex> = OnceLock::new();
+    static UBSAN: OnceLock<Regex> = OnceLock::new();
+    let frame = FRAME.get_or_init(|| {
+        Regex::new(FRAME_PATTERN).expect("valid frame pattern")
+    });
+    let ubsan = UBSAN.get_or_init(|| {
+        Regex::new(UBSAN_PATTERN).expect("valid UBSan pattern")
+    });
+    let stderr = String::from_utf8_lossy(stderr);
+    let mut frames = vec![];
+    let mut location = None;
+    let mut started = false;
+    for line in stderr.lines() {
+        if let Some(captures) = frame.captures(line) {
+            // Next stack trace (e.g. where memory was freed) starts
+            if started && &captures[1] == "0" {
+                break;
+            }
+            started = true;
+            let name = captures.get(2).or_else(|| captures.get(3));
+            match name.map(|x| x.as_str()) {
+                Some(name)
+                    if !RUNTIME_PREFIXES
+                        .iter()
+                        .any(|x| name.starts_with(x)) =>
+                {
+                    frames.push(name.to_owned())
+                }
+                _ => (),
+            }
+        } else if let Some(captures) = ubsan.captures(line) {
+            location.get_or_insert_with(|| captures[1].to_owned());
+        }
+    }
+    frames.truncate(TOP_FRAMES);
+    match (frames.is_empty(), location) {
+        (true, Some(location)) => vec![location],
+        _ => frames,
+    }
+}
+
+/// Name of a bucket grouping crashes likely caused by the same bug
+///
+/// It's the crash reason followed by a hash of top stack frames (when
+/// a sanitizer reported any), e.g. `sig-6-1f3a9c0d` or `sig-11`.
+///
+pub fn bucket(crash: Crash, stderr: &[u8]) -> String {
+    let frames = stack_frames(stderr);
+    match frames.is_empty() {
+        true => crash.to_string(),
+        false => format!("{}-{:08x}", crash, fnv1a(&frames.join("\n")) as u32),
+    }
+}
+
+/// Hash that stays the same across builds (unlike `DefaultHasher`), so
+/// buckets of different sessions match
+///
+fn fnv1a(data: &str) -> u64 {
+    data.bytes().fold(0xcbf29ce484222325, |hash, x| {
+        (hash ^ x as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Unique crash with its smallest reproducer
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BucketSummary {
+    pub bucket: String,
+
+    /// Number of inputs in the bucket
+    ///
+    pub count: usize,
+
+    pub smallest: PathBuf,
+    pub size: u64,
+    pub seed: u64,
+    pub generated: Generation,
+}
+
+/// Report of unique crashes in a crash directory
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub buckets: Vec<BucketSummary>,
+}
+
+/// Summarize crashes saved in a directory (see `CrashStore`)
+///
+pub fn summarize(dir: &Path) -> Result<Summary, BajzelError> {
+    let io_err = |path: &Path, e: std::io::Error| {
+        BajzelError::Io(format!("{}: {}", path.display(), e))
+    };
+    let mut summary = Summary::default();
+    for meta in saved_inputs(dir)? {
+        let input = meta.with_extension("");
+        let size = std::fs::metadata(&input)
+            .map_err(|e| io_err(&input, e))?
+            .len();
+        let record: CrashRecord = std::fs::read_to_string(&meta)
+            .map_err(|e| io_err(&meta, e))?
+            .parse()?;
+        let bucket = match input.parent() {
+            Some(parent) if parent != dir => parent
+                .file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            _ => record.crash.to_string(),
+        };
+        let found = summary.buckets.iter_mut().find(|x| x.bucket == bucket);
+        match found {
+            Some(found) => {
+                found.count += 1;
+                if size < found.size {
+                    found.smallest = input;
+                    found.size = size;
+                    found.seed = record.seed;
+                    found.generated = record.generated;
+                }
+            }
+            None => summary.buckets.push(BucketSummary {
+                bucket,
+                count: 1,
+                smallest: input,
+                size,
+                seed: record.seed,
+                generated: record.generated,
+            }),
+        }
+    }
+    summary.buckets.sort_by(|a, b| a.bucket.cmp(&b.bucket));
+    Ok(summary)
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total: usize = self.buckets.iter().map(|x| x.count).sum();
+        writeln!(
+            f,
+            "{} unique crashes ({} inputs)",
+            self.buckets.len(),
+            total
+        )?;
+        for bucket in &self.buckets {
+            write!(
+                f,
+                "{}: {} inputs, smallest {} ({} bytes, seed {}",
+                bucket.bucket,
+                bucket.count,
+                bucket.smallest.display(),
+                bucket.size,
+                bucket.seed
+            )?;
+            match bucket.generated {
+                Generation::Seed => writeln!(f, ")")?,
+                Generation::Feedback => writeln!(f, " with feedback)")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// What is known about a watched file: its modification time and size
+/// (`None` when it couldn't be read)
+///
+type Stamp = Option<(SystemTime, u64)>;
+
+/// Watches files for modifications by polling their metadata
+///
+/// Polling is used instead of platform notification services, so it works
+/// the same everywhere (including network filesystems and containers).
+/// Besides the modification time the size is compared as well, as some
+/// filesystems record times with a coarse precision.
+///
+#[derive(Debug, Default)]
+pub struct Watcher {
+    files: Vec<(PathBuf, Stamp)>,
+}
+
+impl Watcher {
+    /// Start watching given files in their current state
+    ///
+    pub fn new<I, P>(paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut watcher = Watcher::default();
+        watcher.watch(paths);
+        watcher
+    }
+
+    /// Replace watched files with given ones in their current state
+    ///
+    pub fn watch<I, P>(&mut self, paths: I)
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        self.files.clear();
+        for path in paths {
+            let path = path.as_ref();
+            if self.files.iter().all(|(x, _)| x != path) {
+                self.files.push((path.to_path_buf(), stamp(path)));
+            }
+        }
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(path, _)| path.as_path())
+    }
+
+    /// Files modified (created or removed) since they were last seen,
+    /// remembering their new state
+    ///
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        for (path, seen) in self.files.iter_mut() {
+            let now = stamp(path);
+            if now != *seen {
+                *seen = now;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+
+    /// Block until any of the files is modified, checking them every
+    /// `interval`
+    ///
+    /// Returns once the files stop changing for an `interval`, so a file
+    /// saved in several writes is reported just once.
+    ///
+    pub fn wait(&mut self, interval: Duration) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        loop {
+            thread::sleep(interval);
+            let now = self.changed();
+            if now.is_empty() && !changed.is_empty() {
+                return changed;
+            }
+            for path in now {
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
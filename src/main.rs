@@ -1,84 +1,778 @@
 use bajzel_lib::{
-    evaluator::evaluate_program,
+    covering::covering_array,
+    dictionary::Dictionary,
+    enumerator::enumerate,
+    error::BajzelError,
+    evaluator::{evaluate_file, ProgramEnv},
+    formatter::format_source,
     generator::Gen,
-    lexer::{lex_tokens, Tokens},
+    lexer::{lex_tokens, Token, Tokens},
+    lsp,
+    minimizer::minimize,
+    output::{
+        write_annotated_hexdump, write_annotations, write_sample, Format,
+        FORMATS,
+    },
     parser::parse_tokens,
+    repl::Repl,
+    runner::{
+        crash::CrashStore,
+        net::NetTarget,
+        replay::replay,
+        triage::{self, SUMMARY_FILE},
+        Crash, Delivery, Executor, Outcome, Runner, Target,
+    },
+    watch::Watcher,
 };
-use clap::{arg, Command};
+use clap::{arg, value_parser, Arg, ArgAction, ArgMatches, Command};
+use rand::random;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How often (in executions) runner statistics are printed
+///
+const STATS_INTERVAL: u64 = 1000;
+
+/// How often files are checked for changes by `gen --watch`
+///
+const WATCH_INTERVAL: Duration = Duration::from_millis(300);
 
 fn run() -> Result<(), String> {
-    let cmd = Command::new("bajzel").arg(arg!(<input> ".fuzl input file"));
-    let m = cmd
-        .try_get_matches()
-        .map_err(|_| "Args required".to_string())?;
+    let cmd = Command::new("bajzel")
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .args(generate_args())
+        .subcommand(
+            Command::new("check")
+                .about("Lex, parse and evaluate a program, reporting errors")
+                .arg(arg!(<input> ".fuzl input file")),
+        )
+        .subcommand(
+            Command::new("tokens")
+                .about("Print tokens of a program")
+                .arg(arg!(<input> ".fuzl input file")),
+        )
+        .subcommand(
+            Command::new("ast")
+                .about("Print parsed statements of a program")
+                .arg(arg!(<input> ".fuzl input file")),
+        )
+        .subcommand(
+            Command::new("env")
+                .about("Print evaluated groups, sequences and generator")
+                .arg(arg!(<input> ".fuzl input file")),
+        )
+        .subcommand(
+            Command::new("fmt")
+                .about("Format programs in place")
+                .arg(
+                    arg!(<input> ... ".fuzl input files")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(arg!(--check "Only report files that aren't formatted")),
+        )
+        .subcommand(
+            Command::new("lsp")
+                .about("Run a language server over stdin and stdout"),
+        )
+        .subcommand(
+            Command::new("repl")
+                .about("Explore a program interactively")
+                .arg(arg!(<input> ".fuzl input file"))
+                .arg(
+                    arg!(-x --dict <dict> "Dictionary file (AFL format)")
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    arg!(-s --seed <seed> "Seed of the first input")
+                        .value_parser(value_parser!(u64)),
+                ),
+        )
+        .subcommand(
+            Command::new("gen")
+                .about("Generate inputs (the default command)")
+                .args(generate_args()),
+        )
+        .subcommand(
+            Command::new("enumerate")
+                .about("Generate every distinct input exactly once")
+                .arg(arg!(<input> ".fuzl input file"))
+                .arg(
+                    arg!(-m --max <count> "Stop after this many combinations")
+                        .value_parser(value_parser!(usize))
+                        .default_value("100000"),
+                )
+                .arg(
+                    arg!(-o --"out-dir" <dir> "Write each input to a file")
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("pairwise")
+                .about("Generate inputs covering every N-wise combination")
+                .arg(arg!(<input> ".fuzl input file"))
+                .arg(
+                    arg!(-t --strength <n> "Number of fields combined")
+                        .value_parser(value_parser!(usize))
+                        .default_value("2"),
+                )
+                .arg(
+                    arg!(-o --"out-dir" <dir> "Write each input to a file")
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Feed generated inputs to a target program or server")
+                .arg(arg!(<input> ".fuzl input file"))
+                .arg(
+                    arg!(-x --dict <dict> "Dictionary file (AFL format)")
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    arg!(-n --iterations <count> "Stop after this many inputs")
+                        .value_parser(value_parser!(u64)),
+                )
+                .arg(
+                    arg!(-s --seed <seed> "Seed of the first input")
+                        .value_parser(value_parser!(u64)),
+                )
+                .arg(
+                    arg!(-t --timeout <ms> "Execution timeout in milliseconds")
+                        .value_parser(value_parser!(u64))
+                        .default_value("1000"),
+                )
+                .arg(
+                    arg!(-c --crashes <dir> "Directory for crashing inputs")
+                        .value_parser(value_parser!(PathBuf))
+                        .default_value("crashes"),
+                )
+                .arg(
+                    arg!(-d --deliver <mode> "How inputs are passed to target")
+                        .value_parser(["stdin", "file", "argv"]),
+                )
+                .arg(
+                    arg!(-e --"crash-code" <code> "Exit code meaning a crash")
+                        .value_parser(value_parser!(i32))
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    arg!(--connect <url> "Server to send inputs to")
+                        .long_help(
+                            "Server to send inputs to \
+                            (tcp://host:port or udp://host:port)",
+                        )
+                        .conflicts_with_all(["command", "deliver"]),
+                )
+                .arg(arg!(--reply "Wait for a reply from a server"))
+                .arg(
+                    arg!(--coverage "Keep inputs reaching new coverage")
+                        .long_help(
+                            "Keep inputs reaching new coverage of an \
+                            AFL-instrumented target and mutate them",
+                        )
+                        .conflicts_with("connect"),
+                )
+                .arg(
+                    arg!([command] ... "Target command, @@ replaced by input")
+                        .last(true)
+                        .required_unless_present("connect"),
+                ),
+        )
+        .subcommand(
+            Command::new("minimize")
+                .about("Shrink a crashing input field by field")
+                .arg(arg!(<input> ".fuzl input file"))
+                .arg(
+                    arg!(<crash> "Crashing input")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    arg!(-o --output <file> "Output file [default: CRASH.min]")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    arg!(-n --iterations <count> "Maximum target executions")
+                        .value_parser(value_parser!(u64))
+                        .default_value("10000"),
+                )
+                .arg(
+                    arg!(-t --timeout <ms> "Execution timeout in milliseconds")
+                        .value_parser(value_parser!(u64))
+                        .default_value("1000"),
+                )
+                .arg(
+                    arg!(-d --deliver <mode> "How inputs are passed to target")
+                        .value_parser(["stdin", "file", "argv"]),
+                )
+                .arg(
+                    arg!(-e --"crash-code" <code> "Exit code meaning a crash")
+                        .value_parser(value_parser!(i32))
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    arg!(<command> ... "Target command, @@ replaced by input")
+                        .last(true),
+                ),
+        )
+        .subcommand(
+            Command::new("replay")
+                .about("Feed saved crashes to a target again")
+                .arg(
+                    arg!(<crashes> "Directory of crashing inputs")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    arg!(-x --dict <dict> "Dictionary file (AFL format)")
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    arg!(-t --timeout <ms> "Execution timeout in milliseconds")
+                        .value_parser(value_parser!(u64))
+                        .default_value("1000"),
+                )
+                .arg(
+                    arg!(-d --deliver <mode> "How inputs are passed to target")
+                        .value_parser(["stdin", "file", "argv"]),
+                )
+                .arg(
+                    arg!(-e --"crash-code" <code> "Exit code meaning a crash")
+                        .value_parser(value_parser!(i32))
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    arg!(<command> ... "Target command, @@ replaced by input")
+                        .last(true),
+                ),
+        );
+    let m = cmd.get_matches();
 
-    let path = m.get_one::<String>("input").ok_or("wrong args")?;
+    match m.subcommand() {
+        Some(("check", m)) => run_check(m),
+        Some(("tokens", m)) => run_tokens(m),
+        Some(("ast", m)) => run_ast(m),
+        Some(("env", m)) => run_env(m),
+        Some(("fmt", m)) => run_fmt(m),
+        Some(("lsp", _)) => run_lsp(),
+        Some(("repl", m)) => run_repl(m),
+        Some(("gen", m)) => run_generate(m),
+        Some(("enumerate", m)) => run_enumerate(m),
+        Some(("pairwise", m)) => run_pairwise(m),
+        Some(("run", m)) => run_target(m),
+        Some(("minimize", m)) => run_minimize(m),
+        Some(("replay", m)) => run_replay(m),
+        _ => run_generate(&m),
+    }
+}
+
+/// Arguments of `gen` (also accepted without the subcommand)
+///
+fn generate_args() -> Vec<Arg> {
+    vec![
+        arg!(<input> ".fuzl input file"),
+        arg!(-x --dict <dict> "Dictionary file (AFL format)")
+            .action(ArgAction::Append),
+        arg!(-n --count <count> "Number of inputs")
+            .value_parser(value_parser!(usize))
+            .default_value("1"),
+        arg!(-s --seed <seed> "Seed of the first input")
+            .value_parser(value_parser!(u64)),
+        arg!(-f --format <format> "Output format")
+            .value_parser(FORMATS)
+            .default_value("raw"),
+        arg!(-a --annotate "Print a hex dump with a color of every field")
+            .conflicts_with("format"),
+        arg!(--annotations <file> "Write byte ranges of fields (JSON lines)")
+            .value_parser(value_parser!(PathBuf)),
+        arg!(-w --watch "Generate again whenever the program changes"),
+    ]
+}
+
+/// Lex, parse and evaluate a program from a given file
+///
+fn load_env(path: &str) -> Result<ProgramEnv, String> {
+    evaluate_file(path).map_err(|e| match e {
+        // Already mentions the path
+        BajzelError::Io(_) => e.to_string(),
+        e => format!("{}: {}", path, e),
+    })
+}
+
+/// Lex a program from a given file, passing its tokens to `f`
+///
+fn with_tokens<F>(path: &str, f: F) -> Result<(), String>
+where
+    F: FnOnce(&[Token]) -> Result<(), String>,
+{
     let input = std::fs::read_to_string(path)
-        .map_err(|_e| "Could not read file".to_string())?;
-    let tokens =
-        lex_tokens(input.as_str()).map_err(|_| String::from("Lexer failed"))?;
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let tokens = lex_tokens(input.as_str())
+        .map_err(|_| format!("{}: lexer failed", path))?;
+    f(&tokens)
+}
 
-    // println!("Input: ");
-    // println!("{}\n", input);
+fn run_check(m: &ArgMatches) -> Result<(), String> {
+    let path = m.get_one::<String>("input").ok_or("wrong args")?;
+    load_env(path)?;
+    eprintln!("[*] {}: OK", path);
+    Ok(())
+}
 
-    // println!("Tokens: ");
-    // for token in tokens.iter() {
-    //     println!("- {:?}", token);
-    // }
+fn run_fmt(m: &ArgMatches) -> Result<(), String> {
+    let check = m.get_flag("check");
+    let mut failed = 0;
+    for path in m.get_many::<PathBuf>("input").ok_or("wrong args")? {
+        if let Err(e) = format_file(path, check) {
+            eprintln!("[-] {}: {}", path.display(), e);
+            failed += 1;
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        n if check => Err(format!("{} file(s) not formatted", n)),
+        n => Err(format!("{} file(s) could not be formatted", n)),
+    }
+}
 
-    let tokens = Tokens::new(&tokens);
-    let program = parse_tokens(tokens)?;
+/// Format a file in place, or with `check` only fail when it isn't
+/// formatted already
+///
+fn format_file(path: &Path, check: bool) -> Result<(), String> {
+    let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let formatted = format_source(&source).map_err(|e| e.to_string())?;
+    if formatted == source {
+        return Ok(());
+    }
+    if check {
+        return Err("not formatted".to_owned());
+    }
+    std::fs::write(path, formatted).map_err(|e| e.to_string())?;
+    eprintln!("[*] {}: formatted", path.display());
+    Ok(())
+}
 
-    // for statement in program.clone().into_iter() {
-    //     println!("> {:#?}", statement);
-    // }
+fn run_lsp() -> Result<(), String> {
+    let mut stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout().lock();
+    lsp::serve(&mut stdin, &mut stdout).map_err(|e| e.to_string())
+}
 
-    let env = evaluate_program(program).map_err(|x| match x {
-        bajzel_lib::error::BajzelError::Conversion(x) => {
-            format!("conversion error: {}", x)
+fn run_repl(m: &ArgMatches) -> Result<(), String> {
+    let path = m.get_one::<String>("input").ok_or("wrong args")?;
+    let seed = m.get_one::<u64>("seed").copied().unwrap_or_else(random);
+    let mut repl = Repl::load(path, load_gen(m)?).map_err(|e| match e {
+        BajzelError::Io(_) => e.to_string(),
+        e => format!("{}: {}", path, e),
+    })?;
+    repl.set_seed(seed);
+    eprintln!("[*] {} loaded, seed {}, `help` lists commands", path, seed);
+    let mut stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout().lock();
+    repl.run(&mut stdin, &mut stdout).map_err(write_err)
+}
+
+fn run_tokens(m: &ArgMatches) -> Result<(), String> {
+    let path = m.get_one::<String>("input").ok_or("wrong args")?;
+    with_tokens(path, |tokens| {
+        let mut stdout = std::io::stdout().lock();
+        for token in tokens {
+            writeln!(stdout, "{:?}", token).map_err(write_err)?;
         }
-        bajzel_lib::error::BajzelError::ProgramNotFinished => {
-            "program end not expected yet".to_owned()
+        Ok(())
+    })
+}
+
+fn run_ast(m: &ArgMatches) -> Result<(), String> {
+    let path = m.get_one::<String>("input").ok_or("wrong args")?;
+    with_tokens(path, |tokens| {
+        let program = parse_tokens(Tokens::new(tokens))
+            .map_err(|e| format!("{}: syntax error: {}", path, e))?;
+        let mut stdout = std::io::stdout().lock();
+        for statement in program {
+            writeln!(stdout, "{:#?}", statement).map_err(write_err)?;
         }
-        bajzel_lib::error::BajzelError::Syntax(x) => {
-            format!("syntax error: {}", x)
+        Ok(())
+    })
+}
+
+fn run_env(m: &ArgMatches) -> Result<(), String> {
+    let path = m.get_one::<String>("input").ok_or("wrong args")?;
+    let env = load_env(path)?;
+    write!(std::io::stdout(), "{}", env).map_err(write_err)
+}
+
+fn run_generate(m: &ArgMatches) -> Result<(), String> {
+    let path = m.get_one::<String>("input").ok_or("wrong args")?;
+    let seed = m.get_one::<u64>("seed").copied().unwrap_or_else(random);
+    match m.get_flag("watch") {
+        true => watch_generate(m, path, seed),
+        false => generate_inputs(m, &load_env(path)?, seed),
+    }
+}
+
+/// Generate inputs whenever a program (or a file it depends on) changes,
+/// reporting errors instead of stopping
+///
+/// Every round starts from the same seed, so inputs differ only as much
+/// as the program does. Files are watched before generating, so edits
+/// made meanwhile start another round.
+///
+fn watch_generate(m: &ArgMatches, path: &str, seed: u64) -> Result<(), String> {
+    let mut watcher = Watcher::default();
+    eprintln!("[*] Seed: {}", seed);
+    loop {
+        let result = match load_env(path) {
+            Ok(env) => {
+                watcher.watch(env.sources());
+                generate_inputs(m, &env, seed)
+            }
+            Err(e) => {
+                let mut files: Vec<PathBuf> =
+                    watcher.files().map(Into::into).collect();
+                files.push(PathBuf::from(path));
+                watcher.watch(files);
+                Err(e)
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("[-] {}", e);
         }
-        bajzel_lib::error::BajzelError::Expr(x) => {
-            format!("expression error: {}", x)
+        for path in watcher.wait(WATCH_INTERVAL) {
+            eprintln!("[*] {}: changed", path.display());
         }
-        bajzel_lib::error::BajzelError::NotConstructedProperly => {
-            panic!("program was not constructed properly!");
+    }
+}
+
+fn generate_inputs(
+    m: &ArgMatches,
+    env: &ProgramEnv,
+    seed: u64,
+) -> Result<(), String> {
+    let count = *m.get_one::<usize>("count").ok_or("wrong args")?;
+    let format: Format = m
+        .get_one::<String>("format")
+        .ok_or("wrong args")?
+        .parse()
+        .map_err(|e: BajzelError| e.to_string())?;
+    let annotate = m.get_flag("annotate");
+    let mut sidecar = match m.get_one::<PathBuf>("annotations") {
+        Some(path) => Some(
+            std::fs::File::create(path)
+                .map_err(|e| format!("{}: {}", path.display(), e))?,
+        ),
+        None => None,
+    };
+    let mut gen = load_gen(m)?;
+    let def = env
+        .get_generator()
+        .map_err(|_| "Generator not defined".to_owned())?;
+    let session = env.find_sequence(&def.name).is_some();
+
+    let mut stdout = std::io::stdout().lock();
+    for no in 0..count {
+        // Like in `run`, every input has its own seed and every message
+        // (a group is a session of one) is followed by TERM
+        let seed = seed.wrapping_add(no as u64);
+        gen.set_seed(seed);
+        let (output, annotations) =
+            match (session, annotate || sidecar.is_some()) {
+                (true, true) => gen.generate_input_annotated(env),
+                (false, true) => {
+                    gen.generate_annotated(env).map(|(mut output, x)| {
+                        output.extend_from_slice(&def.term);
+                        (output, x)
+                    })
+                }
+                (_, false) => gen.generate_input(env).map(|x| (x, vec![])),
+            }
+            .map_err(|e| format!("Generate error: {}", e))?;
+        if let Some(sidecar) = &mut sidecar {
+            write_annotations(sidecar, &annotations, Some(seed))
+                .map_err(write_err)?;
         }
-    })?;
+        match annotate {
+            true => write_annotated_hexdump(&mut stdout, &output, &annotations),
+            false => write_sample(&mut stdout, format, no, &output, Some(seed)),
+        }
+        .map_err(write_err)?;
+    }
+    stdout.flush().map_err(write_err)
+}
+
+fn write_err(e: std::io::Error) -> String {
+    format!("Could not write output: {}", e)
+}
+
+/// Create generator using dictionaries given by `-x`
+///
+fn load_gen(m: &ArgMatches) -> Result<Gen, String> {
+    let mut gen = Gen::default();
+    for dict_path in m.get_many::<String>("dict").unwrap_or_default() {
+        let dict = Dictionary::load(dict_path)
+            .map_err(|e| format!("Could not load dictionary: {}", e))?;
+        gen.add_dictionary(dict);
+    }
+    Ok(gen)
+}
+
+/// Paths of dictionaries given by `-x`
+///
+fn dict_paths(m: &ArgMatches) -> Vec<PathBuf> {
+    m.get_many::<String>("dict")
+        .unwrap_or_default()
+        .map(PathBuf::from)
+        .collect()
+}
+
+fn run_enumerate(m: &ArgMatches) -> Result<(), String> {
+    let path = m.get_one::<String>("input").ok_or("wrong args")?;
+    let max = *m.get_one::<usize>("max").ok_or("wrong args")?;
+    let out_dir = m.get_one::<PathBuf>("out-dir");
+    let env = load_env(path)?;
+    let term = env
+        .get_generator()
+        .map_err(|_| "Generator not defined".to_owned())?
+        .term
+        .clone();
+    let mut inputs = enumerate(&env)
+        .map_err(|e| format!("Enumerate error: {}", e))?
+        .with_limit(max as u128);
+
+    match inputs.combination_count() {
+        Some(size) => eprintln!("[*] Combinations to walk through: {}", size),
+        None => eprintln!("[*] Combinations to walk through: more than 2^128"),
+    }
+    write_inputs(inputs.by_ref(), &term, out_dir)?;
+    if !inputs.is_done() {
+        eprintln!("[!] Stopped after {} combinations", max);
+    }
+    eprintln!(
+        "[*] Inputs: {} (skipped {} duplicates or not fitting OUT_MIN/OUT_MAX)",
+        inputs.walked() - inputs.skipped(),
+        inputs.skipped()
+    );
+    Ok(())
+}
+
+fn run_pairwise(m: &ArgMatches) -> Result<(), String> {
+    let path = m.get_one::<String>("input").ok_or("wrong args")?;
+    let strength = *m.get_one::<usize>("strength").ok_or("wrong args")?;
+    let out_dir = m.get_one::<PathBuf>("out-dir");
+    let env = load_env(path)?;
+    let term = env
+        .get_generator()
+        .map_err(|_| "Generator not defined".to_owned())?
+        .term
+        .clone();
+    let cases = covering_array(&env, strength)
+        .map_err(|e| format!("Covering array error: {}", e))?;
 
-    // println!("Evaluated program:\n");
-    // for (name, group) in &env.groups {
-    //     println!(">>> Group: {}", name);
-    //     println!("    Fields:");
-    //     for field in &group.fields {
-    //         println!("        - {:?}: {:?}", field.alias, field.def);
-    //     }
-    //     println!();
-    // }
-
-    // {
-    //     let gen = env.gen.as_ref().expect("Generator should be defined");
-    //     println!(">>> Generator: {}", gen.name);
-    //     println!("    OUT  = {} to {}", gen.out_min, gen.out_max);
-    //     println!("    TERM = {:?}", gen.term);
-    // }
-
-    let gen = Gen::default();
-    let output = gen
-        .generate(&env)
-        .map_err(|_| "Generate error".to_owned())?;
-    let _ = std::io::stdout().write_all(&output);
+    eprintln!("[*] Test cases: {}", cases.len());
+    write_inputs(cases.inputs(), &term, out_dir)
+}
+
+fn run_target(m: &ArgMatches) -> Result<(), String> {
+    let path = m.get_one::<String>("input").ok_or("wrong args")?;
+    let iterations = m.get_one::<u64>("iterations").copied();
+    let seed = m.get_one::<u64>("seed").copied().unwrap_or_else(random);
+    let timeout = *m.get_one::<u64>("timeout").ok_or("wrong args")?;
+    let crashes = m.get_one::<PathBuf>("crashes").ok_or("wrong args")?;
+    let env = load_env(path)?;
+    let gen = load_gen(m)?;
+
+    let input_file = std::env::temp_dir()
+        .join(format!("bajzel-{}.input", std::process::id()));
+    let target: Box<dyn Executor> = match m.get_one::<String>("connect") {
+        Some(url) => {
+            let mut target = NetTarget::from_url(url)
+                .map_err(|e| format!("Invalid endpoint: {}", e))?;
+            target.set_timeout(Duration::from_millis(timeout));
+            target.set_read_reply(m.get_flag("reply"));
+            Box::new(target)
+        }
+        None => {
+            let mut target = process_target(m, &input_file)?;
+            if m.get_flag("coverage") {
+                target
+                    .enable_coverage()
+                    .map_err(|e| format!("Could not set up coverage: {}", e))?;
+            }
+            Box::new(target)
+        }
+    };
+    let store = CrashStore::open(crashes)
+        .map_err(|e| format!("Could not open crash directory: {}", e))?;
+
+    eprintln!("[*] Seed: {}", seed);
+    let mut runner = Runner::new(gen, target, store, PathBuf::from(path), seed);
+    runner.set_dictionaries(dict_paths(m));
+    if m.get_flag("coverage") {
+        runner.enable_feedback();
+    }
+    let result = loop {
+        if iterations.is_some_and(|x| runner.stats().executions >= x) {
+            break Ok(());
+        }
+        match runner.step(&env) {
+            Ok(Outcome::Crashed(Crash::Refused)) => {
+                eprintln!("[!] Server is down, previous input saved");
+                break Ok(());
+            }
+            Ok(Outcome::Crashed(crash)) => eprintln!(
+                "[!] Crash ({}) at execution {}",
+                crash,
+                runner.stats().executions
+            ),
+            Ok(_) => (),
+            Err(e) => break Err(format!("Runner error: {}", e)),
+        }
+        if runner.stats().executions.is_multiple_of(STATS_INTERVAL) {
+            print_stats(&runner);
+        }
+    };
+    let _ = std::fs::remove_file(input_file);
+    print_stats(&runner);
+    if runner.stats().crashes > 0 {
+        print_summary(crashes);
+    }
+    result
+}
+
+/// Print unique crashes and save the report as `summary.txt` in a crash
+/// directory
+///
+fn print_summary(crashes: &Path) {
+    match triage::summarize(crashes) {
+        Ok(summary) => {
+            eprint!("[*] {}", summary);
+            let _ =
+                std::fs::write(crashes.join(SUMMARY_FILE), summary.to_string());
+        }
+        Err(e) => eprintln!("[-] Could not summarize crashes: {}", e),
+    }
+}
+
+/// Create target program from `command`, `timeout`, `crash-code` and
+/// `deliver` arguments
+///
+fn process_target(m: &ArgMatches, input_file: &Path) -> Result<Target, String> {
+    let timeout = *m.get_one::<u64>("timeout").ok_or("wrong args")?;
+    let command = m
+        .get_many::<String>("command")
+        .ok_or("wrong args")?
+        .collect::<Vec<_>>();
+    let mut target = Target::new(&command).map_err(|e| e.to_string())?;
+    target.set_timeout(Duration::from_millis(timeout));
+    for code in m.get_many::<i32>("crash-code").unwrap_or_default() {
+        target.add_crash_code(*code);
+    }
+    let file = Delivery::File(input_file.to_path_buf());
+    match m.get_one::<String>("deliver").map(String::as_str) {
+        Some("stdin") => target.set_delivery(Delivery::Stdin),
+        Some("argv") => target.set_delivery(Delivery::Argv),
+        Some("file") => target.set_delivery(file),
+        _ if target.has_placeholder() => target.set_delivery(file),
+        _ => target.set_delivery(Delivery::Stdin),
+    }
+    Ok(target)
+}
+
+fn run_minimize(m: &ArgMatches) -> Result<(), String> {
+    let path = m.get_one::<String>("input").ok_or("wrong args")?;
+    let crash = m.get_one::<PathBuf>("crash").ok_or("wrong args")?;
+    let max = *m.get_one::<u64>("iterations").ok_or("wrong args")?;
+    let output = match m.get_one::<PathBuf>("output") {
+        Some(output) => output.clone(),
+        None => PathBuf::from(format!("{}.min", crash.display())),
+    };
+    let env = load_env(path)?;
+    let input = std::fs::read(crash)
+        .map_err(|e| format!("{}: {}", crash.display(), e))?;
+
+    let input_file = std::env::temp_dir()
+        .join(format!("bajzel-{}.input", std::process::id()));
+    let mut target = process_target(m, &input_file)?;
+    let result = minimize(&env, &mut target, &input, max);
+    let _ = std::fs::remove_file(input_file);
+    let minimized = match result {
+        Ok(Some(minimized)) => minimized,
+        Ok(None) => return Err("Input doesn't crash the target".to_owned()),
+        Err(e) => return Err(format!("Minimize error: {}", e)),
+    };
+    std::fs::write(&output, &minimized.input)
+        .map_err(|e| format!("{}: {}", output.display(), e))?;
+    eprintln!(
+        "[*] {} -> {} bytes in {} executions, saved to {}",
+        input.len(),
+        minimized.input.len(),
+        minimized.executions,
+        output.display()
+    );
+    Ok(())
+}
+
+fn run_replay(m: &ArgMatches) -> Result<(), String> {
+    let crashes = m.get_one::<PathBuf>("crashes").ok_or("wrong args")?;
+    let mut gen = load_gen(m)?;
+
+    let input_file = std::env::temp_dir()
+        .join(format!("bajzel-{}.input", std::process::id()));
+    let mut target = process_target(m, &input_file)?;
+    let result = replay(crashes, &mut target, &mut gen, &dict_paths(m));
+    let _ = std::fs::remove_file(input_file);
+    let report = result.map_err(|e| format!("Replay error: {}", e))?;
+    print!("{}", report);
+    match report.has_regressions() {
+        true => Err("Some crashes are not fixed".to_owned()),
+        false => Ok(()),
+    }
+}
+
+fn print_stats(runner: &Runner) {
+    let stats = runner.stats();
+    eprintln!(
+        "[*] Executions: {}, crashes: {}, timeouts: {}",
+        stats.executions, stats.crashes, stats.timeouts
+    );
+    if stats.edges > 0 {
+        eprintln!("[*] Corpus: {}, edges: {}", stats.corpus, stats.edges);
+    }
+}
+
+/// Write inputs followed by a terminator to stdout or to separate files
+/// in a given directory
+///
+fn write_inputs<I>(
+    inputs: I,
+    term: &[u8],
+    out_dir: Option<&PathBuf>,
+) -> Result<(), String>
+where
+    I: Iterator<Item = Vec<u8>>,
+{
+    if let Some(dir) = out_dir {
+        std::fs::create_dir_all(dir)
+            .map_err(|_| "Could not create output directory".to_owned())?;
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    for (no, mut input) in inputs.enumerate() {
+        input.extend_from_slice(term);
+        match out_dir {
+            Some(dir) => std::fs::write(dir.join(format!("{:06}", no)), input)
+                .map_err(|_| "Could not write output file".to_owned())?,
+            None => stdout
+                .write_all(&input)
+                .map_err(|_| "Could not write output".to_owned())?,
+        }
+    }
     Ok(())
 }
 
 fn main() {
     if let Err(e) = run() {
         eprintln!("[-] {}", e);
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,133 @@
+use bajzel_lib::afl::{
+    afl_custom_deinit, afl_custom_fuzz, afl_custom_init, Mutator, FUZL_ENV_VAR,
+};
+use pretty_assertions::assert_eq;
+use std::path::PathBuf;
+
+fn write_fuzl(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "bajzel-test-{}-{}.fuzl",
+        name,
+        std::process::id()
+    ));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+#[test]
+fn inputs_follow_definition() {
+    let path = write_fuzl(
+        "afl-inputs",
+        r#"
+        DEFINE cmd
+            "CMD " u8 AS id -> RANGE(1 9),
+        GENERATE cmd
+            TERM = LF
+        "#,
+    );
+    let mut mutator = Mutator::load(&path, 1).unwrap();
+    let mut input = b"seed".to_vec();
+    for _ in 0..10 {
+        input = mutator.mutate(&input, None, 64).unwrap().to_vec();
+        assert!(input.starts_with(b"CMD "), "{:?}", input);
+        assert!(input.ends_with(b"\n"), "{:?}", input);
+    }
+    assert_eq!(mutator.mutate(&input, None, 3).unwrap(), b"CMD");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn same_seed_same_inputs() {
+    let path = write_fuzl(
+        "afl-seed",
+        r#"
+        DEFINE cmd
+            string AS name
+        GENERATE cmd
+        "#,
+    );
+    let mut first = Mutator::load(&path, 42).unwrap();
+    let mut second = Mutator::load(&path, 42).unwrap();
+    let mut input = vec![];
+    for _ in 0..10 {
+        let expected = first.mutate(&input, None, 256).unwrap().to_vec();
+        assert_eq!(second.mutate(&input, None, 256).unwrap(), expected);
+        input = expected;
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn single_field_mutated() {
+    let path = write_fuzl(
+        "afl-fields",
+        r#"
+        DEFINE cmd
+            "A=" u8 AS a -> RANGE(0 99),
+            " B=" u8 AS b -> RANGE(0 99),
+        GENERATE cmd WITH
+            TERM = LF
+        "#,
+    );
+    let mut mutator = Mutator::load(&path, 3).unwrap();
+    for _ in 0..50 {
+        let input = mutator.mutate(b"A=42 B=77\n", None, 64).unwrap();
+        let input = String::from_utf8(input.to_vec()).unwrap();
+        assert!(
+            input.starts_with("A=42 B=") || input.ends_with(" B=77\n"),
+            "{:?}",
+            input
+        );
+    }
+
+    let mut first = Mutator::load(&path, 3).unwrap();
+    let mut second = Mutator::load(&path, 3).unwrap();
+    let spliced = Some(&b"A=1 B=2\n"[..]);
+    let outputs = (0..20)
+        .filter(|_| {
+            let x = first.mutate(b"A=11 B=22\n", spliced, 64).unwrap();
+            let y = second.mutate(b"A=33 B=44\n", spliced, 64).unwrap();
+            x != y
+        })
+        .count();
+    assert_eq!(outputs, 20);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn invalid_file() {
+    let path = write_fuzl("afl-invalid", "DEFINE cmd u8 AS id -> LEN(1)");
+    assert!(Mutator::load(&path, 0).is_err());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn c_api() {
+    let path = write_fuzl(
+        "afl-c-api",
+        r#"
+        DEFINE cmd
+            "PING"
+        GENERATE cmd
+        "#,
+    );
+    std::env::set_var(FUZL_ENV_VAR, &path);
+    unsafe {
+        let data = afl_custom_init(std::ptr::null_mut(), 7);
+        assert!(!data.is_null());
+        let mut buf = *b"seed";
+        let mut out = std::ptr::null();
+        let len = afl_custom_fuzz(
+            data,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut out,
+            std::ptr::null_mut(),
+            0,
+            1024,
+        );
+        assert_eq!(std::slice::from_raw_parts(out, len), b"PING");
+        afl_custom_deinit(data);
+    }
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,168 @@
+use crate::evaluate_str;
+use bajzel_lib::covering::covering_array;
+use itertools::Itertools;
+use pretty_assertions::assert_eq;
+
+/// Parse inputs in a form of `1,2,3` into vectors of numbers
+///
+fn parse_rows(inputs: impl Iterator<Item = Vec<u8>>) -> Vec<Vec<u8>> {
+    inputs
+        .map(|input| {
+            String::from_utf8(input)
+                .unwrap()
+                .split(',')
+                .map(|x| x.parse().unwrap())
+                .collect()
+        })
+        .collect()
+}
+
+/// Make sure every combination of `strength` values of given fields shows
+/// up in at least one row
+///
+fn assert_covered(rows: &[Vec<u8>], values: &[u8], strength: usize) {
+    let fields = rows[0].len();
+    for params in (0..fields).combinations(strength) {
+        for choices in (0..strength).map(|_| values).multi_cartesian_product() {
+            assert!(
+                rows.iter().any(|row| params
+                    .iter()
+                    .zip(&choices)
+                    .all(|(p, v)| row[*p] == **v)),
+                "{:?} = {:?} not covered",
+                params,
+                choices
+            );
+        }
+    }
+}
+
+#[test]
+fn pairwise_over_nested_groups() {
+    let env = evaluate_str(
+        r#"
+        DEFINE inner
+            u8 AS c -> WEIGHTS(1 1 2 1 3 1),
+            ","
+            u8 AS d -> WEIGHTS(1 1 2 1 3 1),
+        DEFINE cmd
+            u8 AS a -> WEIGHTS(1 1 2 1 3 1),
+            ","
+            u8 AS b -> WEIGHTS(1 1 2 1 3 1),
+            ","
+            ref AS i FROM inner
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let cases = covering_array(&env, 2).unwrap();
+    let rows = parse_rows(cases.inputs());
+    assert_eq!(rows.len(), cases.len());
+    assert!(rows.len() < 3 * 3 * 3 * 3);
+    assert_eq!(rows[0].len(), 4);
+    assert_covered(&rows, &[1, 2, 3], 2);
+}
+
+#[test]
+fn three_way_coverage() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u8 AS a -> WEIGHTS(1 1 2 1),
+            ","
+            u8 AS b -> WEIGHTS(1 1 2 1),
+            ","
+            u8 AS c -> WEIGHTS(1 1 2 1),
+            ","
+            u8 AS d -> WEIGHTS(1 1 2 1),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let cases = covering_array(&env, 3).unwrap();
+    let rows = parse_rows(cases.inputs());
+    assert_covered(&rows, &[1, 2], 3);
+}
+
+#[test]
+fn number_boundary_classes() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            i32 AS a -> RANGE(-10 10),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let cases = covering_array(&env, 2).unwrap();
+    let values = parse_rows_i32(cases.inputs());
+    assert_eq!(values, vec![-10, -9, -1, 0, 1, 9, 10]);
+}
+
+fn parse_rows_i32(inputs: impl Iterator<Item = Vec<u8>>) -> Vec<i32> {
+    inputs
+        .map(|input| String::from_utf8(input).unwrap().parse().unwrap())
+        .sorted()
+        .collect()
+}
+
+#[test]
+fn longer_than_out_max() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u8 AS a -> WEIGHTS(1 1 22 1),
+            ","
+            u8 AS b -> WEIGHTS(1 1 2 1),
+        GENERATE cmd WITH
+            OUT_MAX = 3
+        "#,
+    )
+    .unwrap();
+    let err = covering_array(&env, 2).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "syntax error: test case of 4 bytes is longer than OUT_MAX (3)"
+    );
+}
+
+#[test]
+fn padded_to_out_min() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u8 AS a -> WEIGHTS(1 1 2 1),
+            ","
+            u8 AS b -> WEIGHTS(1 1 2 1),
+        GENERATE cmd WITH
+            OUT_MIN = 5
+        "#,
+    )
+    .unwrap();
+    let cases = covering_array(&env, 2).unwrap();
+    let inputs = cases.inputs().sorted().collect_vec();
+    assert_eq!(
+        inputs,
+        [b"1,1\0\0", b"1,2\0\0", b"2,1\0\0", b"2,2\0\0"].map(|x| x.to_vec())
+    );
+}
+
+#[test]
+fn sequences_rejected() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u8 AS a -> WEIGHTS(1 1 2 1),
+        SEQUENCE session
+            cmd -> REPEAT(1 3),
+        GENERATE session
+        "#,
+    )
+    .unwrap();
+    let err = covering_array(&env, 2).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "syntax error: sequence session can't be covered, \
+         GENERATE a group instead"
+    );
+}
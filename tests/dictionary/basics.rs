@@ -0,0 +1,39 @@
+use bajzel_lib::dictionary::Dictionary;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn entries_with_and_without_keywords() {
+    let input = r#"
+        # Comment line
+        kw_get="GET"
+        kw_level@1="POST"
+
+        "HEAD"
+        kw_spaced = "PUT"
+    "#;
+    let dict = Dictionary::parse(input).unwrap();
+    let expected: Vec<Vec<u8>> = vec![
+        b"GET".to_vec(),
+        b"POST".to_vec(),
+        b"HEAD".to_vec(),
+        b"PUT".to_vec(),
+    ];
+    assert_eq!(dict.entries(), expected.as_slice());
+}
+
+#[test]
+fn escaped_bytes() {
+    let input = "crlf=\"\\x0d\\x0A\"\nquoted=\"\\\"\\\\\\xff\"";
+    let dict = Dictionary::parse(input).unwrap();
+    let expected: Vec<Vec<u8>> =
+        vec![vec![0x0d, 0x0a], vec![b'"', b'\\', 0xff]];
+    assert_eq!(dict.entries(), expected.as_slice());
+}
+
+#[test]
+fn malformed_entries() {
+    assert!(Dictionary::parse("kw=GET").is_err());
+    assert!(Dictionary::parse("\"unterminated").is_err());
+    assert!(Dictionary::parse("\"\\x4\"").is_err());
+    assert!(Dictionary::parse("\"\\n\"").is_err());
+}
@@ -0,0 +1,137 @@
+use crate::evaluate_str;
+use bajzel_lib::enumerator::enumerate;
+use itertools::Itertools;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn cartesian_product() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u8 AS a -> RANGE(1 2),
+            ":"
+            le_u16 AS b -> WEIGHTS(5 1 7 1 9 0),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let inputs = enumerate(&env).unwrap();
+    assert_eq!(inputs.combination_count(), Some(4));
+
+    let expected: Vec<Vec<u8>> = vec![
+        vec![b'1', b':', 5, 0],
+        vec![b'1', b':', 7, 0],
+        vec![b'2', b':', 5, 0],
+        vec![b'2', b':', 7, 0],
+    ];
+    assert_eq!(inputs.collect_vec(), expected);
+}
+
+#[test]
+fn strings_of_all_lengths() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            string AS name -> LEN(0 2),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let inputs = enumerate(&env).unwrap();
+    assert_eq!(inputs.combination_count(), Some(1 + 62 + 62 * 62));
+
+    let inputs = inputs.collect_vec();
+    assert_eq!(inputs.len(), 1 + 62 + 62 * 62);
+    assert_eq!(inputs.iter().unique().count(), inputs.len());
+    assert_eq!(inputs[0], b"".to_vec());
+    assert_eq!(inputs[1], b"0".to_vec());
+    assert_eq!(inputs[63], b"00".to_vec());
+}
+
+#[test]
+fn output_length_limits() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            bytes AS payload -> LEN(0 2),
+        GENERATE cmd WITH
+            OUT_MIN = 1
+            OUT_MAX = 1
+        "#,
+    )
+    .unwrap();
+    let inputs = enumerate(&env).unwrap();
+    assert_eq!(inputs.count(), 256);
+}
+
+#[test]
+fn huge_domains() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            bytes AS payload
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let inputs = enumerate(&env).unwrap();
+    assert_eq!(inputs.combination_count(), None);
+    assert_eq!(inputs.take(2).collect_vec(), vec![vec![], vec![0]]);
+}
+
+#[test]
+fn duplicates_skipped() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            string AS a -> LEN(0 1),
+            string AS b -> LEN(0 1),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let mut inputs = enumerate(&env).unwrap();
+    assert_eq!(inputs.combination_count(), Some(63 * 63));
+
+    let outputs = inputs.by_ref().collect_vec();
+    assert_eq!(outputs.len(), 1 + 62 + 62 * 62);
+    assert_eq!(outputs.iter().unique().count(), outputs.len());
+    assert_eq!(inputs.skipped(), 62);
+}
+
+#[test]
+fn limit_counts_skipped() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            bytes AS payload -> LEN(0 3),
+        GENERATE cmd WITH
+            OUT_MIN = 3
+        "#,
+    )
+    .unwrap();
+    let mut inputs = enumerate(&env).unwrap().with_limit(100);
+    assert_eq!(inputs.next(), None);
+    assert_eq!(inputs.walked(), 100);
+    assert!(!inputs.is_done());
+}
+
+#[test]
+fn sequences_rejected() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u8 AS a -> WEIGHTS(1 1 2 1),
+        SEQUENCE session
+            cmd -> REPEAT(1 3),
+        GENERATE session
+        "#,
+    )
+    .unwrap();
+    let err = enumerate(&env).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "syntax error: sequence session can't be enumerated, \
+         GENERATE a group instead"
+    );
+}
@@ -0,0 +1,140 @@
+use crate::evaluate_str;
+use crate::runner::basics::temp_dir;
+use bajzel_lib::{
+    error::BajzelError, evaluator::evaluate_source_in, generator::Gen,
+};
+use pretty_assertions::assert_eq;
+use std::fs;
+
+#[test]
+fn constants_in_attributes() {
+    let env = evaluate_str(
+        r#"
+        CONST HEADER_SIZE = 40
+        CONST SIZE = HEADER_SIZE
+        DEFINE header
+            u8 AS size -> RANGE(SIZE HEADER_SIZE),
+            ":"
+            string AS name -> LEN(SHORT SHORT),
+        CONST SHORT = 3
+        GENERATE header WITH
+            OUT_MAX = HEADER_SIZE
+        "#,
+    );
+    // Constants have to be declared before they're used
+    assert!(env.is_err());
+
+    let env = evaluate_str(
+        r#"
+        CONST HEADER_SIZE = 40
+        CONST SIZE = HEADER_SIZE
+        CONST SHORT = 3
+        DEFINE header
+            u8 AS size -> RANGE(SIZE HEADER_SIZE),
+            ":"
+            string AS name -> LEN(SHORT SHORT),
+        GENERATE header WITH
+            OUT_MAX = HEADER_SIZE
+        "#,
+    )
+    .unwrap();
+    assert_eq!(env.get_generator().unwrap().out_max, 40);
+    let output = Gen::default().generate(&env).unwrap();
+    assert_eq!(&output[..3], b"40:");
+    assert_eq!(output.len(), 6);
+}
+
+#[test]
+fn enum_members() {
+    let env = evaluate_str(
+        r#"
+        ENUM compression {
+            BI_RGB  = 0,
+            BI_RLE8 = 1,
+            BI_RLE4 = 2,
+        }
+        DEFINE info_header
+            le_u32 AS compression -> ENUM(compression),
+            u8     AS rle         -> WEIGHTS(BI_RLE8 1),
+        GENERATE info_header
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        env.find_enum("compression").unwrap(),
+        [
+            ("BI_RGB".to_owned(), 0),
+            ("BI_RLE8".to_owned(), 1),
+            ("BI_RLE4".to_owned(), 2)
+        ]
+    );
+    let output = Gen::default().generate(&env).unwrap();
+    assert_eq!(output.len(), 5);
+    assert_eq!(output[4], b'1');
+}
+
+#[test]
+fn declaration_errors() {
+    let err = |source: &str| match evaluate_str(source) {
+        Err(BajzelError::Syntax(msg)) => msg,
+        x => panic!("unexpected result: {:?}", x),
+    };
+    let program = |declarations: &str, attrs: &str| {
+        format!(
+            "{}\nDEFINE a\n    u8 AS x -> {},\nGENERATE a\n",
+            declarations, attrs
+        )
+    };
+    assert_eq!(
+        err(&program("CONST A = 1\nCONST A = 2", "RANGE(A A)")),
+        "constant A already defined"
+    );
+    assert_eq!(
+        err(&program("CONST A = 1\nENUM e { A = 1 }", "ENUM(e)")),
+        "constant A already defined"
+    );
+    assert_eq!(
+        err(&program("ENUM e { A = 1 }\nENUM e { B = 2 }", "ENUM(e)")),
+        "ENUM e already defined"
+    );
+    assert_eq!(
+        err(&program("CONST A = B", "RANGE(0 1)")),
+        "CONST A: unknown constant (B)"
+    );
+    assert_eq!(
+        err(&program("ENUM e { A = \"a\" }", "ENUM(e)")),
+        "ENUM e: A is not an integer"
+    );
+    assert_eq!(
+        err(&program("", "ENUM(e)")),
+        "ENUM: unknown enumeration (e)"
+    );
+    assert_eq!(
+        err(&program("ENUM e { A = 1, B = 256 }", "ENUM(e)")),
+        "ENUM: value out of bounds (256)"
+    );
+}
+
+#[test]
+fn imported_constants() {
+    let dir = temp_dir("constants-imported");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("bmp.fuzl"),
+        "CONST HEADER_SIZE = 40\nENUM compression { BI_RGB = 0 }\n\
+         DEFINE x\n    \"x\"\n",
+    )
+    .unwrap();
+    let env = evaluate_source_in(
+        "IMPORT \"bmp.fuzl\"\nDEFINE a\n    \
+         u8 AS size -> RANGE(bmp::HEADER_SIZE bmp::HEADER_SIZE),\n    \
+         u8 AS kind -> WEIGHTS(bmp::BI_RGB 1),\nGENERATE a\n",
+        &dir,
+    )
+    .unwrap();
+    assert!(env.find_enum("bmp::compression").is_some());
+    assert!(env.find_enum("compression").is_none());
+    let output = Gen::default().generate(&env).unwrap();
+    assert_eq!(output, b"400".to_vec());
+    fs::remove_dir_all(&dir).unwrap();
+}
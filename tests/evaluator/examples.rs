@@ -0,0 +1,42 @@
+use bajzel_lib::{
+    error::BajzelError,
+    evaluator::{evaluate_file, evaluate_program_in},
+    generator::Gen,
+    lexer::{lex_tokens, Tokens},
+    parser::parse_tokens,
+};
+use std::fs::read_to_string;
+
+#[test]
+fn example3_dict_relative_to_program() {
+    let input = read_to_string("./examples/example3.fuzl").unwrap();
+    let tokens = lex_tokens(input.as_str()).unwrap();
+    let program = parse_tokens(Tokens::new(&tokens)).unwrap();
+    let env = evaluate_program_in(program, "./examples").unwrap();
+
+    let output = Gen::default().generate(&env).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let method = output.split(' ').next().unwrap();
+    assert!(["GET", "POST", "HEAD", "OPTIONS"].contains(&method));
+}
+
+#[test]
+fn example0_summary() {
+    let env = evaluate_file("./examples/example0.fuzl").unwrap();
+    let summary = env.to_string();
+    assert!(summary.starts_with("group string_cmd\n    cmd: AsciiString("));
+    assert!(summary.contains("\n    _: ConstString(\" \")\n"));
+    assert!(summary.ends_with(
+        "generate string_cmd\n    OUT  = 5 to 32\n    TERM = \"\\n\"\n"
+    ));
+}
+
+#[test]
+fn errors_displayed() {
+    let e = evaluate_file("./examples/missing.fuzl").unwrap_err();
+    assert!(e
+        .to_string()
+        .starts_with("io error: ./examples/missing.fuzl"));
+    let e = BajzelError::Syntax("DICT(path): no entries".to_owned());
+    assert_eq!(e.to_string(), "syntax error: DICT(path): no entries");
+}
@@ -0,0 +1,171 @@
+use crate::runner::basics::temp_dir;
+use bajzel_lib::{
+    error::BajzelError,
+    evaluator::{evaluate_file, evaluate_source_in},
+    generator::Gen,
+};
+use pretty_assertions::assert_eq;
+use std::fs;
+use std::path::PathBuf;
+
+/// Write files of given names and contents into a new directory
+///
+fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = temp_dir(name);
+    for (path, source) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+    dir
+}
+
+const TLV: &str = r#"
+DEFINE record
+    u8 AS tag
+    ":"
+    ref AS value FROM text
+WHERE
+    tag -> RANGE(7 7),
+
+DEFINE text
+    "abc"
+"#;
+
+#[test]
+fn namespaced_groups() {
+    let dir = write_files(
+        "imports",
+        &[
+            ("common/tlv.fuzl", TLV),
+            (
+                "main.fuzl",
+                r#"
+                IMPORT "common/tlv.fuzl"
+                DEFINE msg
+                    "MSG "
+                    ref AS body FROM tlv::record
+                GENERATE msg
+                "#,
+            ),
+        ],
+    );
+    let env = evaluate_file(dir.join("main.fuzl")).unwrap();
+    assert!(env.get_group(&"tlv::record").is_ok());
+    assert!(env.get_group(&"tlv::text").is_ok());
+    assert!(env.get_group(&"record").is_err());
+    let output = Gen::default().generate(&env).unwrap();
+    assert_eq!(output, b"MSG 7:abc".to_vec());
+    assert_eq!(
+        env.sources(),
+        [dir.join("main.fuzl"), dir.join("common/tlv.fuzl")]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn imports_only() {
+    let dir = write_files("imports-only", &[("tlv.fuzl", TLV)]);
+    let env =
+        evaluate_source_in("IMPORT \"tlv.fuzl\"\nGENERATE tlv::record\n", &dir)
+            .unwrap();
+    let output = Gen::default().generate(&env).unwrap();
+    assert_eq!(output, b"7:abc".to_vec());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn template_targets() {
+    let dir = write_files(
+        "imports-template-targets",
+        &[(
+            "lib.fuzl",
+            r#"
+            DEFINE inner
+                "x"
+            DEFINE box(T)
+                ref AS body
+                T   AS value -> RANGE(1 1),
+            WHERE
+                body -> TO(inner),
+            "#,
+        )],
+    );
+    let env = evaluate_source_in(
+        r#"
+        IMPORT "lib.fuzl"
+        DEFINE a
+            ref FROM lib::box(u8)
+        GENERATE a
+        "#,
+        &dir,
+    )
+    .unwrap();
+    let output = Gen::default().generate(&env).unwrap();
+    assert_eq!(output, b"x1".to_vec());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn nested_imports() {
+    let dir = write_files(
+        "imports-nested",
+        &[
+            ("tlv.fuzl", TLV),
+            (
+                "frame.fuzl",
+                "IMPORT \"tlv.fuzl\"\nSEQUENCE frames\n    tlv::record\n",
+            ),
+        ],
+    );
+    let env = evaluate_source_in(
+        "IMPORT \"frame.fuzl\"\nGENERATE frame::frames\n",
+        &dir,
+    )
+    .unwrap();
+    assert!(env.get_group(&"frame::tlv::record").is_ok());
+    assert!(env.find_sequence(&"frame::frames").is_some());
+    let output = Gen::default().generate_input(&env).unwrap();
+    assert_eq!(output, b"7:abc".to_vec());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn import_cycle() {
+    let dir = write_files(
+        "imports-cycle",
+        &[
+            ("a.fuzl", "IMPORT \"b.fuzl\"\nDEFINE x\n    \"x\"\n"),
+            ("b.fuzl", "IMPORT \"a.fuzl\"\nDEFINE y\n    \"y\"\n"),
+        ],
+    );
+    let e = evaluate_file(dir.join("a.fuzl")).unwrap_err();
+    assert_eq!(
+        e,
+        BajzelError::Syntax(
+            "IMPORT: cycle (a.fuzl -> b.fuzl -> a.fuzl)".to_owned()
+        )
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn import_errors() {
+    let dir = write_files("imports-errors", &[("tlv.fuzl", TLV)]);
+    let evaluate = |source: &str| evaluate_source_in(source, &dir);
+    assert!(matches!(
+        evaluate("IMPORT \"missing.fuzl\"\nGENERATE missing::x\n"),
+        Err(BajzelError::Io(_))
+    ));
+    assert_eq!(
+        evaluate("DEFINE a\n    \"a\"\nIMPORT \"tlv.fuzl\"\nGENERATE a\n")
+            .unwrap_err(),
+        BajzelError::Syntax("IMPORT must precede definitions".to_owned())
+    );
+    let e = evaluate("IMPORT \"tlv.fuzl\"\nIMPORT \"tlv.fuzl\"\nGENERATE a\n");
+    assert!(matches!(
+        e,
+        Err(BajzelError::Syntax(msg)) if msg.starts_with("IMPORT: group tlv::")
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}
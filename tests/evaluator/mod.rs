@@ -1 +1,6 @@
-
+pub mod constants;
+pub mod examples;
+pub mod imports;
+pub mod references;
+pub mod sequences;
+pub mod templates;
@@ -0,0 +1,49 @@
+use crate::evaluate_str;
+use bajzel_lib::{error::BajzelError, generator::Gen};
+use pretty_assertions::assert_eq;
+
+#[test]
+fn nested_groups_generated_in_place() {
+    let env = evaluate_str(
+        r#"
+        DEFINE pair
+            "(" 1 "," 2 ")"
+        DEFINE cmd
+            "PAIR"
+            ref AS p FROM pair
+            ref AS q
+        WHERE
+            q -> TO(pair),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let output = Gen::default().generate(&env).unwrap();
+    assert_eq!(output, b"PAIR(1,2)(1,2)".to_vec());
+}
+
+#[test]
+fn unknown_group() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            ref AS p FROM missing
+        GENERATE cmd
+        "#,
+    );
+    assert!(matches!(env, Err(BajzelError::Syntax(_))));
+}
+
+#[test]
+fn recursive_groups() {
+    let env = evaluate_str(
+        r#"
+        DEFINE a
+            ref FROM b
+        DEFINE b
+            ref FROM a
+        GENERATE a
+        "#,
+    );
+    assert!(matches!(env, Err(BajzelError::Syntax(_))));
+}
@@ -0,0 +1,196 @@
+use crate::evaluate_str;
+use bajzel_lib::error::BajzelError;
+
+#[test]
+fn steps_defined() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        DEFINE command
+            "CMD"
+        SEQUENCE session
+            login -> MUTATE(0),
+            command -> REPEAT(1 5) NEXT(command END),
+        GENERATE session
+        "#,
+    )
+    .unwrap();
+    let sequence = env.find_sequence(&"session").unwrap();
+    assert_eq!(sequence.steps.len(), 2);
+    assert!(!sequence.steps[0].mutate);
+    assert_eq!(sequence.steps[1].repeat_min, 1);
+    assert_eq!(sequence.steps[1].repeat_max, 5);
+    assert_eq!(
+        sequence.steps[1].next,
+        Some(vec!["command".to_owned(), "END".to_owned()])
+    );
+}
+
+#[test]
+fn unknown_group() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        SEQUENCE session
+            login
+            missing
+        GENERATE session
+        "#,
+    );
+    assert!(matches!(env, Err(BajzelError::Syntax(_))));
+}
+
+#[test]
+fn unknown_next_step() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        DEFINE command
+            "CMD"
+        SEQUENCE session
+            login -> NEXT(command),
+        GENERATE session
+        "#,
+    );
+    assert!(matches!(env, Err(BajzelError::Syntax(_))));
+}
+
+#[test]
+fn ambiguous_next_step() {
+    let source = |step: &str| {
+        format!(
+            r#"
+            DEFINE login
+                "LOGIN"
+            SEQUENCE session
+                login -> NEXT(login),
+                {} -> NEXT(END),
+            GENERATE session
+            "#,
+            step
+        )
+    };
+    let err = evaluate_str(&source("login")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "syntax error: SEQUENCE session: step in NEXT is ambiguous (login), \
+         label it with AS"
+    );
+    assert!(evaluate_str(&source("login AS again")).is_ok());
+    let err = evaluate_str(&source("login AS login")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "syntax error: SEQUENCE session: step name used twice (login)"
+    );
+}
+
+#[test]
+fn invalid_attributes() {
+    for attrs in ["REPEAT(5 1)", "REPEAT(-1)", "MUTATE(2)", "NEXT(1)"] {
+        let env = evaluate_str(&format!(
+            r#"
+            DEFINE login
+                "LOGIN"
+            SEQUENCE session
+                login -> {},
+            GENERATE session
+            "#,
+            attrs
+        ));
+        assert!(matches!(env, Err(BajzelError::Syntax(_))), "{}", attrs);
+    }
+}
+
+#[test]
+fn name_shared_with_group() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        SEQUENCE login
+            login
+        GENERATE login
+        "#,
+    );
+    assert!(matches!(env, Err(BajzelError::Syntax(_))));
+}
+
+#[test]
+fn captures_defined() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        DEFINE welcome
+            "HELLO " string AS session_id
+        DEFINE command
+            "CMD " $nonce " " $session_id
+        SEQUENCE session
+            login -> CAPTURE(nonce "NONCE=([0-9a-f]+)") REPLY(welcome),
+            command
+        GENERATE session
+        "#,
+    )
+    .unwrap();
+    let sequence = env.find_sequence(&"session").unwrap();
+    assert_eq!(sequence.steps[0].captures.len(), 2);
+}
+
+#[test]
+fn invalid_captures() {
+    for attrs in ["CAPTURE(nonce)", "CAPTURE(nonce \"(\")", "REPLY(missing)"] {
+        let env = evaluate_str(&format!(
+            r#"
+            DEFINE login
+                "LOGIN"
+            SEQUENCE session
+                login -> {},
+            GENERATE session
+            "#,
+            attrs
+        ));
+        assert!(matches!(env, Err(BajzelError::Syntax(_))), "{}", attrs);
+    }
+}
+
+#[test]
+fn reply_alias_used_twice() {
+    let env = evaluate_str(
+        r#"
+        DEFINE token
+            "TOKEN=" string AS value -> LEN(1 8),
+        DEFINE welcome
+            "OK " string AS value -> LEN(1 8),
+            ref FROM token
+        DEFINE login
+            "LOGIN"
+        SEQUENCE session
+            login -> REPLY(welcome),
+        GENERATE session
+        "#,
+    );
+    assert_eq!(
+        env.unwrap_err().to_string(),
+        "syntax error: REPLY(welcome): alias value used twice"
+    );
+}
+
+#[test]
+fn variable_not_captured() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        DEFINE command
+            "CMD " $session_id
+        SEQUENCE session
+            login -> CAPTURE(nonce "NONCE=(.*)"),
+            command
+        GENERATE session
+        "#,
+    );
+    assert!(matches!(env, Err(BajzelError::Syntax(_))));
+}
@@ -0,0 +1,188 @@
+use crate::evaluate_str;
+use crate::runner::basics::temp_dir;
+use bajzel_lib::{
+    error::BajzelError, evaluator::evaluate_source_in, generator::Gen,
+};
+use pretty_assertions::assert_eq;
+use std::fs;
+
+const TLV: &str = r#"
+DEFINE tlv(T, max)
+    T     AS tag
+    ":"
+    bytes AS value -> LEN(max max),
+"#;
+
+#[test]
+fn instances() {
+    let env = evaluate_str(&format!(
+        r#"
+        CONST LARGE = 4
+        {}
+        DEFINE file
+            ref AS small FROM tlv(u8, 2)
+            ref AS large FROM tlv(le_u16, LARGE)
+            ref AS again FROM tlv(u8, 2)
+        GENERATE file
+        "#,
+        TLV
+    ))
+    .unwrap();
+    assert!(env.get_group(&"tlv").is_err());
+    let small = env.summary_of("tlv(u8, 2)").unwrap();
+    assert_eq!(small.lines().count(), 1 + 3);
+    assert!(env.get_group(&"tlv(le_u16, 4)").is_ok());
+
+    let large = env.summary_of("tlv(le_u16, 4)").unwrap();
+    assert!(large.contains("length_min: 4, length_max: 4"));
+
+    // Text tags take 1 to 3 bytes, the binary one takes 2 bytes
+    let output = Gen::default().generate(&env).unwrap();
+    assert!((2 * 4 + 7..=2 * 6 + 7).contains(&output.len()));
+}
+
+#[test]
+fn nested_templates() {
+    let env = evaluate_str(
+        r#"
+        DEFINE pair(G, T)
+            ref AS first FROM G
+            ref AS second FROM box(T)
+        DEFINE box(T)
+            "["
+            T AS value -> RANGE(7 7),
+            "]"
+        DEFINE name
+            "x"
+        DEFINE file
+            ref AS p FROM pair(name, u8)
+        GENERATE file
+        "#,
+    )
+    .unwrap();
+    assert!(env.get_group(&"pair(name, u8)").is_ok());
+    assert!(env.get_group(&"box(u8)").is_ok());
+    let output = Gen::default().generate(&env).unwrap();
+    assert_eq!(output, b"x[7]".to_vec());
+}
+
+#[test]
+fn group_and_enum_params() {
+    let env = evaluate_str(
+        r#"
+        ENUM kind { A = 1, B = 2 }
+        DEFINE box(G, E)
+            ref AS body
+            u8  AS k    -> ENUM(E),
+        WHERE
+            body -> TO(G),
+        DEFINE inner
+            "[x]"
+        DEFINE file
+            ref AS b FROM box(inner, kind)
+        GENERATE file
+        "#,
+    )
+    .unwrap();
+    assert!(env.get_group(&"box(inner, kind)").is_ok());
+    let output = Gen::default().generate(&env).unwrap();
+    assert!(output.starts_with(b"[x]"), "{:?}", output);
+}
+
+#[test]
+fn argument_errors() {
+    let err = |template: &str, field: &str| {
+        let source =
+            format!("{}\nDEFINE a\n    {}\nGENERATE a\n", template, field);
+        match evaluate_str(&source) {
+            Err(BajzelError::Syntax(msg)) => msg,
+            x => panic!("unexpected result: {:?}", x),
+        }
+    };
+    assert_eq!(
+        err(TLV, "ref FROM tlv(u8)"),
+        "tlv(u8): expects 2 argument(s), got 1"
+    );
+    assert_eq!(
+        err(TLV, "ref FROM tlv(8, 2)"),
+        "tlv(8, 2): T expects a type (8)"
+    );
+    assert_eq!(
+        err(TLV, "ref FROM tlv(u8, u8)"),
+        "tlv(u8, u8): max expects a value (u8)"
+    );
+    assert_eq!(
+        err(TLV, "ref FROM tlv(u8, MISSING)"),
+        "tlv(u8, MISSING): max expects a value (MISSING)"
+    );
+    assert_eq!(
+        err("DEFINE box(G)\n    ref FROM G", "ref FROM box(u8)"),
+        "box(u8): G expects a group (u8)"
+    );
+    assert_eq!(
+        err(
+            "DEFINE box(G)\n    ref AS x\nWHERE\n    x -> TO(G),",
+            "ref FROM box(u8)"
+        ),
+        "box(u8): G expects a group (u8)"
+    );
+    assert_eq!(
+        err("DEFINE box(E)\n    u8 AS x -> ENUM(E),", "ref FROM box(5)"),
+        "box(5): E expects an enumeration (5)"
+    );
+    assert_eq!(
+        err(TLV, "ref FROM tlv"),
+        "ref: tlv is a template, arguments are missing"
+    );
+    assert_eq!(
+        err(TLV, "ref FROM missing(u8)"),
+        "ref: unknown template (missing)"
+    );
+    assert_eq!(err(TLV, "T AS x"), "unknown type (T)");
+}
+
+#[test]
+fn definition_errors() {
+    let err = |template: &str| {
+        let source = format!("{}\nDEFINE a\n    \"a\"\nGENERATE a\n", template);
+        match evaluate_str(&source) {
+            Err(BajzelError::Syntax(msg)) => msg,
+            x => panic!("unexpected result: {:?}", x),
+        }
+    };
+    assert_eq!(err("DEFINE t(T)\n    X AS x"), "t: unknown parameter (X)");
+    assert_eq!(
+        err("DEFINE t(T)\n    T AS x -> RANGE(0 T),"),
+        "t: parameter T used both as a type and a value"
+    );
+    assert_eq!(
+        err("DEFINE t(T, T)\n    T AS x"),
+        "DEFINE t: duplicate parameter (T)"
+    );
+    assert_eq!(
+        err("DEFINE t(T)\n    T AS x\nDEFINE t(U)\n    U AS x"),
+        "DEFINE t already defined"
+    );
+}
+
+#[test]
+fn imported_templates() {
+    let dir = temp_dir("templates-imported");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("lib.fuzl"),
+        "CONST SIZE = 2\nDEFINE sep\n    \":\"\nDEFINE field(T)\n    \
+         T AS value -> RANGE(SIZE SIZE),\n    ref FROM sep\n",
+    )
+    .unwrap();
+    let env = evaluate_source_in(
+        "IMPORT \"lib.fuzl\"\nDEFINE a\n    ref FROM lib::field(u8)\n\
+         GENERATE a\n",
+        &dir,
+    )
+    .unwrap();
+    assert!(env.get_group(&"lib::field(u8)").is_ok());
+    let output = Gen::default().generate(&env).unwrap();
+    assert_eq!(output, b"2:".to_vec());
+    fs::remove_dir_all(&dir).unwrap();
+}
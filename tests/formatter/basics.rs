@@ -0,0 +1,149 @@
+use bajzel_lib::error::BajzelError;
+use bajzel_lib::formatter::format_source;
+use bajzel_lib::lexer::{lex_spanned, Token};
+use pretty_assertions::assert_eq;
+use std::fs::read_to_string;
+
+#[test]
+fn keywords_and_columns() {
+    let input = "define cmd
+  string as name -> LEN(1 8),
+        \" \"
+  u8 as id ->RANGE(1 16),
+where
+ name   -> DICT(\"x.dict\"),
+generate cmd with
+OUT_MAX=32
+  TERM   = lf
+";
+    let expected = "\
+DEFINE cmd
+    string AS name -> LEN(1 8),
+    \" \"
+    u8     AS id   -> RANGE(1 16),
+WHERE
+    name -> DICT(\"x.dict\"),
+
+GENERATE cmd WITH
+    OUT_MAX = 32
+    TERM    = LF
+";
+    assert_eq!(format_source(input).unwrap(), expected);
+}
+
+#[test]
+fn comments_kept() {
+    let input = "# Header comment
+DEFINE a
+   `0a 0b` AS magic # Magic bytes
+   u8 AS x
+      # Unused field
+   # $x AS y
+# Next group
+DEFINE b
+    ref AS a_ref FROM a  # Reference
+";
+    let expected = "\
+# Header comment
+DEFINE a
+    `0a 0b` AS magic  # Magic bytes
+    u8      AS x
+    # Unused field
+    # $x AS y
+
+# Next group
+DEFINE b
+    ref AS a_ref FROM a  # Reference
+";
+    assert_eq!(format_source(input).unwrap(), expected);
+}
+
+#[test]
+fn blank_lines_collapsed() {
+    let input = "DEFINE a\n\n\n    u8 AS x\n\n\n\n    u8 AS y\n\n\n";
+    let expected = "DEFINE a\n\n    u8 AS x\n\n    u8 AS y\n";
+    assert_eq!(format_source(input).unwrap(), expected);
+}
+
+#[test]
+fn examples_idempotent() {
+    for name in ["example0", "example1", "example3", "example4"] {
+        let path = format!("./examples/{}.fuzl", name);
+        let input = read_to_string(&path).unwrap();
+        let output = format_source(&input).unwrap();
+        assert_eq!(format_source(&output).unwrap(), output, "{}", path);
+        assert_eq!(
+            lex_spanned(&output).unwrap().len(),
+            lex_spanned(&input).unwrap().len(),
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn syntax_error() {
+    let output = format_source("DEFINE a\n    u8 AS x ->\n");
+    assert!(matches!(output, Err(BajzelError::Syntax(_))));
+}
+
+#[test]
+fn spanned_comments() {
+    let input = "DEFINE a # group\n    u8\n";
+    let tokens = lex_spanned(input).unwrap();
+    let expected = vec![
+        (Token::Define, 0..6),
+        (Token::Ident("a"), 7..8),
+        (Token::Comment(" group"), 9..16),
+        (Token::Type("u8"), 21..23),
+    ];
+    assert_eq!(tokens, expected);
+}
+
+#[test]
+fn imports() {
+    let input = "import \"common/tlv.fuzl\"\nIMPORT \"b.fuzl\"\nDEFINE a\n  \
+                 ref AS x FROM tlv::record\n";
+    let expected = "\
+IMPORT \"common/tlv.fuzl\"
+IMPORT \"b.fuzl\"
+
+DEFINE a
+    ref AS x FROM tlv::record
+";
+    assert_eq!(format_source(input).unwrap(), expected);
+}
+
+#[test]
+fn declarations() {
+    let input = "const SIZE=40\nCONST MAX_SIZE = SIZE\nenum kind { A = 0, \
+                 LONGER = 1 }\nDEFINE a\n  u8 AS x -> enum(kind),\n";
+    let expected = "\
+CONST SIZE     = 40
+CONST MAX_SIZE = SIZE
+
+ENUM kind {
+    A      = 0,
+    LONGER = 1,
+}
+
+DEFINE a
+    u8 AS x -> ENUM(kind),
+";
+    assert_eq!(format_source(input).unwrap(), expected);
+}
+
+#[test]
+fn templates() {
+    let input = "DEFINE tlv( T,max )\n  T AS tag\n  bytes AS value -> \
+                 LEN(0 max),\nDEFINE a\n  ref AS x FROM tlv(u8 , 16)\n";
+    let expected = "\
+DEFINE tlv(T, max)
+    T     AS tag
+    bytes AS value -> LEN(0 max),
+
+DEFINE a
+    ref AS x FROM tlv(u8, 16)
+";
+    assert_eq!(format_source(input).unwrap(), expected);
+}
@@ -0,0 +1,135 @@
+use crate::evaluate_str;
+use bajzel_lib::dictionary::Dictionary;
+use bajzel_lib::generator::{
+    annotation::{Annotation, Value, BOUNDARY, DICTIONARY},
+    Gen,
+};
+use pretty_assertions::assert_eq;
+
+fn annotation(
+    group: &str,
+    alias: Option<&str>,
+    range: std::ops::Range<usize>,
+    value: Value,
+) -> Annotation {
+    Annotation {
+        group: group.to_owned(),
+        alias: alias.map(str::to_owned),
+        range,
+        value,
+        mutated_by: None,
+    }
+}
+
+#[test]
+fn fields_with_nested_groups() {
+    let env = evaluate_str(
+        r#"
+        DEFINE pair
+            "(" u8 AS x -> RANGE(7 7), ")"
+        DEFINE cmd
+            "PAIR"
+            ref AS p FROM pair
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let (output, annotations) =
+        Gen::default().generate_annotated(&env).unwrap();
+    assert_eq!(output, b"PAIR(7)");
+    let bytes = |x: &[u8]| Value::Bytes(x.to_vec());
+    assert_eq!(
+        annotations,
+        vec![
+            annotation("cmd", None, 0..4, bytes(b"PAIR")),
+            annotation(
+                "cmd",
+                Some("p"),
+                4..7,
+                Value::Group(vec![
+                    annotation("pair", None, 4..5, bytes(b"(")),
+                    annotation("pair", Some("x"), 5..6, Value::Number(7)),
+                    annotation("pair", None, 6..7, bytes(b")")),
+                ])
+            ),
+        ]
+    );
+    assert_eq!(Annotation::leaves(&annotations).len(), 4);
+}
+
+#[test]
+fn same_output_as_generate() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            string AS name -> LEN(1 10),
+            " "
+            i32 AS id
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let mut gen = Gen::default();
+    for seed in 0..20 {
+        gen.set_seed(seed);
+        let expected = gen.generate(&env).unwrap();
+        gen.set_seed(seed);
+        let (output, annotations) = gen.generate_annotated(&env).unwrap();
+        assert_eq!(output, expected);
+        assert_eq!(annotations.last().unwrap().range.end, output.len());
+    }
+}
+
+#[test]
+fn heuristics_recorded() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u32 AS param -> RANGE(100 200),
+            string AS name
+        GENERATE cmd WITH
+            NUM_UNIFORM     = 0
+            NUM_INTERESTING = 0
+            NUM_BOUNDARY    = 1
+        "#,
+    )
+    .unwrap();
+    let mut gen = Gen::default();
+    gen.add_dictionary(Dictionary::parse("\"GET\"").unwrap());
+    let mut from_dict = false;
+    for _ in 0..50 {
+        let (_, annotations) = gen.generate_annotated(&env).unwrap();
+        assert_eq!(annotations[0].mutated_by, Some(BOUNDARY));
+        if annotations[1].mutated_by == Some(DICTIONARY) {
+            assert_eq!(annotations[1].value, Value::Bytes(b"GET".to_vec()));
+            from_dict = true;
+        }
+    }
+    assert!(from_dict);
+}
+
+#[test]
+fn session_messages() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        DEFINE quit
+            "QUIT"
+        SEQUENCE session
+            login
+            quit
+        GENERATE session
+            TERM = LF
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    let (output, annotations) = gen.generate_input_annotated(&env).unwrap();
+    assert_eq!(output, b"LOGIN\nQUIT\n");
+    let ranges: Vec<_> = annotations.iter().map(|x| x.range.clone()).collect();
+    assert_eq!(ranges, vec![0..5, 6..10]);
+    let leaves = Annotation::leaves(&annotations);
+    assert_eq!(leaves[1].group, "quit");
+    assert_eq!(leaves[1].range, 6..10);
+}
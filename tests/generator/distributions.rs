@@ -0,0 +1,120 @@
+use crate::evaluate_str;
+use bajzel_lib::generator::Gen;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn number_weights() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u8 AS param -> WEIGHTS(7 1 9 0),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    for _ in 0..50 {
+        assert_eq!(gen.generate(&env).unwrap(), b"7".to_vec());
+    }
+}
+
+#[test]
+fn string_length_weights() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            string AS name -> WEIGHTS(3 1),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    for _ in 0..50 {
+        assert_eq!(gen.generate(&env).unwrap().len(), 3);
+    }
+}
+
+#[test]
+fn normal_without_deviation() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            bytes AS payload -> LEN(0 100) DIST(normal 42 0),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    for _ in 0..50 {
+        assert_eq!(gen.generate(&env).unwrap().len(), 42);
+    }
+}
+
+#[test]
+fn log_favors_short_lengths() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            bytes AS payload -> LEN(0 1000) DIST(log),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    let short = (0..300)
+        .filter(|_| gen.generate(&env).unwrap().len() < 100)
+        .count();
+    assert!(short > 150, "only {} of 300 were short", short);
+}
+
+#[test]
+fn log_reaches_max() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u8 AS param -> RANGE(0 2) DIST(log),
+        GENERATE cmd WITH
+            NUM_INTERESTING = 0
+            NUM_BOUNDARY    = 0
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    let mut seen: Vec<_> =
+        (0..300).map(|_| gen.generate(&env).unwrap()).collect();
+    seen.sort();
+    seen.dedup();
+    assert_eq!(seen, [b"0".to_vec(), b"1".to_vec(), b"2".to_vec()]);
+}
+
+#[test]
+fn unsupported_distribution() {
+    let input = r#"
+        DEFINE cmd
+            bytes AS payload -> DIST(zipf),
+        GENERATE cmd
+    "#;
+    assert!(evaluate_str(input).is_err());
+}
+
+#[test]
+fn enum_favors_members() {
+    let env = evaluate_str(
+        r#"
+        ENUM kind { A = 10, B = 20, C = 30 }
+        DEFINE cmd
+            u8 AS kind -> ENUM(kind),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    let members = (0..500)
+        .filter(|_| {
+            let output = gen.generate(&env).unwrap();
+            [&b"10"[..], b"20", b"30"].contains(&output.as_slice())
+        })
+        .count();
+    assert!(members > 400, "only {} of 500 were members", members);
+    assert!(members < 500, "no value outside of the enumeration");
+}
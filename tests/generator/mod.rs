@@ -0,0 +1,4 @@
+pub mod annotations;
+pub mod distributions;
+pub mod numbers;
+pub mod sessions;
@@ -0,0 +1,125 @@
+use crate::evaluate_str;
+use bajzel_lib::generator::Gen;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn const_integer_is_not_random() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            "x=" 42
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let output = Gen::default().generate(&env).unwrap();
+    assert_eq!(output, b"x=42".to_vec());
+}
+
+#[test]
+fn text_number_within_range() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            i32 AS param -> RANGE(-3 5),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    for _ in 0..100 {
+        let output = gen.generate(&env).unwrap();
+        let value: i32 = String::from_utf8(output).unwrap().parse().unwrap();
+        assert!((-3..=5).contains(&value));
+    }
+}
+
+#[test]
+fn u64_upper_bound() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u64 AS top -> RANGE(18446744073709551614 18446744073709551615),
+            ","
+            le_u64 AS any
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    let mut tops = vec![];
+    let mut above_i64 = false;
+    for _ in 0..100 {
+        let output = gen.generate(&env).unwrap();
+        let (top, any) = output.split_at(output.len() - 9);
+        tops.push(String::from_utf8(top.to_vec()).unwrap());
+        let any = u64::from_le_bytes(any[1..].try_into().unwrap());
+        above_i64 |= any > i64::MAX as u64;
+    }
+    tops.sort();
+    tops.dedup();
+    assert_eq!(tops, ["18446744073709551614", "18446744073709551615"]);
+    assert!(above_i64);
+
+    let source = "DEFINE a\n    u64 AS x -> RANGE(0 18446744073709551616),\n\
+                  GENERATE a\n";
+    assert!(evaluate_str(source).is_err());
+}
+
+#[test]
+fn boundary_values_only() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u32 AS param -> RANGE(100 200),
+        GENERATE cmd WITH
+            NUM_UNIFORM     = 0
+            NUM_INTERESTING = 0
+            NUM_BOUNDARY    = 1
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    for _ in 0..100 {
+        let output = gen.generate(&env).unwrap();
+        let value: u32 = String::from_utf8(output).unwrap().parse().unwrap();
+        assert!([100, 101, 199, 200].contains(&value));
+    }
+}
+
+#[test]
+fn interesting_values_only() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u8 AS param -> RANGE(0 20),
+        GENERATE cmd WITH
+            NUM_UNIFORM     = 0
+            NUM_INTERESTING = 1
+            NUM_BOUNDARY    = 0
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    for _ in 0..100 {
+        let output = gen.generate(&env).unwrap();
+        let value: u8 = String::from_utf8(output).unwrap().parse().unwrap();
+        assert!([0, 1, 2, 3, 4, 5, 7, 8, 9, 15, 16, 17].contains(&value));
+    }
+}
+
+#[test]
+fn byte_number_endianess() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            le_u16 AS le -> RANGE(258 258),
+            be_u16 AS be -> RANGE(258 258),
+            be_i32 AS neg -> RANGE(-2 -2),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let output = Gen::default().generate(&env).unwrap();
+    assert_eq!(output, vec![0x02, 0x01, 0x01, 0x02, 0xff, 0xff, 0xff, 0xfe]);
+}
@@ -0,0 +1,217 @@
+use crate::evaluate_str;
+use bajzel_lib::dictionary::Dictionary;
+use bajzel_lib::generator::{session::Message, Gen};
+use pretty_assertions::assert_eq;
+
+fn message(group: &str, mutate: bool) -> Message {
+    Message {
+        group: group.to_owned(),
+        mutate,
+    }
+}
+
+#[test]
+fn steps_in_order() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        DEFINE command
+            "CMD"
+        DEFINE quit
+            "QUIT"
+        SEQUENCE session
+            login -> MUTATE(0),
+            command -> REPEAT(3),
+            quit
+        GENERATE session
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    assert_eq!(
+        gen.plan_session(&env).unwrap(),
+        vec![
+            message("login", false),
+            message("command", true),
+            message("command", true),
+            message("command", true),
+            message("quit", true),
+        ]
+    );
+    assert_eq!(
+        gen.generate_session(&env).unwrap(),
+        vec![
+            b"LOGIN".to_vec(),
+            b"CMD".to_vec(),
+            b"CMD".to_vec(),
+            b"CMD".to_vec(),
+            b"QUIT".to_vec()
+        ]
+    );
+}
+
+#[test]
+fn single_group_session() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        GENERATE login
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    assert_eq!(
+        gen.plan_session(&env).unwrap(),
+        vec![message("login", true)]
+    );
+}
+
+#[test]
+fn next_steps() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        DEFINE command
+            "CMD"
+        DEFINE quit
+            "QUIT"
+        SEQUENCE session
+            login -> NEXT(quit),
+            command
+            quit -> NEXT(END),
+            command
+        GENERATE session
+        "#,
+    )
+    .unwrap();
+    let plan = Gen::default().plan_session(&env).unwrap();
+    assert_eq!(plan, vec![message("login", true), message("quit", true)]);
+}
+
+#[test]
+fn next_labelled_step() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        DEFINE command
+            "CMD"
+        SEQUENCE session
+            login -> NEXT(relogin),
+            command
+            login AS relogin -> NEXT(last),
+            command AS last -> NEXT(END),
+        GENERATE session
+        "#,
+    )
+    .unwrap();
+    let plan = Gen::default().plan_session(&env).unwrap();
+    assert_eq!(
+        plan,
+        vec![
+            message("login", true),
+            message("login", true),
+            message("command", true)
+        ]
+    );
+}
+
+#[test]
+fn messages_capped() {
+    let env = evaluate_str(
+        r#"
+        DEFINE command
+            "CMD"
+        SEQUENCE session
+            command -> NEXT(command),
+        GENERATE session
+            MAX_MESSAGES = 10
+        "#,
+    )
+    .unwrap();
+    let plan = Gen::default().plan_session(&env).unwrap();
+    assert_eq!(plan.len(), 10);
+}
+
+#[test]
+fn not_mutated_steps_skip_global_dictionary() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            string AS user -> LEN(4 4),
+        SEQUENCE session
+            login -> MUTATE(0),
+        GENERATE session
+        "#,
+    )
+    .unwrap();
+    let mut gen = Gen::default();
+    gen.add_dictionary(Dictionary::parse(r#""@@@""#).unwrap());
+    for _ in 0..100 {
+        let session = gen.generate_session(&env).unwrap();
+        assert_eq!(session[0].len(), 4);
+    }
+}
+
+#[test]
+fn captured_values_used_by_later_messages() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        DEFINE welcome
+            "HELLO " string AS user " " u32 AS session_id
+        DEFINE command
+            "CMD " $nonce " " $session_id
+        SEQUENCE session
+            login -> CAPTURE(nonce "NONCE=([0-9a-f]+)") REPLY(welcome),
+            command
+        GENERATE session
+            TERM = LF
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    let mut session = gen.start_session(&env).unwrap();
+    assert_eq!(session.next_message().unwrap(), Some(b"LOGIN\n".to_vec()));
+    assert!(session.expects_reply());
+    session
+        .handle_reply(b"HELLO joe 1234\nNONCE=c0ffee\n")
+        .unwrap();
+    assert_eq!(session.vars().get("user"), Some(&b"joe".to_vec()));
+    assert_eq!(
+        session.next_message().unwrap(),
+        Some(b"CMD c0ffee 1234\n".to_vec())
+    );
+    assert!(!session.expects_reply());
+    assert_eq!(session.next_message().unwrap(), None);
+    assert_eq!(
+        session.sent(),
+        &[b"LOGIN\n".to_vec(), b"CMD c0ffee 1234\n".to_vec()]
+    );
+}
+
+#[test]
+fn unmatched_reply_leaves_variables_empty() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        DEFINE command
+            "CMD " $nonce
+        SEQUENCE session
+            login -> CAPTURE(nonce "NONCE=([0-9a-f]+)"),
+            command
+        GENERATE session
+        "#,
+    )
+    .unwrap();
+    let gen = Gen::default();
+    let mut session = gen.start_session(&env).unwrap();
+    session.next_message().unwrap();
+    session.handle_reply(b"ERROR").unwrap();
+    assert_eq!(session.next_message().unwrap(), Some(b"CMD ".to_vec()));
+}
@@ -90,8 +90,8 @@ fn const_integers() {
 #[test]
 fn const_integers_overflowing() {
     let input = r#"
-        -170141183460469231731687303715884105728
-        170141183460469231731687303715884105727
+        -170141183460469231731687303715884105729
+        170141183460469231731687303715884105728
     "#;
     let output = lex_tokens(input);
     let invalids = {
@@ -105,7 +105,7 @@ fn const_integers_overflowing() {
         }
     };
     // Count invalids so we don't need to add them into expected vector
-    assert_eq!(invalids, 40);
+    assert_eq!(invalids, 2);
 
     // Filter out Invalids as we already figured it out
     let output = output.map(|tokens| {
@@ -115,11 +115,11 @@ fn const_integers_overflowing() {
             .collect_vec()
     });
 
-    // When there's too many integer numbers, only the last part (of size i64) is parsed
+    // When there's too many integer numbers, only the last part (of size i128) is parsed
     let expected = vec![
         Token::Subtract,
-        Token::IntegerLiteral(1687303715884105728),
-        Token::IntegerLiteral(1687303715884105727),
+        Token::IntegerLiteral(70141183460469231731687303715884105729),
+        Token::IntegerLiteral(70141183460469231731687303715884105728),
         Token::Eof,
     ];
     assert_eq!(output, Ok(expected));
@@ -176,3 +176,44 @@ fn comments_are_discarded() {
     ];
     assert_eq!(output, Ok(expected));
 }
+
+#[test]
+fn namespaced_idents() {
+    let input = r#"IMPORT "common/tlv.fuzl" tlv::record a::b::c tlv:"#;
+    let output = lex_tokens(input);
+    let expected = vec![
+        Token::Import,
+        Token::StringLiteral("common/tlv.fuzl"),
+        Token::Ident("tlv::record"),
+        Token::Ident("a::b::c"),
+        Token::Ident("tlv"),
+        Token::Colon,
+        Token::Eof,
+    ];
+    assert_eq!(output, Ok(expected));
+}
+
+#[test]
+fn declarations() {
+    let input = "CONST SIZE = 40 enum kind { A = 0, B = 1 }";
+    let output = lex_tokens(input);
+    let expected = vec![
+        Token::Const,
+        Token::Ident("SIZE"),
+        Token::Assign,
+        Token::IntegerLiteral(40),
+        Token::Enum,
+        Token::Ident("kind"),
+        Token::LeftBrace,
+        Token::Ident("A"),
+        Token::Assign,
+        Token::IntegerLiteral(0),
+        Token::Comma,
+        Token::Ident("B"),
+        Token::Assign,
+        Token::IntegerLiteral(1),
+        Token::RightBrace,
+        Token::Eof,
+    ];
+    assert_eq!(output, Ok(expected));
+}
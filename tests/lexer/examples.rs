@@ -86,9 +86,9 @@ fn example1() {
         Token::Type("bytes"),
         Token::As,
         Token::Ident("payload"),
-        // Token::Type("ref"),
-        // Token::As,
-        // Token::Ident("mem_range"),
+        Token::Type("ref"),
+        Token::As,
+        Token::Ident("mem_range"),
         Token::Where,
         // Token::Ident("prefix"),
         // Token::RightArrow,
@@ -118,12 +118,13 @@ fn example1() {
         // Token::Ident("payload_len"),
         // Token::RightParen,
         // Token::Comma,
-        // Token::Ident("mem_range"),
-        // Token::RightArrow,
-        // Token::Ident("DEF"),
-        // Token::LeftParen,
-        // Token::Ident("int_pair"),
-        // Token::RightParen,
+        Token::Ident("mem_range"),
+        Token::RightArrow,
+        Token::Ident("TO"),
+        Token::LeftParen,
+        Token::Ident("int_pair"),
+        Token::RightParen,
+        Token::Comma,
         Token::Define,
         Token::Ident("int_pair"),
         Token::Type("i32"),
@@ -0,0 +1,56 @@
+use bajzel_lib::libfuzzer::{custom_mutator, Grammar};
+use pretty_assertions::assert_eq;
+
+const SOURCE: &str = r#"
+    DEFINE cmd
+        "CMD "
+        u32 AS id       -> RANGE(1 1000),
+        " "
+        string AS name  -> LEN(1 16),
+    GENERATE cmd
+        TERM = LF
+"#;
+
+#[test]
+fn same_data_same_input() {
+    let mut grammar = Grammar::from_source(SOURCE).unwrap();
+    let data: Vec<u8> = (0..64u8).map(|x| x.wrapping_mul(7)).collect();
+    let first = grammar.generate(&data).unwrap();
+    assert!(first.starts_with(b"CMD "), "{:?}", first);
+    assert!(first.ends_with(b"\n"), "{:?}", first);
+    assert_eq!(grammar.generate(&data).unwrap(), first);
+}
+
+#[test]
+fn empty_data_gives_smallest_values() {
+    let mut grammar = Grammar::from_source(SOURCE).unwrap();
+    assert_eq!(grammar.generate(&[]).unwrap(), b"CMD 1 A\n");
+}
+
+#[test]
+fn different_data_different_inputs() {
+    let mut grammar = Grammar::from_source(SOURCE).unwrap();
+    let inputs: Vec<_> = (0..16u8)
+        .map(|x| grammar.generate(&[x; 32]).unwrap())
+        .collect();
+    assert!(inputs.iter().any(|x| *x != inputs[0]));
+}
+
+#[test]
+fn mutations_fit_max_size() {
+    let mut data = [0xAA; 32];
+    for seed in 0..100 {
+        let size = custom_mutator(&mut data, 16, 24, seed);
+        assert!(size <= 24, "{}", size);
+    }
+    assert_eq!(custom_mutator(&mut data, 0, 0, 1), 0);
+}
+
+#[test]
+fn mutations_are_deterministic() {
+    let mut first = [1; 32];
+    let mut second = [1; 32];
+    let size = custom_mutator(&mut first, 8, 32, 42);
+    assert_eq!(custom_mutator(&mut second, 8, 32, 42), size);
+    assert_eq!(first, second);
+}
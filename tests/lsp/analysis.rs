@@ -0,0 +1,265 @@
+use bajzel_lib::lsp::analysis::{
+    completions, definition, diagnostics, hover, CompletionKind,
+};
+use pretty_assertions::assert_eq;
+use std::path::Path;
+
+const SOURCE: &str = "\
+DEFINE login
+    \"LOGIN \"
+    string AS user -> LEN(3 8),
+
+DEFINE command
+    \"CMD \"
+    u8  AS id
+    ref AS body FROM login
+WHERE
+    id -> RANGE(1 16),
+
+SEQUENCE session
+    login   -> MUTATE(0),
+    command -> NEXT(command END),
+
+GENERATE session WITH
+    TERM = LF
+";
+
+/// Labels of completions at a place marked with `|` in a source
+///
+fn labels_at(source: &str) -> Vec<(String, CompletionKind)> {
+    let offset = source.find('|').unwrap();
+    let source = source.replace('|', "");
+    completions(&source, offset)
+        .into_iter()
+        .map(|x| (x.label, x.kind))
+        .collect()
+}
+
+fn labels(source: &str, kind: CompletionKind) -> Vec<String> {
+    labels_at(source)
+        .into_iter()
+        .filter(|(_, x)| *x == kind)
+        .map(|(label, _)| label)
+        .collect()
+}
+
+#[test]
+fn valid_program() {
+    assert_eq!(diagnostics(SOURCE, Path::new("")), vec![]);
+}
+
+#[test]
+fn syntax_error_located() {
+    let source = "DEFINE a\n    u8 AS x\n    @\n";
+    let found = diagnostics(source, Path::new(""));
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].range, 25..26);
+
+    let source = "DEFINE a\n    u8 AS x ->\nDEFINE b\n";
+    let found = diagnostics(source, Path::new(""));
+    assert_eq!(found.len(), 1);
+    assert_eq!(&source[found[0].range.clone()], "->");
+}
+
+#[test]
+fn evaluation_error() {
+    let source = "DEFINE a\n    u8 AS x -> LEN(4),\n";
+    let found = diagnostics(source, Path::new(""));
+    assert_eq!(found.len(), 1);
+    assert!(found[0].message.contains("attribute"), "{:?}", found);
+
+    let source = "DEFINE a\n    u8 AS x -> RANGE(1 2),\n";
+    let found = diagnostics(source, Path::new(""));
+    assert_eq!(found[0].message, "program end not expected yet");
+
+    let source = "DEFINE a\n    `0a 0b` AS magic\nGENERATE a\n";
+    let found = diagnostics(source, Path::new(""));
+    assert!(found[0].message.contains("bytes literals"), "{:?}", found);
+
+    let source = "DEFINE a\n    u8 AS x\nGENERATE a\n    u8 AS y\n";
+    let found = diagnostics(source, Path::new(""));
+    assert!(found[0].message.contains("in GENERATE"), "{:?}", found);
+
+    let source = "DEFINE a\n    string AS name\nWHERE\n    nam -> LEN(1 2),\n";
+    let found = diagnostics(source, Path::new(""));
+    assert_eq!(found.len(), 1);
+    assert_eq!(
+        found[0].message,
+        "syntax error: unknown field nam in WHERE of a"
+    );
+}
+
+#[test]
+fn types_at_field_start() {
+    let found = labels("DEFINE a\n    u8 AS x\n    |", CompletionKind::Type);
+    assert!(found.contains(&"le_u16".to_owned()));
+    assert!(found.contains(&"string".to_owned()));
+}
+
+#[test]
+fn attributes_of_field_kind() {
+    let found = labels("DEFINE a\n    u8 AS x -> |", CompletionKind::Attribute);
+    assert_eq!(found, ["RANGE", "DIST", "WEIGHTS", "ENUM"]);
+
+    let found = labels(
+        "DEFINE a\n    string AS x -> LEN(1 2) D|",
+        CompletionKind::Attribute,
+    );
+    assert_eq!(found, ["LEN", "DICT", "DIST", "WEIGHTS"]);
+
+    let found = labels(
+        "DEFINE a\n    bytes AS x\n    u8 AS y\nWHERE\n    x -> |",
+        CompletionKind::Attribute,
+    );
+    assert_eq!(found, ["LEN", "DICT", "DIST", "WEIGHTS"]);
+
+    let found = labels(
+        "DEFINE a\n    u8\nSEQUENCE s\n    a -> |",
+        CompletionKind::Attribute,
+    );
+    assert_eq!(found, ["REPEAT", "MUTATE", "NEXT", "CAPTURE", "REPLY"]);
+}
+
+#[test]
+fn imports_before_blocks() {
+    let found = labels("IMPORT \"a.fuzl\"\n|", CompletionKind::Keyword);
+    assert_eq!(
+        found,
+        ["IMPORT", "CONST", "ENUM", "DEFINE", "SEQUENCE", "GENERATE"]
+    );
+    let found = labels("DEFINE a\n    u8\n|", CompletionKind::Keyword);
+    assert!(!found.contains(&"IMPORT".to_owned()));
+}
+
+#[test]
+fn group_names() {
+    let source = "DEFINE a\n    u8\nDEFINE b\n    ref AS x FROM |";
+    assert_eq!(labels(source, CompletionKind::Group), ["a", "b"]);
+
+    let source = "DEFINE a\n    u8\nDEFINE b\n    ref AS x -> TO(|";
+    assert_eq!(labels(source, CompletionKind::Group), ["a", "b"]);
+
+    let source = "DEFINE a\n    u8\nSEQUENCE s\n    a\n    |";
+    assert_eq!(labels(source, CompletionKind::Group), ["a", "s"]);
+}
+
+#[test]
+fn step_labels() {
+    let source =
+        "DEFINE a\n    u8\nSEQUENCE s\n    a -> NEXT(|),\n    a AS b\n";
+    assert_eq!(labels(source, CompletionKind::Field), ["b"]);
+}
+
+#[test]
+fn generator_params() {
+    let found = labels("GENERATE a WITH\n    |", CompletionKind::Field);
+    assert!(found.contains(&"OUT_MAX".to_owned()));
+    assert!(found.contains(&"TERM".to_owned()));
+}
+
+#[test]
+fn group_definitions() {
+    let at = |text: &str, nth: usize| {
+        SOURCE.match_indices(text).nth(nth).unwrap().0 + 1
+    };
+    let login = at("login", 0)..at("login", 0) + 4;
+    let found = definition(SOURCE, at("login", 1)).unwrap();
+    assert_eq!(&SOURCE[found.clone()], "login");
+    assert_eq!(found.start, login.start - 1);
+    assert_eq!(definition(SOURCE, at("login", 2)), Some(found));
+
+    // Definitions themselves and fields aren't references
+    assert_eq!(definition(SOURCE, at("login", 0)), None);
+    assert_eq!(definition(SOURCE, at("user", 0)), None);
+}
+
+#[test]
+fn hovered_fields() {
+    let hovered = |text: &str| {
+        let offset = SOURCE.find(text).unwrap() + 1;
+        hover(SOURCE, Path::new(""), offset)
+            .map(|(range, text)| (SOURCE[range].to_owned(), text))
+    };
+    assert_eq!(
+        hovered("user"),
+        Some((
+            "string AS user -> LEN(3 8),".to_owned(),
+            "`user`: string, length 3 to 8".to_owned()
+        ))
+    );
+    assert_eq!(
+        hovered("id -> RANGE"),
+        Some((
+            "id".to_owned(),
+            "`id`: number as text, range 1 to 16".to_owned()
+        ))
+    );
+    let (_, text) = hovered("\"CMD").unwrap();
+    assert_eq!(text, "`_`: constant \"CMD \", length 4");
+    let (_, text) = hovered("body").unwrap();
+    assert_eq!(text, "`body`: fields of group `login`");
+    assert_eq!(hovered("TERM"), None);
+}
+
+#[test]
+fn enumerations() {
+    let source = "\
+ENUM kind { A = 1, B = 2 }
+DEFINE a
+    u8 AS x -> ENUM(kind),
+GENERATE a
+";
+    let found = labels(
+        "ENUM kind { A = 1 }\nDEFINE a\n    u8 AS x -> ENUM(|",
+        CompletionKind::Value,
+    );
+    assert_eq!(found, ["kind"]);
+    let found = labels(
+        "ENUM kind { A = 1 }\nDEFINE a\n    u8 AS x -> |",
+        CompletionKind::Attribute,
+    );
+    assert!(found.contains(&"ENUM".to_owned()));
+
+    let at = |text: &str, nth: usize| {
+        source.match_indices(text).nth(nth).unwrap().0 + 1
+    };
+    let found = definition(source, at("kind", 1)).unwrap();
+    assert_eq!(found.start, at("kind", 0) - 1);
+    let (_, text) = hover(source, Path::new(""), at("kind", 0)).unwrap();
+    assert_eq!(text, "enumeration `kind`: 2 member(s)");
+    let (_, text) = hover(source, Path::new(""), at("x", 0)).unwrap();
+    assert_eq!(
+        text,
+        "`x`: number as text, range 0 to 255, 2 enumerated values"
+    );
+}
+
+#[test]
+fn templates() {
+    let source = "\
+DEFINE tlv(T, max)
+    T     AS tag
+    bytes AS value -> LEN(0 max),
+
+DEFINE file
+    ref AS rec FROM tlv(le_u16, 255)
+GENERATE file
+";
+    let at = |text: &str, nth: usize| {
+        source.match_indices(text).nth(nth).unwrap().0 + 1
+    };
+    let (_, text) = hover(source, Path::new(""), at("tlv", 0)).unwrap();
+    assert_eq!(text, "template `tlv`");
+    let (range, text) = hover(source, Path::new(""), at("rec", 0)).unwrap();
+    assert_eq!(&source[range], "ref AS rec FROM tlv(le_u16, 255)");
+    assert_eq!(text, "`rec`: fields of group `tlv(le_u16, 255)`");
+    assert_eq!(
+        definition(source, at("tlv", 1)).map(|x| x.start),
+        Some(at("tlv", 0) - 1)
+    );
+    let found = labels(
+        "DEFINE tlv(T)\n    T AS x\nDEFINE a\n    ref FROM |",
+        CompletionKind::Group,
+    );
+    assert_eq!(found, ["tlv", "a"]);
+}
@@ -0,0 +1,136 @@
+use bajzel_lib::lsp::{json::Json, serve};
+use pretty_assertions::assert_eq;
+use std::io::Cursor;
+
+fn framed(messages: &[Json]) -> Vec<u8> {
+    let mut input = vec![];
+    for message in messages {
+        let body = message.to_string();
+        input.extend(format!("Content-Length: {}\r\n\r\n", body.len()).bytes());
+        input.extend(body.bytes());
+    }
+    input
+}
+
+fn unframed(output: &[u8]) -> Vec<Json> {
+    let output = String::from_utf8(output.to_vec()).unwrap();
+    output
+        .split("Content-Length: ")
+        .skip(1)
+        .map(|x| Json::parse(x.split_once("\r\n\r\n").unwrap().1).unwrap())
+        .collect()
+}
+
+fn request(id: usize, method: &str, params: Json) -> Json {
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("id", id.into()),
+        ("method", method.into()),
+        ("params", params),
+    ])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ])
+}
+
+fn position(line: usize, character: usize) -> Json {
+    Json::object([
+        (
+            "textDocument",
+            Json::object([("uri", "file:///tmp/a.fuzl".into())]),
+        ),
+        (
+            "position",
+            Json::object([
+                ("line", line.into()),
+                ("character", character.into()),
+            ]),
+        ),
+    ])
+}
+
+#[test]
+fn json_round_trip() {
+    let input = r#" {"a": [1, -2.5, true, null], "b\né😀": {}} "#;
+    let json = Json::parse(input).unwrap();
+    assert_eq!(json.get("a").as_array().unwrap()[0].as_usize(), Some(1));
+    assert_eq!(
+        json.to_string(),
+        "{\"a\":[1,-2.5,true,null],\"b\\u000a\u{e9}\u{1f600}\":{}}"
+    );
+    assert!(Json::parse("{\"a\": }").is_err());
+}
+
+#[test]
+fn session() {
+    let document = Json::object([
+        ("uri", "file:///tmp/a.fuzl".into()),
+        (
+            "text",
+            "DEFINE a\n    u8 AS x -> RANGE(1 9),\n    @\n".into(),
+        ),
+    ]);
+    let change = Json::object([
+        (
+            "textDocument",
+            Json::object([("uri", "file:///tmp/a.fuzl".into())]),
+        ),
+        (
+            "contentChanges",
+            vec![Json::object([(
+                "text",
+                "DEFINE a\n    u8 AS x -> RANGE(1 9),\nGENERATE a\n".into(),
+            )])]
+            .into(),
+        ),
+    ]);
+    let input = framed(&[
+        request(1, "initialize", Json::object([])),
+        notification("initialized", Json::object([])),
+        notification(
+            "textDocument/didOpen",
+            Json::object([("textDocument", document)]),
+        ),
+        notification("textDocument/didChange", change),
+        request(2, "textDocument/hover", position(1, 11)),
+        request(3, "textDocument/completion", position(1, 14)),
+        request(4, "textDocument/formatting", Json::object([])),
+        request(5, "shutdown", Json::Null),
+        notification("exit", Json::Null),
+        request(6, "shutdown", Json::Null),
+    ]);
+    let mut output = vec![];
+    serve(&mut Cursor::new(input), &mut output).unwrap();
+    let messages = unframed(&output);
+    assert_eq!(messages.len(), 7);
+
+    let capabilities = messages[0].get("result").get("capabilities");
+    assert_eq!(*capabilities.get("hoverProvider"), Json::Bool(true));
+
+    let diagnostics = messages[1].get("params").get("diagnostics");
+    let diagnostic = &diagnostics.as_array().unwrap()[0];
+    let start = diagnostic.get("range").get("start");
+    assert_eq!(start.get("line").as_usize(), Some(2));
+    assert_eq!(start.get("character").as_usize(), Some(4));
+    let diagnostics = messages[2].get("params").get("diagnostics");
+    assert_eq!(diagnostics.as_array().unwrap().len(), 0);
+
+    let hover = messages[3].get("result").get("contents").get("value");
+    assert_eq!(hover.as_str(), Some("`x`: number as text, range 1 to 9"));
+
+    let items = messages[4].get("result").as_array().unwrap();
+    let labels: Vec<_> = items
+        .iter()
+        .filter_map(|x| x.get("label").as_str())
+        .collect();
+    assert_eq!(labels, ["RANGE", "DIST", "WEIGHTS", "ENUM"]);
+
+    assert_eq!(messages[5].get("id").as_usize(), Some(4));
+    assert!(messages[5].get("error") != &Json::Null);
+    assert_eq!(messages[6].get("id").as_usize(), Some(5));
+}
@@ -0,0 +1,132 @@
+use crate::evaluate_str;
+use crate::runner::basics::temp_dir;
+use bajzel_lib::error::BajzelError;
+use bajzel_lib::evaluator::evaluate_source_in;
+use bajzel_lib::minimizer::minimize;
+use bajzel_lib::runner::{Crash, Executor, Outcome};
+use pretty_assertions::assert_eq;
+
+/// Target crashing whenever an input contains a given pattern
+///
+struct CrashOn(&'static [u8]);
+
+impl Executor for CrashOn {
+    fn execute(&mut self, input: &[u8]) -> Result<Outcome, BajzelError> {
+        match input.windows(self.0.len()).any(|x| x == self.0) {
+            true => Ok(Outcome::Crashed(Crash::Signal(11))),
+            false => Ok(Outcome::Exited(0)),
+        }
+    }
+}
+
+#[test]
+fn fields_shrunk() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            "CMD "
+            u32 AS id       -> RANGE(5 1000),
+            " "
+            string AS name  -> LEN(2 40),
+        GENERATE cmd
+            TERM = LF
+        "#,
+    )
+    .unwrap();
+    let minimized =
+        minimize(&env, &mut CrashOn(b"XY"), b"CMD 734 XYabcdefgh\n", 100)
+            .unwrap()
+            .unwrap();
+    assert_eq!(minimized.input, b"CMD 5 XY\n");
+}
+
+#[test]
+fn shorter_dict_entry() {
+    let dir = temp_dir("minimizer-dict");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("modes.dict"), "\"fast\"\n\"slow\"\n\"x\"\n")
+        .unwrap();
+    let source = r#"
+        DEFINE cmd
+            "MODE "
+            string AS mode -> DICT("modes.dict"),
+        GENERATE cmd
+            TERM = LF
+        "#;
+    let env = evaluate_source_in(source, &dir).unwrap();
+    let minimized = minimize(&env, &mut CrashOn(b"MODE"), b"MODE slow\n", 100)
+        .unwrap()
+        .unwrap();
+    assert_eq!(minimized.input, b"MODE x\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn repeated_messages_dropped() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        DEFINE command
+            "CMD " u8 AS id
+        SEQUENCE session
+            login
+            command -> REPEAT(1 10),
+        GENERATE session
+            TERM = LF
+        "#,
+    )
+    .unwrap();
+    let input = b"LOGIN\nCMD 1\nCMD 7\nCMD 3\n";
+    let minimized = minimize(&env, &mut CrashOn(b"7"), input, 100)
+        .unwrap()
+        .unwrap();
+    assert_eq!(minimized.input, b"LOGIN\nCMD 7\n");
+}
+
+#[test]
+fn not_crashing() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            "CMD"
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let result = minimize(&env, &mut CrashOn(b"X"), b"CMD", 100).unwrap();
+    assert_eq!(result, None);
+}
+
+#[test]
+fn input_not_matching_program() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            "CMD " u8 AS id
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let result = minimize(&env, &mut CrashOn(b"X"), b"QUIT X", 100);
+    assert!(matches!(result, Err(BajzelError::Conversion(_))));
+}
+
+#[test]
+fn executions_limited() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            string AS name -> LEN(0 100),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let input = [b'X'; 100];
+    let minimized = minimize(&env, &mut CrashOn(b"X"), &input, 3)
+        .unwrap()
+        .unwrap();
+    assert_eq!(minimized.executions, 3);
+    // Out of executions right after halving the string
+    assert_eq!(minimized.input.len(), 50);
+}
@@ -1,3 +1,28 @@
+pub mod afl;
+pub mod covering;
+pub mod dictionary;
+pub mod enumerator;
 pub mod evaluator;
+pub mod formatter;
+pub mod generator;
 pub mod lexer;
+pub mod libfuzzer;
+pub mod lsp;
+pub mod minimizer;
+pub mod output;
 pub mod parser;
+pub mod repl;
+pub mod runner;
+pub mod watch;
+
+use bajzel_lib::{
+    error::BajzelError,
+    evaluator::{evaluate_source_in, ProgramEnv},
+};
+
+/// Lex, parse and evaluate a program given as a string, relative paths
+/// resolved against the current directory
+///
+pub fn evaluate_str(input: &str) -> Result<ProgramEnv, BajzelError> {
+    evaluate_source_in(input, "")
+}
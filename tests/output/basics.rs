@@ -0,0 +1,125 @@
+use bajzel_lib::generator::annotation::{Annotation, Value, BOUNDARY};
+use bajzel_lib::output::{
+    base64, escape, write_annotated_hexdump, write_annotations, write_sample,
+    Format, FORMATS,
+};
+use pretty_assertions::assert_eq;
+
+fn formatted(format: Format, data: &[u8], seed: Option<u64>) -> String {
+    let mut out = vec![];
+    write_sample(&mut out, format, 3, data, seed).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn formats_parsed() {
+    for name in FORMATS {
+        assert!(name.parse::<Format>().is_ok(), "{}", name);
+    }
+    assert!("xml".parse::<Format>().is_err());
+}
+
+#[test]
+fn hexdump() {
+    let data = b"CMD 1\nlonger than a line\x00";
+    let expected = "\
+00000000  43 4d 44 20 31 0a 6c 6f  6e 67 65 72 20 74 68 61 |CMD 1.longer tha|
+00000010  6e 20 61 20 6c 69 6e 65  00                      |n a line.|
+00000019
+";
+    assert_eq!(formatted(Format::Hexdump, data, None), expected);
+    assert_eq!(formatted(Format::Hexdump, b"", None), "00000000\n");
+}
+
+#[test]
+fn arrays() {
+    let data = b"AB\n";
+    assert_eq!(
+        formatted(Format::C, data, None),
+        "uint8_t sample_3[] = {\n    0x41, 0x42, 0x0a,\n};\n"
+    );
+    assert_eq!(
+        formatted(Format::Rust, data, None),
+        "const SAMPLE_3: &[u8] = &[\n    0x41, 0x42, 0x0a,\n];\n"
+    );
+}
+
+#[test]
+fn escaped() {
+    assert_eq!(escape(b"GET /\\ \r\n\xff"), "GET /\\x5c \\x0d\\x0a\\xff");
+    assert_eq!(formatted(Format::Escaped, b"A\n", None), "A\\x0a\n");
+}
+
+#[test]
+fn jsonl() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64(b"\xff\xfe\xfd\xfc"), "//79/A==");
+    assert_eq!(
+        formatted(Format::Jsonl, b"CMD 1\n", Some(7)),
+        "{\"seed\":7,\"length\":6,\"data\":\"Q01EIDEK\"}\n"
+    );
+    assert_eq!(
+        formatted(Format::Jsonl, b"", None),
+        "{\"seed\":null,\"length\":0,\"data\":\"\"}\n"
+    );
+}
+
+#[test]
+fn raw() {
+    assert_eq!(formatted(Format::Raw, b"CMD 1\n", Some(1)), "CMD 1\n");
+}
+
+fn annotations() -> Vec<Annotation> {
+    vec![
+        Annotation {
+            group: "cmd".to_owned(),
+            alias: None,
+            range: 0..2,
+            value: Value::Bytes(b"\"\n".to_vec()),
+            mutated_by: None,
+        },
+        Annotation {
+            group: "cmd".to_owned(),
+            alias: Some("id".to_owned()),
+            range: 2..3,
+            value: Value::Number(7),
+            mutated_by: Some(BOUNDARY),
+        },
+    ]
+}
+
+#[test]
+fn annotations_json() {
+    let mut out = vec![];
+    write_annotations(&mut out, &annotations(), Some(3)).unwrap();
+    let expected = concat!(
+        r#"{"seed":3,"fields":["#,
+        r#"{"group":"cmd","alias":null,"start":0,"end":2,"#,
+        r#""value":"\"\\x0a","mutated_by":null},"#,
+        r#"{"group":"cmd","alias":"id","start":2,"end":3,"#,
+        r#""value":7,"mutated_by":"boundary"}]}"#,
+        "\n"
+    );
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+#[test]
+fn annotated_hexdump() {
+    let mut out = vec![];
+    write_annotated_hexdump(&mut out, b"\"\n7", &annotations()).unwrap();
+    // 13 missing bytes and a gap in the middle of a line
+    let padding = " ".repeat(13 * 3 + 1);
+    let expected = format!(
+        "00000000  \x1b[31m22\x1b[0m \x1b[31m0a\x1b[0m \x1b[32m37\x1b[0m {}\
+|\x1b[31m\"\x1b[0m\x1b[31m.\x1b[0m\x1b[32m7\x1b[0m|
+00000003
+\x1b[31mcmd._\x1b[0m [0..2] = \"\"\\x0a\"
+\x1b[32mcmd.id\x1b[0m [2..3] = 7 (boundary)
+",
+        padding
+    );
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
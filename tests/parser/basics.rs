@@ -166,3 +166,287 @@ fn generator_params() {
 
     assert_eq!(output, Ok(expected));
 }
+
+#[test]
+fn attr_with_ident() {
+    let input = vec![
+        Token::Ident("size"),
+        Token::RightArrow,
+        Token::Ident("DIST"),
+        Token::LeftParen,
+        Token::Ident("normal"),
+        Token::IntegerLiteral(10),
+        Token::IntegerLiteral(2),
+        Token::RightParen,
+        Token::Comma,
+        Token::Eof,
+    ];
+    let input = Tokens::new(&input);
+    let output = parse_tokens(input);
+    let expected: Program = vec![
+        Statement::MakeCurrentField("size".into()),
+        Statement::UpdateField(
+            "DIST".into(),
+            Expr::Group(vec![
+                Expr::IdentExpr("normal".into()),
+                Expr::LiteralExpr(Literal::IntegerLiteral(10)),
+                Expr::LiteralExpr(Literal::IntegerLiteral(2)),
+            ]),
+        ),
+        Statement::Run,
+    ]
+    .into();
+
+    assert_eq!(output, Ok(expected));
+}
+
+#[test]
+fn define_ref_field() {
+    let input = vec![
+        Token::Define,
+        Token::Ident("file"),
+        Token::Type("ref"),
+        Token::As,
+        Token::Ident("header"),
+        Token::From,
+        Token::Ident("file_header"),
+        Token::Eof,
+    ];
+    let input = Tokens::new(&input);
+    let output = parse_tokens(input);
+    let expected: Program = vec![
+        Statement::StartGroupDefinition("file".into()),
+        Statement::DefineRefField("file_header".into(), Some("header".into())),
+        Statement::Run,
+    ]
+    .into();
+
+    assert_eq!(output, Ok(expected));
+}
+
+#[test]
+fn define_sequence() {
+    let input = vec![
+        Token::Sequence,
+        Token::Ident("session"),
+        Token::Ident("login"),
+        Token::Ident("command"),
+        Token::RightArrow,
+        Token::Ident("REPEAT"),
+        Token::LeftParen,
+        Token::IntegerLiteral(1),
+        Token::IntegerLiteral(5),
+        Token::RightParen,
+        Token::Comma,
+        Token::Ident("quit"),
+        Token::Generate,
+        Token::Ident("session"),
+        Token::Eof,
+    ];
+    let input = Tokens::new(&input);
+    let output = parse_tokens(input);
+    let expected: Program = vec![
+        Statement::StartSequenceDefinition("session".into()),
+        Statement::DefineStep("login".into(), None),
+        Statement::DefineStep("command".into(), None),
+        Statement::UpdateField(
+            "REPEAT".into(),
+            Expr::Group(vec![
+                Expr::LiteralExpr(Literal::IntegerLiteral(1)),
+                Expr::LiteralExpr(Literal::IntegerLiteral(5)),
+            ]),
+        ),
+        Statement::DefineStep("quit".into(), None),
+        Statement::StartGeneratorDefinition("session".into()),
+        Statement::Run,
+    ]
+    .into();
+
+    assert_eq!(output, Ok(expected));
+}
+
+#[test]
+fn define_captured_field() {
+    let input = vec![
+        Token::Define,
+        Token::Ident("command"),
+        Token::StringLiteral("CMD "),
+        Token::Reference,
+        Token::Ident("session_id"),
+        Token::Reference,
+        Token::Ident("nonce"),
+        Token::As,
+        Token::Ident("n"),
+        Token::Eof,
+    ];
+    let input = Tokens::new(&input);
+    let output = parse_tokens(input);
+    let expected: Program = vec![
+        Statement::StartGroupDefinition("command".into()),
+        Statement::DefineConstField(
+            Literal::StringLiteral("CMD ".to_owned()),
+            None,
+        ),
+        Statement::DefineCapturedField("session_id".into(), None),
+        Statement::DefineCapturedField("nonce".into(), Some("n".into())),
+        Statement::Run,
+    ]
+    .into();
+
+    assert_eq!(output, Ok(expected));
+}
+
+#[test]
+fn import() {
+    let input = vec![
+        Token::Import,
+        Token::StringLiteral("common/tlv.fuzl"),
+        Token::Define,
+        Token::Ident("msg"),
+        Token::Type("ref"),
+        Token::From,
+        Token::Ident("tlv::record"),
+        Token::Eof,
+    ];
+    let output = parse_tokens(Tokens::new(&input));
+
+    let expected: Program = vec![
+        Statement::Import("common/tlv.fuzl".to_owned()),
+        Statement::StartGroupDefinition("msg".into()),
+        Statement::DefineRefField("tlv::record".into(), None),
+        Statement::Run,
+    ]
+    .into();
+    assert_eq!(output, Ok(expected));
+    assert!(parse_tokens(Tokens::new(&[
+        Token::Import,
+        Token::Ident("tlv"),
+        Token::Eof
+    ]))
+    .is_err());
+}
+
+#[test]
+fn declarations() {
+    let input = vec![
+        Token::Const,
+        Token::Ident("SIZE"),
+        Token::Assign,
+        Token::IntegerLiteral(40),
+        Token::Enum,
+        Token::Ident("kind"),
+        Token::LeftBrace,
+        Token::Ident("A"),
+        Token::Assign,
+        Token::IntegerLiteral(0),
+        Token::Comma,
+        Token::Ident("B"),
+        Token::Assign,
+        Token::Ident("SIZE"),
+        Token::Comma,
+        Token::RightBrace,
+        Token::Define,
+        Token::Ident("msg"),
+        Token::Type("u8"),
+        Token::As,
+        Token::Ident("x"),
+        Token::RightArrow,
+        Token::Enum,
+        Token::LeftParen,
+        Token::Ident("kind"),
+        Token::RightParen,
+        Token::Comma,
+        Token::Eof,
+    ];
+    let output = parse_tokens(Tokens::new(&input));
+
+    let expected: Program = vec![
+        Statement::DefineConst(
+            "SIZE".into(),
+            Expr::LiteralExpr(Literal::IntegerLiteral(40)),
+        ),
+        Statement::DefineEnum(
+            "kind".into(),
+            vec![
+                ("A".into(), Expr::LiteralExpr(Literal::IntegerLiteral(0))),
+                ("B".into(), Expr::IdentExpr("SIZE".into())),
+            ],
+        ),
+        Statement::StartGroupDefinition("msg".into()),
+        Statement::DefineVariableField("u8".to_owned(), Some("x".into())),
+        Statement::MakeCurrentField("x".into()),
+        Statement::UpdateField("ENUM".into(), Expr::IdentExpr("kind".into())),
+        Statement::Run,
+    ]
+    .into();
+    assert_eq!(output, Ok(expected));
+    assert!(parse_tokens(Tokens::new(&[
+        Token::Enum,
+        Token::Ident("kind"),
+        Token::LeftBrace,
+        Token::RightBrace,
+        Token::Eof
+    ]))
+    .is_err());
+}
+
+#[test]
+fn templates() {
+    let input = vec![
+        Token::Define,
+        Token::Ident("tlv"),
+        Token::LeftParen,
+        Token::Ident("T"),
+        Token::Comma,
+        Token::Ident("max"),
+        Token::RightParen,
+        Token::Ident("T"),
+        Token::As,
+        Token::Ident("tag"),
+        Token::Define,
+        Token::Ident("file"),
+        Token::Type("ref"),
+        Token::As,
+        Token::Ident("rec"),
+        Token::From,
+        Token::Ident("tlv"),
+        Token::LeftParen,
+        Token::Type("le_u16"),
+        Token::Comma,
+        Token::IntegerLiteral(255),
+        Token::RightParen,
+        Token::Eof,
+    ];
+    let output = parse_tokens(Tokens::new(&input));
+
+    let expected: Program = vec![
+        Statement::StartTemplateDefinition(
+            "tlv".into(),
+            vec!["T".into(), "max".into()],
+        ),
+        Statement::DefineParamField("T".into(), Some("tag".into())),
+        Statement::StartGroupDefinition("file".into()),
+        Statement::DefineTemplateRefField(
+            "tlv".into(),
+            vec![
+                Expr::IdentExpr("le_u16".into()),
+                Expr::LiteralExpr(Literal::IntegerLiteral(255)),
+            ],
+            Some("rec".into()),
+        ),
+        Statement::Run,
+    ]
+    .into();
+    assert_eq!(output, Ok(expected));
+    // Parameters used as types need an alias
+    assert!(parse_tokens(Tokens::new(&[
+        Token::Define,
+        Token::Ident("tlv"),
+        Token::LeftParen,
+        Token::Ident("T"),
+        Token::RightParen,
+        Token::Ident("T"),
+        Token::Eof
+    ]))
+    .is_err());
+}
@@ -0,0 +1,130 @@
+use crate::runner::basics::temp_dir;
+use bajzel_lib::{
+    generator::Gen,
+    repl::{Flow, Repl},
+};
+use pretty_assertions::assert_eq;
+
+const SOURCE: &str = "\
+DEFINE login
+    \"LOGIN \"
+    string AS user -> LEN(3 8),
+
+DEFINE command
+    \"CMD \"
+    u8  AS id
+    ref AS body FROM login
+WHERE
+    id -> RANGE(1 16),
+
+GENERATE command
+";
+
+fn repl() -> Repl {
+    Repl::new(SOURCE, "", Gen::default()).unwrap()
+}
+
+/// Output of given commands, one per line
+///
+fn execute(repl: &mut Repl, commands: &str) -> String {
+    let mut output = vec![];
+    for line in commands.lines() {
+        repl.execute(line, &mut output).unwrap();
+    }
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn generate_from_groups() {
+    let mut repl = repl();
+    let output = execute(&mut repl, "gen 3\ngen login 2");
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[..3].iter().all(|x| x.starts_with("CMD ")));
+    assert!(lines[3..].iter().all(|x| x.starts_with("LOGIN ")));
+}
+
+#[test]
+fn seed_repeats_inputs() {
+    let mut repl = repl();
+    let first = execute(&mut repl, "set seed 7\ngen 2");
+    let again = execute(&mut repl, "set seed 7\ngen\ngen");
+    assert_eq!(first, again);
+    assert_ne!(first, execute(&mut repl, "gen 2"));
+}
+
+#[test]
+fn show_definitions() {
+    let mut repl = repl();
+    let output = execute(&mut repl, "show group login");
+    assert!(output.starts_with("group login\n"));
+    assert!(output.contains("user"));
+    assert!(!output.contains("command"));
+    assert!(repl.execute("show sequence nope", &mut vec![]).is_err());
+}
+
+#[test]
+fn redefine_field() {
+    let mut repl = repl();
+    execute(&mut repl, "redefine field login.user \"bob\" AS user");
+    let output = execute(&mut repl, "gen 4");
+    assert!(output.lines().all(|x| x.ends_with("LOGIN bob")));
+    assert!(
+        execute(&mut repl, "show source").contains("\n    \"bob\" AS user\n")
+    );
+}
+
+#[test]
+fn redefine_keeps_program_on_error() {
+    let mut repl = repl();
+    let before = execute(&mut repl, "show source");
+    assert!(repl
+        .execute("redefine field login.user u8 AS", &mut vec![])
+        .is_err());
+    assert!(repl
+        .execute("redefine field login.nope u8 AS nope", &mut vec![])
+        .is_err());
+    assert_eq!(execute(&mut repl, "show source"), before);
+}
+
+#[test]
+fn redefine_keeps_program_on_unknown_where_field() {
+    let mut repl = repl();
+    let before = execute(&mut repl, "show source\nset seed 3\ngen 2");
+    let err = repl
+        .execute("redefine field command.id u8 AS num", &mut vec![])
+        .unwrap_err();
+    assert!(err.to_string().contains("unknown field id"), "{}", err);
+    assert_eq!(execute(&mut repl, "show source\nset seed 3\ngen 2"), before);
+}
+
+#[test]
+fn reload_keeps_program_on_error() {
+    let dir = temp_dir("repl-reload");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("prog.bz");
+    std::fs::write(&path, SOURCE).unwrap();
+    let mut repl = Repl::load(&path, Gen::default()).unwrap();
+    let before = execute(&mut repl, "set seed 3\ngen 2");
+
+    std::fs::write(&path, SOURCE.replace("id -> RANGE", "nid -> RANGE"))
+        .unwrap();
+    let err = repl.execute("reload", &mut vec![]).unwrap_err();
+    assert!(err.to_string().contains("unknown field nid"), "{}", err);
+    assert_eq!(execute(&mut repl, "set seed 3\ngen 2"), before);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn session() {
+    let mut repl = repl();
+    let mut input =
+        "bogus\nset format hexdump\ngen login\nquit\ngen\n".as_bytes();
+    let mut output = vec![];
+    repl.run(&mut input, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("> [-] syntax error: unknown command (bogus)"));
+    assert!(output.contains("4c 4f 47 49 4e 20"));
+    assert!(output.ends_with("> "));
+    assert_eq!(repl.execute("exit", &mut vec![]).unwrap(), Flow::Quit);
+}
@@ -0,0 +1,168 @@
+use crate::evaluate_str;
+use bajzel_lib::generator::Gen;
+use bajzel_lib::runner::{
+    crash::CrashStore, Crash, Delivery, Outcome, Runner, Target,
+};
+use pretty_assertions::assert_eq;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+pub fn sh(script: &str) -> Target {
+    Target::new(&["sh", "-c", script, "sh", "@@"]).unwrap()
+}
+
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "bajzel-test-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn outcomes() {
+    assert_eq!(sh("exit 0").run(b"").unwrap(), Outcome::Exited(0));
+    assert_eq!(sh("exit 3").run(b"").unwrap(), Outcome::Exited(3));
+    assert_eq!(
+        sh("kill -SEGV $$").run(b"").unwrap(),
+        Outcome::Crashed(Crash::Signal(11))
+    );
+
+    let mut target = sh("exit 3");
+    target.add_crash_code(3);
+    assert_eq!(
+        target.run(b"").unwrap(),
+        Outcome::Crashed(Crash::ExitCode(3))
+    );
+
+    let mut target = sh("sleep 5");
+    target.set_timeout(Duration::from_millis(50));
+    assert_eq!(target.run(b"").unwrap(), Outcome::TimedOut);
+}
+
+#[test]
+fn deliveries() {
+    let target = sh(r#"read x; [ "$x" = abc ]"#);
+    assert_eq!(target.run(b"abc\n").unwrap(), Outcome::Exited(0));
+    assert_eq!(target.run(b"abd\n").unwrap(), Outcome::Exited(1));
+
+    let dir = temp_dir("deliveries");
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut target = sh(r#"[ "$(cat "$1")" = abc ]"#);
+    target.set_delivery(Delivery::File(dir.join("input")));
+    assert_eq!(target.run(b"abc").unwrap(), Outcome::Exited(0));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let mut target = sh(r#"[ "$1" = abc ]"#);
+    target.set_delivery(Delivery::Argv);
+    assert_eq!(target.run(b"abc").unwrap(), Outcome::Exited(0));
+    assert_eq!(target.run(b"abc\0def").unwrap(), Outcome::Exited(0));
+}
+
+#[test]
+fn same_seed_same_input() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            i32 AS a
+            ":"
+            string AS b -> LEN(0 20),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let mut gen = Gen::default();
+    gen.set_seed(42);
+    let first = gen.generate(&env).unwrap();
+    gen.set_seed(42);
+    assert_eq!(gen.generate(&env).unwrap(), first);
+}
+
+#[test]
+fn gen_and_run_same_inputs() {
+    let dir = temp_dir("gen-run");
+    std::fs::create_dir_all(&dir).unwrap();
+    let program = dir.join("cmd.fuzl");
+    std::fs::write(
+        &program,
+        "DEFINE cmd\n    u8 AS a\n    string AS b -> LEN(0 4),\n\
+        GENERATE cmd WITH\n    TERM = LF\n",
+    )
+    .unwrap();
+    let bajzel = || Command::new(env!("CARGO_BIN_EXE_bajzel"));
+    let generated = bajzel()
+        .args(["gen", "-s", "7", "-n", "3"])
+        .arg(&program)
+        .output()
+        .unwrap();
+    assert!(generated.status.success());
+
+    let received = dir.join("received");
+    let status = bajzel()
+        .args(["run", "-s", "7", "-n", "3", "-c"])
+        .arg(dir.join("crashes"))
+        .arg(&program)
+        .args(["--", "sh", "-c", "cat >> \"$0\""])
+        .arg(&received)
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(std::fs::read(&received).unwrap(), generated.stdout);
+    assert!(generated.stdout.ends_with(b"\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crashes_saved_with_seed() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u8 AS a -> RANGE(0 9),
+        GENERATE cmd
+            TERM = LF
+        "#,
+    )
+    .unwrap();
+    let dir = temp_dir("crashes");
+    let store = CrashStore::open(&dir).unwrap();
+    let target = sh(r#"read x; [ "$x" -lt 5 ] || kill -SEGV $$"#);
+    let source = PathBuf::from("cmd.fuzl");
+    let mut runner =
+        Runner::new(Gen::default(), Box::new(target), store, source, 100);
+    for _ in 0..20 {
+        runner.step(&env).unwrap();
+    }
+    let stats = runner.stats();
+    assert_eq!(stats.executions, 20);
+    assert!(stats.crashes > 0);
+
+    let mut saved = std::fs::read_dir(dir.join("sig-11"))
+        .unwrap()
+        .map(|x| x.unwrap().path())
+        .filter(|x| x.extension().is_none())
+        .collect::<Vec<_>>();
+    saved.sort();
+    assert_eq!(saved.len() as u64, stats.crashes);
+
+    let name = saved[0].file_name().unwrap().to_str().unwrap().to_owned();
+    let seed: u64 = name.split(',').nth(1).unwrap()[5..].parse().unwrap();
+    let meta = std::fs::read_to_string(saved[0].with_extension("meta"));
+    assert_eq!(
+        meta.unwrap(),
+        format!(
+            "seed = {}\nsource = cmd.fuzl\ncrash = sig-11\ngenerated = seed\n",
+            seed
+        )
+    );
+
+    let mut gen = Gen::default();
+    gen.set_seed(seed);
+    let mut input = gen.generate(&env).unwrap();
+    input.push(b'\n');
+    assert_eq!(std::fs::read(&saved[0]).unwrap(), input);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
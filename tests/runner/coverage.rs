@@ -0,0 +1,155 @@
+use super::basics::{sh, temp_dir};
+use crate::evaluate_str;
+use bajzel_lib::error::BajzelError;
+use bajzel_lib::generator::Gen;
+use bajzel_lib::runner::{
+    coverage::{Feedback, MAP_SIZE},
+    crash::{saved_inputs, CrashRecord, CrashStore, Generation},
+    Crash, Executor, Outcome, Runner,
+};
+use pretty_assertions::assert_eq;
+use std::path::PathBuf;
+
+/// Target pretending to be instrumented: every input length is an edge
+///
+struct LengthCoverage {
+    trace: Vec<u8>,
+}
+
+impl Executor for LengthCoverage {
+    fn execute(&mut self, input: &[u8]) -> Result<Outcome, BajzelError> {
+        self.trace.fill(0);
+        self.trace[input.len() % MAP_SIZE] = 1;
+        Ok(Outcome::Exited(0))
+    }
+
+    fn coverage(&self) -> Option<&[u8]> {
+        Some(&self.trace)
+    }
+}
+
+#[test]
+fn new_coverage_kept() {
+    let mut feedback = Feedback::default();
+    let mut trace = vec![0; MAP_SIZE];
+    trace[10] = 1;
+    assert!(feedback.update(&trace, vec![1]));
+    assert!(!feedback.update(&trace, vec![2]));
+
+    // Hit counts in the same class are not new
+    trace[10] = 2;
+    assert!(feedback.update(&trace, vec![3]));
+    trace[10] = 5;
+    assert!(feedback.update(&trace, vec![4]));
+    trace[10] = 6;
+    assert!(!feedback.update(&trace, vec![5]));
+
+    trace[20] = 1;
+    assert!(feedback.update(&trace, vec![6]));
+    assert_eq!(feedback.corpus_len(), 4);
+    assert_eq!(feedback.edges(), 2);
+}
+
+#[test]
+fn entropy_depends_on_seed() {
+    let mut feedback = Feedback::default();
+    assert_eq!(feedback.next_entropy(1), feedback.next_entropy(1));
+    assert_ne!(feedback.next_entropy(1), feedback.next_entropy(2));
+
+    let mut trace = vec![0; MAP_SIZE];
+    trace[0] = 1;
+    feedback.update(&trace, vec![7; 32]);
+    assert_eq!(feedback.next_entropy(3), feedback.next_entropy(3));
+}
+
+#[test]
+fn runner_builds_corpus() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            string AS name  -> LEN(0 32),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let dir = temp_dir("coverage");
+    let store = CrashStore::open(&dir).unwrap();
+    let target = LengthCoverage {
+        trace: vec![0; MAP_SIZE],
+    };
+    let mut runner = Runner::new(
+        Gen::default(),
+        Box::new(target),
+        store,
+        PathBuf::from("cmd.fuzl"),
+        0,
+    );
+    runner.enable_feedback();
+    for _ in 0..200 {
+        runner.step(&env).unwrap();
+    }
+    let stats = runner.stats();
+    assert!(stats.corpus > 1, "{:?}", stats);
+    assert_eq!(stats.corpus, stats.edges);
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+/// Instrumented target crashing on every input
+///
+struct AlwaysCrashes {
+    trace: Vec<u8>,
+}
+
+impl Executor for AlwaysCrashes {
+    fn execute(&mut self, _input: &[u8]) -> Result<Outcome, BajzelError> {
+        Ok(Outcome::Crashed(Crash::Signal(11)))
+    }
+
+    fn coverage(&self) -> Option<&[u8]> {
+        Some(&self.trace)
+    }
+}
+
+#[test]
+fn feedback_crashes_marked() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            string AS name  -> LEN(0 32),
+        GENERATE cmd
+        "#,
+    )
+    .unwrap();
+    let dir = temp_dir("coverage-crashes");
+    let store = CrashStore::open(&dir).unwrap();
+    let target = AlwaysCrashes {
+        trace: vec![0; MAP_SIZE],
+    };
+    let mut runner = Runner::new(
+        Gen::default(),
+        Box::new(target),
+        store,
+        PathBuf::from("cmd.fuzl"),
+        5,
+    );
+    runner.enable_feedback();
+    runner.step(&env).unwrap();
+
+    let metas = saved_inputs(&dir).unwrap();
+    assert_eq!(metas.len(), 1);
+    let name = metas[0].file_name().unwrap().to_str().unwrap();
+    assert_eq!(name, "id-000000,feedback-5,sig-11.meta");
+    let record: CrashRecord =
+        std::fs::read_to_string(&metas[0]).unwrap().parse().unwrap();
+    assert_eq!(record.generated, Generation::Feedback);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn map_passed_to_target() {
+    let mut target = sh(r#"[ -n "$__AFL_SHM_ID" ] || exit 3"#);
+    assert_eq!(target.run(b"").unwrap(), Outcome::Exited(3));
+    target.enable_coverage().unwrap();
+    assert_eq!(target.run(b"").unwrap(), Outcome::Exited(0));
+    assert_eq!(target.coverage(), Some(&[0; MAP_SIZE][..]));
+}
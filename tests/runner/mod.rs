@@ -0,0 +1,5 @@
+pub mod basics;
+pub mod coverage;
+pub mod net;
+pub mod replay;
+pub mod triage;
@@ -0,0 +1,173 @@
+use crate::evaluate_str;
+use bajzel_lib::generator::Gen;
+use bajzel_lib::runner::{
+    crash::CrashStore, net::NetTarget, Crash, Executor, Outcome, Runner,
+};
+use pretty_assertions::assert_eq;
+use std::io::{Read, Write};
+use std::net::{TcpListener, UdpSocket};
+use std::path::PathBuf;
+use std::thread;
+
+/// Port nothing listens on
+///
+fn closed_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[test]
+fn tcp_reply() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0; 16];
+        let len = stream.read(&mut buf).unwrap();
+        stream.write_all(&buf[..len].to_ascii_uppercase()).unwrap();
+    });
+
+    let mut target =
+        NetTarget::from_url(&format!("tcp://127.0.0.1:{}", port)).unwrap();
+    target.set_read_reply(true);
+    assert_eq!(
+        target.send(b"ping\n").unwrap(),
+        Outcome::Delivered(Some(b"PING\n".to_vec()))
+    );
+    server.join().unwrap();
+}
+
+#[test]
+fn tcp_session_over_single_connection() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        for (len, reply) in [(6, b"OK 1"), (4, b"OK 2")] {
+            let mut buf = vec![0; len];
+            stream.read_exact(&mut buf).unwrap();
+            stream.write_all(reply).unwrap();
+        }
+    });
+
+    let mut target =
+        NetTarget::from_url(&format!("tcp://127.0.0.1:{}", port)).unwrap();
+    target.set_read_reply(true);
+    let messages = vec![b"LOGIN\n".to_vec(), b"CMD\n".to_vec()];
+    assert_eq!(
+        target.send_session(&messages).unwrap(),
+        Outcome::Delivered(Some(b"OK 2".to_vec()))
+    );
+    server.join().unwrap();
+}
+
+#[test]
+fn udp_reply() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let mut buf = [0; 16];
+        let (len, peer) = socket.recv_from(&mut buf).unwrap();
+        socket.send_to(&buf[..len], peer).unwrap();
+    });
+
+    let mut target =
+        NetTarget::from_url(&format!("udp://127.0.0.1:{}", port)).unwrap();
+    target.set_read_reply(true);
+    assert_eq!(
+        target.send(b"ping").unwrap(),
+        Outcome::Delivered(Some(b"ping".to_vec()))
+    );
+    server.join().unwrap();
+}
+
+#[test]
+fn refused() {
+    let url = format!("tcp://127.0.0.1:{}", closed_port());
+    let mut target = NetTarget::from_url(&url).unwrap();
+    assert_eq!(target.send(b"").unwrap(), Outcome::Crashed(Crash::Refused));
+
+    assert!(NetTarget::from_url("http://127.0.0.1:80").is_err());
+}
+
+#[test]
+fn previous_input_blamed() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u32 AS a
+        GENERATE cmd
+            TERM = LF
+        "#,
+    )
+    .unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut input = vec![];
+        stream.read_to_end(&mut input).unwrap();
+        input
+    });
+
+    let dir = std::env::temp_dir()
+        .join(format!("bajzel-test-refused-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let store = CrashStore::open(&dir).unwrap();
+    let target =
+        NetTarget::from_url(&format!("tcp://127.0.0.1:{}", port)).unwrap();
+    let source = PathBuf::from("cmd.fuzl");
+    let mut runner =
+        Runner::new(Gen::default(), Box::new(target), store, source, 7);
+
+    assert_eq!(runner.step(&env).unwrap(), Outcome::Delivered(None));
+    let received = server.join().unwrap();
+    assert_eq!(runner.step(&env).unwrap(), Outcome::Crashed(Crash::Refused));
+    assert_eq!(
+        std::fs::read(dir.join("refused/id-000000,seed-7,refused")).unwrap(),
+        received
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn tcp_session_captures_reply() {
+    let env = evaluate_str(
+        r#"
+        DEFINE login
+            "LOGIN"
+        DEFINE command
+            "CMD " $sid
+        SEQUENCE session
+            login -> CAPTURE(sid "SID=([a-z]+)"),
+            command
+        GENERATE session
+            TERM = LF
+        "#,
+    )
+    .unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut login = [0; 6];
+        stream.read_exact(&mut login).unwrap();
+        stream.write_all(b"OK SID=abc\n").unwrap();
+        let mut command = vec![];
+        stream.read_to_end(&mut command).unwrap();
+        command
+    });
+
+    let gen = Gen::default();
+    let mut session = gen.start_session(&env).unwrap();
+    let mut target =
+        NetTarget::from_url(&format!("tcp://127.0.0.1:{}", port)).unwrap();
+    assert_eq!(
+        target.execute_session(&mut session).unwrap(),
+        Outcome::Delivered(Some(b"OK SID=abc\n".to_vec()))
+    );
+    assert_eq!(server.join().unwrap(), b"CMD abc\n");
+}
@@ -0,0 +1,143 @@
+use super::basics::{sh, temp_dir};
+use bajzel_lib::generator::Gen;
+use bajzel_lib::runner::{
+    crash::{CrashRecord, CrashStore, Generation},
+    replay::{replay, Verdict},
+    Crash,
+};
+use pretty_assertions::assert_eq;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const SCRIPT: &str = r#"
+    read x
+    case "$x" in
+        crash) kill -SEGV $$ ;;
+        hang) sleep 5 ;;
+    esac
+"#;
+
+fn record(seed: u64, source: PathBuf) -> CrashRecord {
+    CrashRecord {
+        seed,
+        source,
+        crash: Crash::Signal(11),
+        generated: Generation::Seed,
+        dicts: vec![],
+    }
+}
+
+#[test]
+fn verdicts() {
+    let dir = temp_dir("replay");
+    let mut store = CrashStore::open(&dir).unwrap();
+    let source = PathBuf::from("cmd.fuzl");
+    store
+        .save(b"crash\n", &record(1, source.clone()), "sig-11")
+        .unwrap();
+    store
+        .save(b"fine\n", &record(2, source.clone()), "sig-11")
+        .unwrap();
+    store.save(b"hang\n", &record(3, source), "sig-11").unwrap();
+
+    let mut target = sh(SCRIPT);
+    target.set_timeout(Duration::from_millis(100));
+    let report = replay(&dir, &mut target, &mut Gen::default(), &[]).unwrap();
+    let verdicts: Vec<_> = report.inputs.iter().map(|x| x.verdict).collect();
+    assert_eq!(
+        verdicts,
+        vec![
+            Verdict::Crashes(Crash::Signal(11)),
+            Verdict::Fixed,
+            Verdict::TimedOut
+        ]
+    );
+    assert!(report.has_regressions());
+    assert!(report.inputs.iter().all(|x| !x.regenerated));
+    assert!(report.to_string().ends_with(
+        "1 still crash, 1 fixed, 1 timed out, 0 not reproducible\n"
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn regenerated_from_seed() {
+    let dir = temp_dir("replay-regenerate");
+    std::fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("cmd.fuzl");
+    std::fs::write(
+        &source,
+        r#"
+        DEFINE cmd
+            "crash"
+        GENERATE cmd
+            TERM = LF
+        "#,
+    )
+    .unwrap();
+    let crashes = dir.join("crashes");
+    let mut store = CrashStore::open(&crashes).unwrap();
+    let input = store.save(b"", &record(7, source), "sig-11").unwrap();
+    std::fs::remove_file(input).unwrap();
+
+    let report = replay(&crashes, &mut sh(SCRIPT), &mut Gen::default(), &[]);
+    let report = report.unwrap();
+    assert_eq!(report.inputs.len(), 1);
+    assert!(report.inputs[0].regenerated);
+    assert_eq!(
+        report.inputs[0].verdict,
+        Verdict::Crashes(Crash::Signal(11))
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn not_regenerated() {
+    let dir = temp_dir("replay-not-reproducible");
+    std::fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("cmd.fuzl");
+    std::fs::write(&source, "DEFINE cmd\n    \"crash\"\nGENERATE cmd\n")
+        .unwrap();
+    let crashes = dir.join("crashes");
+    let mut store = CrashStore::open(&crashes).unwrap();
+    let feedback = CrashRecord {
+        generated: Generation::Feedback,
+        ..record(7, source.clone())
+    };
+    let with_dict = CrashRecord {
+        dicts: vec![PathBuf::from("http.dict")],
+        ..record(8, source)
+    };
+    for record in [feedback, with_dict] {
+        let input = store.save(b"", &record, "sig-11").unwrap();
+        std::fs::remove_file(input).unwrap();
+    }
+
+    let report = replay(&crashes, &mut sh(SCRIPT), &mut Gen::default(), &[]);
+    let report = report.unwrap();
+    let verdicts: Vec<_> = report.inputs.iter().map(|x| x.verdict).collect();
+    assert_eq!(
+        verdicts,
+        vec![
+            Verdict::NotReproducible("generated with coverage feedback"),
+            Verdict::NotReproducible("generated with other dictionaries"),
+        ]
+    );
+    assert!(report.inputs.iter().all(|x| !x.regenerated));
+    assert!(!report.has_regressions());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn all_fixed() {
+    let dir = temp_dir("replay-fixed");
+    let mut store = CrashStore::open(&dir).unwrap();
+    let source = PathBuf::from("cmd.fuzl");
+    store.save(b"fine\n", &record(1, source), "sig-11").unwrap();
+
+    let report =
+        replay(&dir, &mut sh(SCRIPT), &mut Gen::default(), &[]).unwrap();
+    assert_eq!(report.inputs[0].verdict, Verdict::Fixed);
+    assert!(!report.has_regressions());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,142 @@
+use super::basics::{sh, temp_dir};
+use crate::evaluate_str;
+use bajzel_lib::generator::Gen;
+use bajzel_lib::runner::{
+    crash::{CrashRecord, CrashStore, Generation},
+    triage::{bucket, stack_frames, summarize},
+    Crash, Runner,
+};
+use pretty_assertions::assert_eq;
+use std::path::PathBuf;
+
+const ASAN_REPORT: &str = "\
+=================================================================
+==1234==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011
+READ of size 1 at 0x602000000011 thread T0
+    #0 0x4f5e3a in __asan_memcpy (/tmp/target+0x4f5e3a)
+    #1 0x51a2b0 in parse_header /src/parser.c:42:7
+    #2 0x51a5c1 in parse_file /src/parser.c:108:3
+    #3 0x51a7f2 in main /src/main.c:20:5
+    #4 0x7f12a3c in (/lib/x86_64-linux-gnu/libc.so.6+0x29d8f)
+
+0x602000000011 is located 0 bytes after 1-byte region
+allocated by thread T0 here:
+    #0 0x4c1d2e in malloc (/tmp/target+0x4c1d2e)
+    #1 0x51a1f0 in read_input /src/input.c:10:12
+";
+
+#[test]
+fn asan_frames() {
+    assert_eq!(
+        stack_frames(ASAN_REPORT.as_bytes()),
+        vec!["parse_header", "parse_file", "main"]
+    );
+}
+
+#[test]
+fn ubsan_location() {
+    let report = "parser.c:12:5: runtime error: signed integer overflow\n";
+    assert_eq!(stack_frames(report.as_bytes()), vec!["parser.c:12"]);
+}
+
+#[test]
+fn buckets() {
+    assert_eq!(bucket(Crash::Signal(11), b""), "sig-11");
+    assert_eq!(bucket(Crash::Signal(11), b"Segmentation fault\n"), "sig-11");
+
+    let asan = bucket(Crash::Signal(6), ASAN_REPORT.as_bytes());
+    assert!(asan.starts_with("sig-6-"), "{}", asan);
+    assert_eq!(asan.len(), "sig-6-".len() + 8);
+
+    // Addresses and other traces don't matter
+    let other_run = ASAN_REPORT.replace("0x51a", "0x71b").replace("10:12", "");
+    assert_eq!(bucket(Crash::Signal(6), other_run.as_bytes()), asan);
+    let other_bug = ASAN_REPORT.replace("parse_file", "parse_body");
+    assert_ne!(bucket(Crash::Signal(6), other_bug.as_bytes()), asan);
+}
+
+#[test]
+fn crashes_bucketed() {
+    let env = evaluate_str(
+        r#"
+        DEFINE cmd
+            u8 AS a -> RANGE(0 9),
+        GENERATE cmd
+            TERM = LF
+        "#,
+    )
+    .unwrap();
+    let dir = temp_dir("triage");
+    let store = CrashStore::open(&dir).unwrap();
+    let script = r#"
+        read x
+        [ "$x" -lt 5 ] && exit 0
+        echo "    #0 0x1 in handle_$x /src/a.c:1:1" >&2
+        kill -ABRT $$
+    "#;
+    let mut runner = Runner::new(
+        Gen::default(),
+        Box::new(sh(script)),
+        store,
+        PathBuf::from("cmd.fuzl"),
+        0,
+    );
+    for _ in 0..50 {
+        runner.step(&env).unwrap();
+    }
+
+    let summary = summarize(&dir).unwrap();
+    assert!(summary.buckets.len() > 1, "{}", summary);
+    let total: usize = summary.buckets.iter().map(|x| x.count).sum();
+    assert_eq!(total as u64, runner.stats().crashes);
+    for bucket in &summary.buckets {
+        assert!(bucket.bucket.starts_with("sig-6-"), "{}", bucket.bucket);
+        assert_eq!(bucket.smallest.parent().unwrap(), dir.join(&bucket.bucket));
+        assert_eq!(bucket.size, 2);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn smallest_reproducer() {
+    let dir = temp_dir("summary");
+    let mut store = CrashStore::open(&dir).unwrap();
+    let record = |seed| CrashRecord {
+        seed,
+        source: PathBuf::from("cmd.fuzl"),
+        crash: Crash::Signal(11),
+        generated: Generation::Seed,
+        dicts: vec![],
+    };
+    store.save(b"long input", &record(1), "sig-11").unwrap();
+    let smallest = store.save(b"short", &record(2), "sig-11").unwrap();
+    store.save(b"other", &record(3), "sig-6-00000000").unwrap();
+
+    let summary = summarize(&dir).unwrap();
+    assert_eq!(summary.buckets.len(), 2);
+    let sig11 = &summary.buckets[0];
+    assert_eq!(sig11.bucket, "sig-11");
+    assert_eq!(sig11.count, 2);
+    assert_eq!(sig11.smallest, smallest);
+    assert_eq!(sig11.size, 5);
+    assert_eq!(sig11.seed, 2);
+
+    // Numbering continues across buckets
+    let store = CrashStore::open(&dir);
+    let path = store.unwrap().save(b"x", &record(4), "sig-11").unwrap();
+    assert!(path.ends_with("sig-11/id-000003,seed-4,sig-11"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn records_parsed() {
+    let record = CrashRecord {
+        seed: 1234,
+        source: PathBuf::from("examples/example1.fuzl"),
+        crash: Crash::ExitCode(3),
+        generated: Generation::Feedback,
+        dicts: vec![PathBuf::from("a.dict"), PathBuf::from("b.dict")],
+    };
+    assert_eq!(record.to_string().parse::<CrashRecord>(), Ok(record));
+    assert!("seed = x\n".parse::<CrashRecord>().is_err());
+}
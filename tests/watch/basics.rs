@@ -0,0 +1,105 @@
+use crate::runner::basics::temp_dir;
+use bajzel_lib::{evaluator::evaluate_file, watch::Watcher};
+use pretty_assertions::assert_eq;
+use std::fs;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn modified_files() {
+    let dir = temp_dir("watch");
+    fs::create_dir_all(&dir).unwrap();
+    let (a, b) = (dir.join("a.fuzl"), dir.join("b.txt"));
+    fs::write(&a, "x").unwrap();
+    fs::write(&b, "x").unwrap();
+    let mut watcher = Watcher::new([&a, &b, &a]);
+    assert_eq!(watcher.files().count(), 2);
+    assert!(watcher.changed().is_empty());
+
+    // Same modification time (on coarse filesystems), different size
+    fs::write(&b, "xy").unwrap();
+    assert_eq!(watcher.changed(), vec![b.clone()]);
+    assert!(watcher.changed().is_empty());
+
+    fs::remove_file(&a).unwrap();
+    assert_eq!(watcher.wait(Duration::from_millis(10)), vec![a.clone()]);
+    fs::write(&a, "x").unwrap();
+    assert_eq!(watcher.changed(), vec![a]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn program_sources() {
+    let dir = temp_dir("watch-sources");
+    fs::create_dir_all(&dir).unwrap();
+    let program = dir.join("words.fuzl");
+    fs::write(dir.join("words.dict"), "\"GET\"\n\"PUT\"\n").unwrap();
+    fs::write(
+        &program,
+        "DEFINE a\n    string AS word -> DICT(\"words.dict\"),\nGENERATE a\n",
+    )
+    .unwrap();
+    let env = evaluate_file(&program).unwrap();
+    assert_eq!(env.sources(), [program, dir.join("words.dict")]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Read everything a given stream outputs, sending it in chunks
+///
+fn forward<R: Read + Send + 'static>(mut stream: R) -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0; 1024];
+        while let Ok(n @ 1..) = stream.read(&mut buf) {
+            let chunk = String::from_utf8_lossy(&buf[..n]).into_owned();
+            if tx.send(chunk).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Wait until a text appears in a stream
+///
+fn expect_output(rx: &mpsc::Receiver<String>, text: &str) {
+    let mut seen = String::new();
+    while !seen.contains(text) {
+        match rx.recv_timeout(Duration::from_secs(10)) {
+            Ok(chunk) => seen.push_str(&chunk),
+            Err(_) => panic!("{:?} not found in {:?}", text, seen),
+        }
+    }
+}
+
+#[test]
+fn generate_survives_invalid_program() {
+    let dir = temp_dir("watch-generate");
+    fs::create_dir_all(&dir).unwrap();
+    let program = dir.join("prog.fuzl");
+    let source = "DEFINE a\n    \"ONE\"\n    u8 AS x\nWHERE\n    x -> RANGE(49 50),\nGENERATE a\n";
+    fs::write(&program, source).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bajzel"))
+        .args(["gen", "-w", "-s", "1"])
+        .arg(&program)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdout = forward(child.stdout.take().unwrap());
+    let stderr = forward(child.stderr.take().unwrap());
+    expect_output(&stdout, "ONE");
+
+    fs::write(&program, source.replace("x -> ", "nope -> ")).unwrap();
+    expect_output(&stderr, "unknown field nope in WHERE of a");
+
+    fs::write(&program, source.replace("ONE", "TWO")).unwrap();
+    expect_output(&stdout, "TWO");
+    assert!(child.try_wait().unwrap().is_none());
+    child.kill().unwrap();
+    child.wait().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}